@@ -0,0 +1,1776 @@
+use std::sync::Arc;
+
+/// One statement of a block, with the source line it starts on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub number: usize,
+    /// The statement as written (with its block structure normalized), for traces.
+    pub text: String,
+    pub stmt: Stmt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    /// `दर्श(a, *सूची)`
+    Print(Vec<PrintArg>),
+    Assign(String, Expr),
+    /// `a, b = x, y`, or `a, b = सूची` (a single value) to unpack a list.
+    Unpack { targets: Vec<String>, values: Vec<Expr> },
+    /// `ध्रुव name = expr`
+    Const(String, Expr),
+    /// `विलोप name` or `विलोप name[index]`
+    Delete(String, Option<Expr>),
+    /// `प्रतिफल expr`; a bare `प्रतिफल` returns `शून्य`.
+    Return(Expr),
+    /// `विरम` leaves the innermost loop; `विरम name` the loop labeled `name`.
+    Break(Option<String>),
+    /// `दोषं कृ(expr)`
+    Raise(Expr),
+    /// `निश्चय (cond), message`; `text` is the condition as written, the
+    /// message when none is given.
+    Assert { cond: Cond, message: Option<Expr>, text: String },
+    /// `आयात "path"`, with the path still quoted.
+    Import(String),
+    /// A call statement, `name(args)`, whose result is discarded.
+    Call(String, Vec<Arg>),
+    /// Any other expression evaluated for its effect, e.g. `कोश["f"](x)`.
+    Expr(Expr),
+    Help,
+    If { cond: Cond, then: Vec<Line>, otherwise: Option<Vec<Line>> },
+    /// `यावत् (cond):`, or `चिह्न name: यावत् (cond):` to give it a label.
+    While { label: Option<String>, cond: Cond, body: Vec<Line> },
+    /// `कुरु:` with its body, then `यावत् (cond)` on the line after it: the
+    /// body runs before each test of `cond`, so at least once.
+    DoWhile { label: Option<String>, body: Vec<Line>, cond: Cond },
+    /// `चक्र:`, repeating its body until a `विरम` leaves it.
+    Loop { label: Option<String>, body: Vec<Line> },
+    /// `परिभ्रमण var in iterable [यावत् (guard)]`; with several vars
+    /// (`परिभ्रमण i, v in ...`) each item is a list unpacked into them. Like
+    /// `यावत्`, it may be labeled.
+    For { label: Option<String>, vars: Vec<String>, iterable: Expr, guard: Option<Cond>, body: Vec<Line> },
+    /// `प्रयत्न:` ... `दोषे name:` ...
+    Try { body: Vec<Line>, name: Option<String>, handler: Vec<Line> },
+    /// `विकल्प scrutinee:` with its `स्थिति` arms.
+    Match { scrutinee: Expr, arms: Vec<Arm> },
+    /// `कार्य name(params):`. The body is parsed once here and shared by
+    /// every call.
+    Function { name: String, params: Vec<String>, body: Arc<Vec<Line>>, doc: Vec<String>, globals: Vec<String> },
+    /// A bare expression ending REPL input; its value is shown (see
+    /// `parse_interactive`).
+    Echo(Expr),
+    /// A statement that doesn't parse; running it reports the error.
+    Invalid(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrintArg {
+    Value(Expr),
+    /// `*सूची`, with the list expression as written for error messages.
+    Spread(String, Expr),
+}
+
+/// One `स्थिति` arm of a `विकल्प`; `values` is `None` for `अन्यथा`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arm {
+    pub values: Option<Vec<Expr>>,
+    pub body: Vec<Line>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Positional(Expr),
+    /// `name=expr`
+    Keyword(String, Expr),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Null,
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    /// A variable, or a function used as a value.
+    Var(String),
+    List(Vec<Expr>),
+    /// `{"key": value}`; keys must evaluate to strings.
+    Map(Vec<(Expr, Expr)>),
+    Neg(Box<Expr>),
+    /// `+`, `-`, `*`, `/`, `//` or `%`.
+    Arith(&'static str, Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    /// `item मध्ये collection`
+    In(Box<Expr>, Box<Expr>),
+    /// A comparison or logical expression, which gives a boolean.
+    Condition(Box<Cond>),
+    /// `then यदि cond अन्यथा otherwise`; only the chosen branch is evaluated.
+    IfElse { cond: Box<Cond>, then: Box<Expr>, otherwise: Box<Expr> },
+    /// `name(args)`: a builtin, a user function, or a variable holding one.
+    Call(String, Vec<Arg>),
+    /// Calling the value of an expression: `कोश["f"](x)`, `f(1)(2)`.
+    CallValue(Box<Expr>, Vec<Arg>),
+    Index(Box<Expr>, Box<Expr>),
+    /// `target[start:stop:step]`, with two or three bounds.
+    Slice(Box<Expr>, Vec<Expr>),
+    /// Text that doesn't parse. Evaluating it raises the error, so like any
+    /// runtime error it only matters when the expression is reached.
+    Invalid(String),
+}
+
+/// A condition, as tested by `यदि`, `यावत्` and `निश्चय`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cond {
+    /// `a वा b`, short-circuiting.
+    Or(Box<Cond>, Box<Cond>),
+    /// `a तथा b`, short-circuiting.
+    And(Box<Cond>, Box<Cond>),
+    /// `न a`
+    Not(Box<Cond>),
+    /// A chain like `0 < x <= 10`: each operand is evaluated once, stopping
+    /// at the first link that doesn't hold.
+    Compare(Box<Expr>, Vec<(&'static str, Expr)>),
+    /// Any other value, by its truthiness.
+    Truthy(Expr),
+}
+
+/// Parse a program into its statements. Problems are kept as `Invalid`
+/// statements and expressions rather than failing the parse, so a program
+/// still runs up to its first bad statement.
+pub fn parse(src: &str) -> Vec<Line> {
+    parse_block(src, 0)
+}
+
+/// Parse REPL input: like `parse`, but a bare expression or call on the
+/// last line becomes an `Echo`, so `2 + 3` shows `5` instead of being an
+/// unknown command.
+pub fn parse_interactive(src: &str) -> Vec<Line> {
+    let mut program = parse(src);
+    if let Some(last) = program.last_mut() {
+        let expr = match &last.stmt {
+            Stmt::Call(name, args) => Some(Expr::Call(name.clone(), args.clone())),
+            Stmt::Expr(expr) => Some(expr.clone()),
+            Stmt::Invalid(e) if *e == unknown_command(&last.text) => Some(parse_expr(&last.text)),
+            _ => None,
+        };
+        if let Some(expr) = expr {
+            last.stmt = Stmt::Echo(expr);
+        }
+    }
+    program
+}
+
+/// Parse a block whose line `i` (from 0) is line `line_base + i + 1` of the
+/// source file.
+pub(crate) fn parse_block(src: &str, line_base: usize) -> Vec<Line> {
+    let norm = preprocess_indentation(src);
+    let lines: Vec<String> = norm.lines().map(|l| l.to_string()).collect();
+    let mut block = Vec::new();
+    let mut i = 0usize;
+    while i < lines.len() {
+        let text = lines[i].trim();
+        if text.is_empty() || text.starts_with("!!") || text.starts_with('#') {
+            i += 1;
+            continue;
+        }
+        let (stmt, consumed) = match parse_compound(&lines, i, line_base) {
+            Some(Ok(parsed)) => parsed,
+            // A header that doesn't parse takes its block with it.
+            Some(Err(e)) => (Stmt::Invalid(e), skip_block(&lines, i)),
+            None => (parse_statement(text), 1),
+        };
+        block.push(Line {
+            number: line_base + i + 1,
+            text: text.to_string(),
+            stmt,
+        });
+        i += consumed;
+    }
+    block
+}
+
+/// A block statement starting at `lines[start]` and the number of lines it
+/// spans, or `None` if the line doesn't start one.
+fn parse_compound(lines: &[String], start: usize, base: usize) -> Option<Result<(Stmt, usize), String>> {
+    let (label, line) = match split_loop_label(lines[start].trim()) {
+        Ok(split) => split,
+        Err(e) => return Some(Err(e)),
+    };
+    let label = label.map(str::to_string);
+    let parsed = if line == "प्रयत्न" {
+        parse_try(lines, start, base)
+    } else if line.starts_with("दोषे") {
+        Err("त्रुटिः: दोषे प्रयत्न-खण्डं विना".to_string())
+    } else if line.starts_with("अन्यथा") {
+        Err("त्रुटिः: अन्यथा यदि-खण्डं विना".to_string())
+    } else if line.starts_with("स्थिति ") {
+        Err("त्रुटिः: स्थिति विकल्प-खण्डं विना".to_string())
+    } else if line.starts_with("यदि") {
+        parse_if(lines, start, base)
+    } else if line.starts_with("यावत्") {
+        parse_while(line, label, lines, start, base)
+    } else if line.starts_with("परिभ्रमण") {
+        parse_for(line, label, lines, start, base)
+    } else if line == "कुरु" {
+        parse_do_while(label, lines, start, base)
+    } else if line == "चक्र" {
+        collect_block(lines, start)
+            .map(|(body, consumed, first)| (Stmt::Loop { label, body: parse_block(&body, base + first) }, consumed))
+    } else if line.starts_with("विकल्प ") {
+        parse_match(lines, start, base)
+    } else if line.starts_with("कार्य") {
+        parse_function(lines, start, base)
+    } else {
+        return None;
+    };
+    Some(parsed)
+}
+
+fn parse_if(lines: &[String], start: usize, base: usize) -> Result<(Stmt, usize), String> {
+    let cond = parse_condition(paren_condition(lines[start].trim(), "यदि")?);
+    let (then_block, consumed, then_first) = collect_block(lines, start)?;
+    let then = parse_block(&then_block, base + then_first);
+    let else_idx = next_code_line(lines, start + consumed).filter(|&idx| lines[idx].trim().starts_with("अन्यथा"));
+    let Some(idx) = else_idx else {
+        return Ok((Stmt::If { cond, then, otherwise: None }, consumed));
+    };
+    let (else_block, else_consumed, else_first) = collect_block(lines, idx)?;
+    let otherwise = Some(parse_block(&else_block, base + else_first));
+    Ok((Stmt::If { cond, then, otherwise }, idx + else_consumed - start))
+}
+
+/// `header` is the loop's header line without its label.
+fn parse_while(
+    header: &str,
+    label: Option<String>,
+    lines: &[String],
+    start: usize,
+    base: usize,
+) -> Result<(Stmt, usize), String> {
+    let cond = parse_condition(paren_condition(header, "यावत्")?);
+    let (body, consumed, first) = collect_block(lines, start)?;
+    Ok((Stmt::While { label, cond, body: parse_block(&body, base + first) }, consumed))
+}
+
+fn parse_for(
+    header: &str,
+    label: Option<String>,
+    lines: &[String],
+    start: usize,
+    base: usize,
+) -> Result<(Stmt, usize), String> {
+    let (vars, iterable, guard) = parse_for_header(header)?;
+    let (body, consumed, first) = collect_block(lines, start)?;
+    let stmt = Stmt::For {
+        label,
+        vars: vars.into_iter().map(str::to_string).collect(),
+        iterable: parse_expr(iterable),
+        guard: guard.map(parse_condition),
+        body: parse_block(&body, base + first),
+    };
+    Ok((stmt, consumed))
+}
+
+fn parse_do_while(label: Option<String>, lines: &[String], start: usize, base: usize) -> Result<(Stmt, usize), String> {
+    let (body, consumed, first) = collect_block(lines, start)?;
+    let tail = next_code_line(lines, start + consumed)
+        .filter(|&idx| lines[idx].trim().starts_with("यावत्"))
+        .ok_or(DO_WITHOUT_WHILE)?;
+    let cond = parse_condition(do_while_condition(lines[tail].trim())?);
+    Ok((Stmt::DoWhile { label, body: parse_block(&body, base + first), cond }, tail + 1 - start))
+}
+
+fn parse_try(lines: &[String], start: usize, base: usize) -> Result<(Stmt, usize), String> {
+    let (try_block, try_consumed, try_first) = collect_block(lines, start)?;
+    let except_idx = next_code_line(lines, start + try_consumed)
+        .filter(|&idx| lines[idx].trim().starts_with("दोषे"))
+        .ok_or("त्रुटिः: प्रयत्न-खण्डात् परं दोषे अपेक्षितम्")?;
+    let (except_block, except_consumed, except_first) = collect_block(lines, except_idx)?;
+    let consumed = except_idx + except_consumed - start;
+    // A bad `दोषे` header takes the whole statement, so its block isn't
+    // then reported again as a stray `दोषे`.
+    let name = match parse_except_header(lines[except_idx].trim()) {
+        Ok(name) => name,
+        Err(e) => return Ok((Stmt::Invalid(e), consumed)),
+    };
+    let stmt = Stmt::Try {
+        body: parse_block(&try_block, base + try_first),
+        name: name.map(str::to_string),
+        handler: parse_block(&except_block, base + except_first),
+    };
+    Ok((stmt, consumed))
+}
+
+fn parse_match(lines: &[String], start: usize, base: usize) -> Result<(Stmt, usize), String> {
+    let scrutinee = parse_expr(parse_match_header(lines[start].trim())?);
+    let (block, consumed, first) = collect_block(lines, start)?;
+    let arms = parse_match_arms(&block)?
+        .into_iter()
+        .map(|arm| {
+            let values = match &arm.values {
+                Some(values) => Some(split_args(values)?.into_iter().map(parse_expr).collect()),
+                None => None,
+            };
+            Ok(Arm {
+                values,
+                body: parse_block(&arm.body, base + first + arm.first),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok((Stmt::Match { scrutinee, arms }, consumed))
+}
+
+fn parse_function(lines: &[String], start: usize, base: usize) -> Result<(Stmt, usize), String> {
+    let (name, params) = parse_function_header(lines[start].trim())?;
+    let (block, consumed, first) = collect_block(lines, start)?;
+    let (doc, body) = split_docstring(&block);
+    let (globals, body) = split_globals(&body)?;
+    let stmt = Stmt::Function {
+        name: name.to_string(),
+        params,
+        body: Arc::new(parse_block(&body, base + first)),
+        doc,
+        globals,
+    };
+    Ok((stmt, consumed))
+}
+
+/// Parse a one-line statement.
+fn parse_statement(line: &str) -> Stmt {
+    parse_simple(line).unwrap_or_else(Stmt::Invalid)
+}
+
+fn parse_simple(line: &str) -> Result<Stmt, String> {
+    if line == "प्रतिफल" || line.starts_with("प्रतिफल ") {
+        return Ok(Stmt::Return(parse_expr(&line["प्रतिफल".len()..])));
+    }
+    if let Some(rest) = line.strip_prefix("दोषं कृ") {
+        return Ok(Stmt::Raise(parse_expr(raise_argument(rest)?)));
+    }
+    if line.starts_with("वैश्विक ") {
+        return Err("त्रुटिः: वैश्विक घोषणा कार्यस्य आरम्भे एव भवेत्".into());
+    }
+    if let Some(rest) = assertion_rest(line) {
+        let (cond, message) = parse_assertion(rest)?;
+        return Ok(Stmt::Assert {
+            cond: parse_condition(cond),
+            message: message.map(parse_expr),
+            text: cond.trim().to_string(),
+        });
+    }
+    if let Some(rest) = line.strip_prefix("ध्रुव ") {
+        let (name, value) = parse_constant(rest)?;
+        return Ok(Stmt::Const(name.to_string(), parse_expr(value)));
+    }
+    if let Some(spec) = line.strip_prefix("आयात ") {
+        if string_literal(spec.trim()).is_none() {
+            return Err("त्रुटिः: आयात प्रयोगः आयात \"सञ्चिका\" स्वरूपेण भवेत्".into());
+        }
+        return Ok(Stmt::Import(spec.trim().to_string()));
+    }
+    if let Some(target) = line.strip_prefix("विलोप ") {
+        let (name, index) = parse_delete_target(target.trim())?;
+        return Ok(Stmt::Delete(name.to_string(), index.map(parse_expr)));
+    }
+    if let Some((name, value)) = split_assignment(line) {
+        if let Some(unpack) = split_unpack(name, value) {
+            let (targets, values) = unpack?;
+            return Ok(Stmt::Unpack {
+                targets: targets.into_iter().map(str::to_string).collect(),
+                values: values.into_iter().map(parse_expr).collect(),
+            });
+        }
+        check_assign_target(name)?;
+        return Ok(Stmt::Assign(name.to_string(), parse_expr(value)));
+    }
+    if let Some(label) = parse_break(line) {
+        return Ok(Stmt::Break(label?.map(str::to_string)));
+    }
+    if let Some(rest) = line.strip_prefix("दर्श") {
+        // The `(` after दर्श must close at the end: not `दर्श(1) दर्श(2)`.
+        let lp = line.len() - rest.trim_start().len();
+        if !rest.trim_start().starts_with('(') || find_matching_paren(line, lp) != Some(line.len() - 1) {
+            return Err("त्रुटिः: दर्श प्रयोगः केवलं दर्श(expr) स्वरूपेण भवेत्".into());
+        }
+        let inner = &line[lp + 1..line.len() - 1];
+        let args = split_args(inner)?
+            .into_iter()
+            .map(|arg| match arg.strip_prefix('*') {
+                Some(spread) => PrintArg::Spread(spread.trim().to_string(), parse_expr(spread)),
+                None => PrintArg::Value(parse_expr(arg)),
+            })
+            .collect();
+        return Ok(Stmt::Print(args));
+    }
+    if let Some(lp) = line.find('(') {
+        let name = line[..lp].trim();
+        if find_matching_paren(line, lp) == Some(line.len() - 1) && is_valid_identifier(name) {
+            return Ok(Stmt::Call(name.to_string(), parse_args(&line[lp + 1..line.len() - 1])?));
+        }
+    }
+    if line.ends_with(')') && find_call_open(line).is_some() {
+        return Ok(Stmt::Expr(parse_expr(line)));
+    }
+    if line == "help" {
+        return Ok(Stmt::Help);
+    }
+    Err(unknown_command(line))
+}
+
+fn unknown_command(line: &str) -> String {
+    format!("अज्ञाता आज्ञा: {}", line)
+}
+
+/// The arguments of a call, positional ones first.
+fn parse_args(args: &str) -> Result<Vec<Arg>, String> {
+    let mut parsed = Vec::new();
+    let mut keywords = false;
+    for arg in split_args(args)? {
+        if let Some((key, expr)) = split_kwarg(arg) {
+            keywords = true;
+            parsed.push(Arg::Keyword(key.to_string(), parse_expr(expr)));
+        } else if keywords {
+            return Err("त्रुटिः: नामित-तर्कात् परं स्थानीय-तर्कः न अनुमतः".into());
+        } else {
+            parsed.push(Arg::Positional(parse_expr(arg)));
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parse an expression. Forms are tried loosest-binding first; an empty
+/// expression is `शून्य`.
+pub fn parse_expr(expr: &str) -> Expr {
+    let s = expr.trim();
+    if s.is_empty() {
+        return Expr::Null;
+    }
+    if let Some((0, end)) = outer_paren_bounds(s) {
+        if end == s.len() - 1 {
+            return parse_expr(&s[1..end]);
+        }
+    }
+    if let Some(text) = string_literal(s) {
+        return decode_escapes(text).map_or_else(Expr::Invalid, Expr::Str);
+    }
+    if let Some(b) = sanskrit_to_bool(s) {
+        return Expr::Bool(b);
+    }
+    if s == "शून्य" {
+        return Expr::Null;
+    }
+    if let Ok(n) = s.parse::<f64>() {
+        return Expr::Number(n);
+    }
+    // Conditional expression: a यदि cond अन्यथा b
+    if let Some(p) = find_top_level_keyword(s, "यदि") {
+        let rest = &s[p + "यदि".len()..];
+        let Some(q) = find_top_level_keyword(rest, "अन्यथा") else {
+            return Expr::Invalid(format!("त्रुटिः: यदि-अभिव्यक्तौ अन्यथा अपेक्षितम् -> {}", s));
+        };
+        return Expr::IfElse {
+            cond: Box::new(parse_condition(&rest[..q])),
+            then: Box::new(parse_expr(&s[..p])),
+            otherwise: Box::new(parse_expr(&rest[q + "अन्यथा".len()..])),
+        };
+    }
+    // Logical operators and comparisons give a boolean: x = a < b तथा c
+    let logical = ["वा", "तथा"].iter().any(|w| find_top_level_keyword(s, w).is_some())
+        || s.strip_prefix('न').is_some_and(|r| r.starts_with([' ', '(']));
+    if logical {
+        return Expr::Condition(Box::new(parse_condition(s)));
+    }
+    if let Some(p) = find_top_level_keyword(s, "मध्ये") {
+        return Expr::In(Box::new(parse_operand(&s[..p], s)), Box::new(parse_operand(&s[p + "मध्ये".len()..], s)));
+    }
+    if !find_comparisons(s).is_empty() {
+        return Expr::Condition(Box::new(parse_condition(s)));
+    }
+    // Binary arithmetic, lowest precedence first; the rightmost operator
+    // at a level is split on so that chains stay left-associative.
+    for ops in [&['+', '-'][..], &['*', '/', '%']] {
+        if let Some((idx, op)) = find_top_level_binary(s, ops) {
+            let left = parse_operand(&s[..idx], s);
+            let right = parse_operand(&s[idx + op.len()..], s);
+            return Expr::Arith(op, Box::new(left), Box::new(right));
+        }
+    }
+    if let Some(rest) = s.strip_prefix('-') {
+        return Expr::Neg(Box::new(parse_expr(rest)));
+    }
+    // Power: binds tighter than unary minus and is right-associative,
+    // so split on the leftmost `**`: 2 ** 3 ** 2 == 2 ** 9
+    if let Some(p) = find_top_level_op(s, "**") {
+        return Expr::Pow(Box::new(parse_operand(&s[..p], s)), Box::new(parse_operand(&s[p + 2..], s)));
+    }
+    if s.starts_with('[') && find_matching_paren(s, 0) == Some(s.len() - 1) {
+        return match split_args(&s[1..s.len() - 1]) {
+            Ok(items) => Expr::List(items.into_iter().map(parse_expr).collect()),
+            Err(e) => Expr::Invalid(e),
+        };
+    }
+    if s.starts_with('{') && find_matching_paren(s, 0) == Some(s.len() - 1) {
+        return parse_map(&s[1..s.len() - 1]).unwrap_or_else(Expr::Invalid);
+    }
+    if let Some(lp) = s.find('(') {
+        let name = s[..lp].trim();
+        if find_matching_paren(s, lp) == Some(s.len() - 1) && is_valid_identifier(name) {
+            return match parse_args(&s[lp + 1..s.len() - 1]) {
+                Ok(args) => Expr::Call(name.to_string(), args),
+                Err(e) => Expr::Invalid(e),
+            };
+        }
+    }
+    if s.ends_with(')') {
+        if let Some(lp) = find_call_open(s) {
+            return match parse_args(&s[lp + 1..s.len() - 1]) {
+                Ok(args) => Expr::CallValue(Box::new(parse_expr(&s[..lp])), args),
+                Err(e) => Expr::Invalid(e),
+            };
+        }
+    }
+    if s.ends_with(']') {
+        if let Some(lb) = find_subscript_open(s) {
+            let target = Box::new(parse_expr(&s[..lb]));
+            let inner = &s[lb + 1..s.len() - 1];
+            let parts = split_top_level(inner, ':');
+            return match parts.len() {
+                1 => Expr::Index(target, Box::new(parse_expr(inner))),
+                2 | 3 => Expr::Slice(target, parts.into_iter().map(parse_expr).collect()),
+                _ => Expr::Invalid("त्रुटिः: खण्डः [start:stop:step] स्वरूपेण भवेत्".into()),
+            };
+        }
+    }
+    if let Some(c) = builtin_constant(s) {
+        return Expr::Number(c);
+    }
+    if is_valid_identifier(s) {
+        return Expr::Var(s.to_string());
+    }
+    Expr::Invalid(format!("त्रुटिः: अभिव्यक्ति न संगृहीता -> {}", s))
+}
+
+/// One side of an operator in `whole`; unlike a whole expression, it may
+/// not be empty, so `1 +` is an error rather than `1 + शून्य`.
+fn parse_operand(operand: &str, whole: &str) -> Expr {
+    if operand.trim().is_empty() {
+        return Expr::Invalid(format!("त्रुटिः: अपूर्णा अभिव्यक्तिः -> {}", whole));
+    }
+    parse_expr(operand)
+}
+
+fn parse_map(entries: &str) -> Result<Expr, String> {
+    let mut parsed = Vec::new();
+    for entry in split_args(entries)? {
+        let colon = find_top_level_char(entry, ':')
+            .ok_or_else(|| format!("त्रुटिः: कोश-प्रविष्टौ ':' अपेक्षितम् -> {}", entry.trim()))?;
+        parsed.push((parse_expr(&entry[..colon]), parse_expr(&entry[colon + 1..])));
+    }
+    Ok(Expr::Map(parsed))
+}
+
+/// Parse a condition. Precedence, loosest first: `वा`, `तथा`, `न`, then
+/// comparisons; anything else is tested for truthiness.
+pub fn parse_condition(cond: &str) -> Cond {
+    let cond = strip_outer_parens(cond);
+    if let Some(p) = find_top_level_keyword(cond, "वा") {
+        return Cond::Or(Box::new(parse_condition(&cond[..p])), Box::new(parse_condition(&cond[p + "वा".len()..])));
+    }
+    if let Some(p) = find_top_level_keyword(cond, "तथा") {
+        return Cond::And(
+            Box::new(parse_condition(&cond[..p])),
+            Box::new(parse_condition(&cond[p + "तथा".len()..])),
+        );
+    }
+    if let Some(rest) = cond.strip_prefix('न').filter(|r| r.starts_with([' ', '('])) {
+        return Cond::Not(Box::new(parse_condition(rest)));
+    }
+    let links = find_comparisons(cond);
+    if let Some(&(first, _)) = links.first() {
+        let operands = links
+            .iter()
+            .enumerate()
+            .map(|(n, &(p, op))| {
+                let end = links.get(n + 1).map_or(cond.len(), |&(next, _)| next);
+                (op, parse_operand(&cond[p + op.len()..end], cond))
+            })
+            .collect();
+        return Cond::Compare(Box::new(parse_operand(&cond[..first], cond)), operands);
+    }
+    Cond::Truthy(parse_expr(cond))
+}
+
+/// The problems in a parsed program that show without running it, as
+/// (line, message): statements and expressions that don't parse, and
+/// `प्रतिफल` or `विरम` where there is no function or loop to leave.
+pub fn problems(program: &[Line]) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    check_lines(program, Scope::default(), &mut found);
+    found
+}
+
+/// What encloses a statement, for `problems`.
+#[derive(Clone, Copy, Default)]
+struct Scope {
+    in_function: bool,
+    in_loop: bool,
+}
+
+fn check_lines(lines: &[Line], scope: Scope, found: &mut Vec<(usize, String)>) {
+    for line in lines {
+        let mut errors = Vec::new();
+        let loop_scope = Scope { in_loop: true, ..scope };
+        match &line.stmt {
+            Stmt::Print(args) => {
+                for arg in args {
+                    match arg {
+                        PrintArg::Value(expr) | PrintArg::Spread(_, expr) => expr_errors(expr, &mut errors),
+                    }
+                }
+            }
+            Stmt::Assign(_, expr) | Stmt::Const(_, expr) | Stmt::Raise(expr) | Stmt::Expr(expr) | Stmt::Echo(expr) => {
+                expr_errors(expr, &mut errors)
+            }
+            Stmt::Return(expr) => {
+                if !scope.in_function {
+                    errors.push("त्रुटिः: प्रतिफल केवलं कार्यस्य अन्तः प्रयोक्तव्यम्");
+                }
+                expr_errors(expr, &mut errors);
+            }
+            Stmt::Break(_) if !scope.in_loop => errors.push("त्रुटिः: विरम केवलं पुनरावृत्तेः अन्तः प्रयोक्तव्यम्"),
+            Stmt::Unpack { values, .. } => values.iter().for_each(|expr| expr_errors(expr, &mut errors)),
+            Stmt::Delete(_, index) => index.iter().for_each(|expr| expr_errors(expr, &mut errors)),
+            Stmt::Assert { cond, message, .. } => {
+                cond_errors(cond, &mut errors);
+                message.iter().for_each(|expr| expr_errors(expr, &mut errors));
+            }
+            Stmt::Call(_, args) => args_errors(args, &mut errors),
+            Stmt::If { cond, then, otherwise } => {
+                cond_errors(cond, &mut errors);
+                check_lines(then, scope, found);
+                otherwise.iter().for_each(|block| check_lines(block, scope, found));
+            }
+            Stmt::While { cond, body, .. } | Stmt::DoWhile { cond, body, .. } => {
+                cond_errors(cond, &mut errors);
+                check_lines(body, loop_scope, found);
+            }
+            Stmt::Loop { body, .. } => check_lines(body, loop_scope, found),
+            Stmt::For { iterable, guard, body, .. } => {
+                expr_errors(iterable, &mut errors);
+                guard.iter().for_each(|cond| cond_errors(cond, &mut errors));
+                check_lines(body, loop_scope, found);
+            }
+            Stmt::Try { body, handler, .. } => {
+                check_lines(body, scope, found);
+                check_lines(handler, scope, found);
+            }
+            Stmt::Match { scrutinee, arms } => {
+                expr_errors(scrutinee, &mut errors);
+                for arm in arms {
+                    arm.values.iter().flatten().for_each(|expr| expr_errors(expr, &mut errors));
+                    check_lines(&arm.body, scope, found);
+                }
+            }
+            // A call starts outside any loop, like the interpreter's.
+            Stmt::Function { body, .. } => check_lines(body, Scope { in_function: true, in_loop: false }, found),
+            Stmt::Invalid(e) => errors.push(e),
+            Stmt::Break(_) | Stmt::Import(_) | Stmt::Help => {}
+        }
+        // Keep each problem in line order, before those of the block below it.
+        let at = found.iter().rposition(|&(n, _)| n <= line.number).map_or(0, |i| i + 1);
+        let errors = errors.into_iter().map(|e| (line.number, e.to_string()));
+        found.splice(at..at, errors);
+    }
+}
+
+fn args_errors<'a>(args: &'a [Arg], errors: &mut Vec<&'a str>) {
+    for arg in args {
+        match arg {
+            Arg::Positional(expr) | Arg::Keyword(_, expr) => expr_errors(expr, errors),
+        }
+    }
+}
+
+fn expr_errors<'a>(expr: &'a Expr, errors: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Invalid(e) => errors.push(e),
+        Expr::Null | Expr::Number(_) | Expr::Str(_) | Expr::Bool(_) | Expr::Var(_) => {}
+        Expr::List(items) => items.iter().for_each(|item| expr_errors(item, errors)),
+        Expr::Map(entries) => {
+            for (key, value) in entries {
+                expr_errors(key, errors);
+                expr_errors(value, errors);
+            }
+        }
+        Expr::Neg(inner) => expr_errors(inner, errors),
+        Expr::Arith(_, left, right) | Expr::Pow(left, right) | Expr::In(left, right) | Expr::Index(left, right) => {
+            expr_errors(left, errors);
+            expr_errors(right, errors);
+        }
+        Expr::Condition(cond) => cond_errors(cond, errors),
+        Expr::IfElse { cond, then, otherwise } => {
+            cond_errors(cond, errors);
+            expr_errors(then, errors);
+            expr_errors(otherwise, errors);
+        }
+        Expr::Call(_, args) => args_errors(args, errors),
+        Expr::CallValue(callee, args) => {
+            expr_errors(callee, errors);
+            args_errors(args, errors);
+        }
+        Expr::Slice(target, bounds) => {
+            expr_errors(target, errors);
+            bounds.iter().for_each(|bound| expr_errors(bound, errors));
+        }
+    }
+}
+
+fn cond_errors<'a>(cond: &'a Cond, errors: &mut Vec<&'a str>) {
+    match cond {
+        Cond::Or(left, right) | Cond::And(left, right) => {
+            cond_errors(left, errors);
+            cond_errors(right, errors);
+        }
+        Cond::Not(inner) => cond_errors(inner, errors),
+        Cond::Compare(first, links) => {
+            expr_errors(first, errors);
+            links.iter().for_each(|(_, expr)| expr_errors(expr, errors));
+        }
+        Cond::Truthy(expr) => expr_errors(expr, errors),
+    }
+}
+
+/// The word a boolean is written and printed as.
+pub(crate) fn bool_to_sanskrit(b: bool) -> &'static str {
+    if b {
+        "सत्य"
+    } else {
+        "असत्य"
+    }
+}
+
+/// The boolean a `सत्य`/`असत्य` literal stands for.
+pub(crate) fn sanskrit_to_bool(s: &str) -> Option<bool> {
+    [true, false].into_iter().find(|&b| bool_to_sanskrit(b) == s)
+}
+
+/// The message expression in `दोषं कृ(expr)`, given the text after `दोषं कृ`.
+pub(crate) fn raise_argument(rest: &str) -> Result<&str, String> {
+    let rest = rest.trim();
+    if rest.starts_with('(') && find_matching_paren(rest, 0) == Some(rest.len() - 1) && rest.len() > 2 {
+        Ok(&rest[1..rest.len() - 1])
+    } else {
+        Err("त्रुटिः: दोषं कृ(सन्देशः) स्वरूपेण भवेत्".into())
+    }
+}
+
+/// The text after the `निश्चय` keyword, if `line` is an assertion (and not,
+/// say, an assignment to a variable whose name starts with it).
+pub(crate) fn assertion_rest(line: &str) -> Option<&str> {
+    line.strip_prefix("निश्चय").filter(|r| r.is_empty() || r.starts_with([' ', '(']))
+}
+
+/// Split the text after `निश्चय` into the condition and the optional message.
+pub(crate) fn parse_assertion(rest: &str) -> Result<(&str, Option<&str>), String> {
+    let err = || "त्रुटिः: निश्चय स्वरूपः: निश्चय (cond), \"सन्देशः\"".to_string();
+    let (cond, message) = match find_top_level_char(rest, ',') {
+        Some(comma) => (&rest[..comma], Some(rest[comma + 1..].trim())),
+        None => (rest, None),
+    };
+    if cond.trim().is_empty() || message.is_some_and(str::is_empty) {
+        return Err(err());
+    }
+    Ok((cond, message))
+}
+
+/// Parse the `name = expr` that follows `ध्रुव`.
+pub(crate) fn parse_constant(rest: &str) -> Result<(&str, &str), String> {
+    let (left, right) =
+        split_assignment(rest).ok_or("त्रुटिः: ध्रुव प्रयोगः ध्रुव नाम = expr स्वरूपेण भवेत्")?;
+    check_assign_target(left)?;
+    Ok((left, right))
+}
+
+/// Parse the target of `विलोप`: a variable name, optionally followed by `[index]`.
+pub(crate) fn parse_delete_target(target: &str) -> Result<(&str, Option<&str>), String> {
+    let (name, index) = match target.find('[') {
+        Some(lb) if find_matching_paren(target, lb) == Some(target.len() - 1) => {
+            (target[..lb].trim(), Some(&target[lb + 1..target.len() - 1]))
+        }
+        _ => (target, None),
+    };
+    if !is_valid_identifier(name) {
+        return Err("त्रुटिः: विलोप प्रयोगः विलोप नाम विलोप नाम[कुंजी] वा स्वरूपेण भवेत्".into());
+    }
+    Ok((name, index))
+}
+
+/// Split `name = expr` at its top-level `=`, ignoring `==`, `!=`, `<=` and `>=`.
+/// The first top-level `=` that is not part of a comparison is the
+/// assignment, so `x = y == z` assigns the comparison's result.
+pub(crate) fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let bytes = line.as_bytes();
+    let mut offset = 0;
+    while let Some(p) = find_top_level_char(&line[offset..], '=') {
+        let eq = offset + p;
+        if bytes.get(eq + 1) == Some(&b'=') {
+            // `==`: skip both characters
+            offset = eq + 2;
+            continue;
+        }
+        let before = if eq > 0 { bytes.get(eq - 1) } else { None };
+        if !matches!(before, Some(b'=' | b'>' | b'<' | b'!')) {
+            return Some((line[..eq].trim(), line[eq + 1..].trim()));
+        }
+        offset = eq + 1;
+    }
+    None
+}
+
+/// The target names and value expressions of a multiple assignment.
+type Unpack<'a> = (Vec<&'a str>, Vec<&'a str>);
+
+/// `a, b = x, y` → (["a", "b"], ["x", "y"]); a single value, as in
+/// `a, b = सूची`, is a list to unpack when run. `None` when there is only one
+/// target.
+pub(crate) fn split_unpack<'a>(left: &'a str, value: &'a str) -> Option<Result<Unpack<'a>, String>> {
+    find_top_level_char(left, ',')?;
+    let split = || {
+        let targets: Vec<&str> = split_top_level(left, ',').into_iter().map(str::trim).collect();
+        for name in &targets {
+            check_assign_target(name)?;
+        }
+        let values = split_args(value)?;
+        if values.len() > 1 && values.len() != targets.len() {
+            return Err(unpack_count_error(targets.len(), values.len()));
+        }
+        Ok((targets, values))
+    };
+    Some(split())
+}
+
+pub(crate) fn unpack_count_error(targets: usize, values: usize) -> String {
+    format!("त्रुटिः: {} नामानि किन्तु {} मूल्यानि", targets, values)
+}
+
+pub(crate) fn check_assign_target(name: &str) -> Result<(), String> {
+    if !is_valid_identifier(name) {
+        return Err("त्रुटिः: असाइनस्य नाम अवैधम्".into());
+    }
+    if builtin_constant(name).is_some() {
+        return Err(format!("त्रुटिः: स्थिरांकः {} परिवर्तयितुं न शक्यते", name));
+    }
+    Ok(())
+}
+
+/// The condition inside `keyword (cond)`.
+pub(crate) fn paren_condition<'a>(line: &'a str, keyword: &str) -> Result<&'a str, String> {
+    let err = || format!("त्रुटिः: {} शर्ता ( ) मध्ये भवेत्", keyword);
+    let lp = line.find('(').ok_or_else(err)?;
+    let rp = line.rfind(')').ok_or_else(err)?;
+    if rp < lp {
+        return Err(err());
+    }
+    Ok(&line[lp + 1..rp])
+}
+
+/// Split `चिह्न name: यावत् ...` into the label and the loop header; other
+/// lines come back unlabeled. Only loops (`यावत्`, `परिभ्रमण`, `कुरु` and
+/// `चक्र`) take a label.
+pub(crate) fn split_loop_label(line: &str) -> Result<(Option<&str>, &str), String> {
+    let Some(rest) = line.strip_prefix("चिह्न ").filter(|_| split_assignment(line).is_none()) else {
+        return Ok((None, line));
+    };
+    let err = || "त्रुटिः: चिह्न प्रयोगः चिह्न नाम: यावत् / परिभ्रमण / कुरु / चक्र ... स्वरूपेण भवेत्".to_string();
+    let (label, header) = rest.split_once(':').ok_or_else(err)?;
+    let (label, header) = (label.trim(), header.trim());
+    let is_loop = header.starts_with("यावत्") || header.starts_with("परिभ्रमण") || header == "कुरु" || header == "चक्र";
+    if !is_valid_identifier(label) || !is_loop {
+        return Err(err());
+    }
+    Ok((Some(label), header))
+}
+
+/// Error for a `कुरु` block that isn't followed by its `यावत् (cond)` line.
+pub(crate) const DO_WITHOUT_WHILE: &str = "त्रुटिः: कुरु-खण्डात् परं यावत् (शर्त) अपेक्षितम्";
+
+/// The condition of the `यावत् (cond)` line that ends a `कुरु` loop. As in
+/// a `यावत्` header, the parentheses may be left out.
+pub(crate) fn do_while_condition(line: &str) -> Result<&str, String> {
+    let rest = line.strip_prefix("यावत्").map(str::trim).unwrap_or_default();
+    if rest.is_empty() {
+        return Err(DO_WITHOUT_WHILE.into());
+    }
+    if rest.contains('(') {
+        paren_condition(rest, "यावत्")
+    } else {
+        Ok(rest)
+    }
+}
+
+/// `विरम` → `Some(Ok(None))`, `विरम name` → `Some(Ok(Some(name)))`; `None`
+/// when `line` isn't a break.
+pub(crate) fn parse_break(line: &str) -> Option<Result<Option<&str>, String>> {
+    if line == "विरम" {
+        return Some(Ok(None));
+    }
+    let label = line.strip_prefix("विरम ")?.trim();
+    Some(if is_valid_identifier(label) {
+        Ok(Some(label))
+    } else {
+        Err("त्रुटिः: विरम प्रयोगः विरम अथवा विरम चिह्नम् स्वरूपेण भवेत्".into())
+    })
+}
+
+/// `परिभ्रमण x in iterable [यावत् (cond)]` → ([x], iterable, cond).
+pub(crate) fn parse_for_header(line: &str) -> Result<(Vec<&str>, &str, Option<&str>), String> {
+    let after_kw = line
+        .strip_prefix("परिभ्रमण")
+        .ok_or_else(|| "त्रुटिः: परिभ्रमण वाक्य अवैधम्".to_string())?
+        .trim_start();
+    let in_pos = after_kw
+        .find(" in ")
+        .ok_or_else(|| "त्रुटिः: परिभ्रमण स्वरूपः: परिभ्रमण x in परिधि(n)".to_string())?;
+    // `परिभ्रमण i, v in ...` unpacks each item into several variables.
+    let vars: Vec<&str> = split_top_level(&after_kw[..in_pos], ',').into_iter().map(str::trim).collect();
+    if !vars.iter().all(|var| is_valid_identifier(var)) {
+        return Err("त्रुटिः: परिभ्रमण चरः अवैधः".into());
+    }
+    let mut iter_part = after_kw[in_pos + 4..].trim();
+    // Optional early exit: परिभ्रमण x in परिधि(n) यावत् (cond)
+    let mut guard_cond = None;
+    if let Some(p) = find_top_level_op(iter_part, "यावत्") {
+        let cond = iter_part[p + "यावत्".len()..].trim();
+        guard_cond = Some(strip_outer_parens(cond));
+        iter_part = iter_part[..p].trim();
+    }
+    Ok((vars, iter_part, guard_cond))
+}
+
+/// The variable named by `दोषे नाम`, if any.
+pub(crate) fn parse_except_header(line: &str) -> Result<Option<&str>, String> {
+    let name = line.strip_prefix("दोषे").map(str::trim).unwrap_or_default();
+    if name.is_empty() {
+        return Ok(None);
+    }
+    check_assign_target(name).map_err(|_| "त्रुटिः: दोषे स्वरूपः: दोषे नाम:".to_string())?;
+    Ok(Some(name))
+}
+
+/// The index of the first line at or after `from` that isn't blank or a comment.
+pub(crate) fn next_code_line(lines: &[String], from: usize) -> Option<usize> {
+    (from..lines.len()).find(|&idx| {
+        let l = lines[idx].trim();
+        !(l.is_empty() || l.starts_with("!!") || l.starts_with('#'))
+    })
+}
+
+/// The scrutinee of `विकल्प expr`.
+pub(crate) fn parse_match_header(line: &str) -> Result<&str, String> {
+    match line.strip_prefix("विकल्प").map(str::trim) {
+        Some(expr) if !expr.is_empty() => Ok(expr),
+        _ => Err("त्रुटिः: विकल्प स्वरूपः: विकल्प expr:".into()),
+    }
+}
+
+/// One arm of a `विकल्प` block: the comma-separated `स्थिति` values, or
+/// `None` for the `अन्यथा` default, and the arm's body.
+pub(crate) struct MatchArm {
+    pub(crate) values: Option<String>,
+    pub(crate) body: String,
+    /// Index of the body's first line within the `विकल्प` block.
+    pub(crate) first: usize,
+}
+
+/// Split the body of a `विकल्प` block into its `स्थिति value:` arms and an
+/// optional final `अन्यथा:`.
+pub(crate) fn parse_match_arms(block: &str) -> Result<Vec<MatchArm>, String> {
+    let lines: Vec<String> = block.lines().map(|l| l.to_string()).collect();
+    let mut arms: Vec<MatchArm> = Vec::new();
+    let mut i = 0usize;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if line.is_empty() || line.starts_with("!!") || line.starts_with('#') {
+            i += 1;
+            continue;
+        }
+        if arms.last().is_some_and(|arm| arm.values.is_none()) {
+            return Err("त्रुटिः: विकल्पे अन्यथा अन्तिमं भवेत्".into());
+        }
+        let values = match line.strip_prefix("स्थिति") {
+            _ if line == "अन्यथा" => None,
+            Some(values) if !values.trim().is_empty() => Some(values.trim().to_string()),
+            _ => return Err(format!("त्रुटिः: विकल्पे स्थिति अन्यथा वा अपेक्षितम्: {}", line)),
+        };
+        let (body, consumed, first) = collect_block(&lines, i)?;
+        arms.push(MatchArm { values, body, first });
+        i += consumed;
+    }
+    if arms.is_empty() {
+        return Err("त्रुटिः: विकल्पे न्यूनातिन्यूनम् एका स्थिति अपेक्षिता".into());
+    }
+    Ok(arms)
+}
+
+/// `कार्य name(a, b)` → (name, params).
+pub(crate) fn parse_function_header(line: &str) -> Result<(&str, Vec<String>), String> {
+    let rest = line
+        .strip_prefix("कार्य")
+        .ok_or_else(|| "त्रुटिः: कार्य स्वरूप अवैधः".to_string())?
+        .trim_start();
+    let lp = rest
+        .find('(')
+        .ok_or_else(|| "त्रुटिः: कार्य नामस्य अनन्तरं ( अपेक्षितम्".to_string())?;
+    let rp = rest
+        .rfind(')')
+        .ok_or_else(|| "त्रुटिः: कार्य तर्काणां ')' न लब्धम्".to_string())?;
+    let name = rest[..lp].trim();
+    if !is_valid_identifier(name) {
+        return Err("त्रुटिः: कार्य नाम अवैधम्".into());
+    }
+    let params_str = &rest[lp + 1..rp];
+    let params: Vec<String> = if params_str.trim().is_empty() {
+        Vec::new()
+    } else {
+        params_str
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .collect()
+    };
+    for p in &params {
+        if !is_valid_identifier(p) {
+            return Err("त्रुटिः: कार्य तर्कस्य नाम अवैधम्".into());
+        }
+    }
+    Ok((name, params))
+}
+
+/// Separate a function body's leading string-literal lines (its docstring).
+/// A triple-quoted docstring contributes one line per source line.
+/// The docstring's lines are blanked rather than removed, so the body keeps
+/// its line numbering.
+pub(crate) fn split_docstring(block: &str) -> (Vec<String>, String) {
+    let mut doc = Vec::new();
+    let mut body_lines: Vec<&str> = block.lines().collect();
+    for line in body_lines.iter_mut().skip_while(|l| l.trim().is_empty()) {
+        let Some(text) = string_literal(line.trim()) else {
+            break;
+        };
+        match decode_escapes(text) {
+            Ok(text) if text.contains('\n') => doc.extend(text.lines().map(|l| l.trim().to_string())),
+            _ => doc.push(text.to_string()),
+        }
+        *line = "";
+    }
+    (doc, body_lines.join("\n"))
+}
+
+/// Split the `वैश्विक a, b` declarations at the top of a function body from the
+/// rest of the body.
+pub(crate) fn split_globals(body: &str) -> Result<(Vec<String>, String), String> {
+    let mut globals = Vec::new();
+    let mut body_lines: Vec<&str> = body.lines().collect();
+    for line in body_lines.iter_mut().skip_while(|l| l.trim().is_empty()) {
+        let Some(names) = line.trim().strip_prefix("वैश्विक ") else {
+            break;
+        };
+        for name in names.split(',').map(str::trim) {
+            check_assign_target(name).map_err(|_| format!("त्रुटिः: वैश्विक नाम अवैधम्: {}", name))?;
+            globals.push(name.to_string());
+        }
+        *line = "";
+    }
+    Ok((globals, body_lines.join("\n")))
+}
+
+/// Tracks whether a left-to-right scan is inside a string literal, which is
+/// delimited by either `"` or `'`. A backslash inside a string escapes the
+/// next character, so `\"` does not end it, and the other quote character
+/// is ordinary text.
+#[derive(Default)]
+pub(crate) struct StrScan {
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl StrScan {
+    /// Feed the next character; true when it is part of a string literal,
+    /// quotes included.
+    pub(crate) fn step(&mut self, c: char) -> bool {
+        match self.quote {
+            Some(q) => {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == '\\' {
+                    self.escaped = true;
+                } else if c == q {
+                    self.quote = None;
+                }
+                true
+            }
+            None => {
+                self.quote = is_quote(c).then_some(c);
+                self.quote.is_some()
+            }
+        }
+    }
+
+    pub(crate) fn in_str(&self) -> bool {
+        self.quote.is_some()
+    }
+
+    /// The quote character of the string being scanned.
+    pub(crate) fn quote(&self) -> Option<char> {
+        self.quote
+    }
+}
+
+pub(crate) fn is_quote(c: char) -> bool {
+    c == '"' || c == '\''
+}
+
+/// The raw (still escaped) contents of `s` if it is a single string literal
+/// in either quote style.
+pub(crate) fn string_literal(s: &str) -> Option<&str> {
+    let open = s.chars().next().filter(|&c| is_quote(c))?;
+    let mut scan = StrScan::default();
+    scan.step(open);
+    let close = s.char_indices().skip(1).find(|&(_, c)| {
+        scan.step(c);
+        !scan.in_str()
+    })?;
+    (close.0 == s.len() - 1).then(|| &s[1..close.0])
+}
+
+/// Decode the escape sequences in a string literal's contents: `\n`, `\t`,
+/// `\\`, `\"`, `\'` and `\u{...}`.
+pub(crate) fn decode_escapes(raw: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().enumerate();
+    while let Some((_, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let (pos, esc) = match chars.next() {
+            Some((i, e)) => (i, e),
+            None => return Err("त्रुटिः: अपूर्णः पलायनक्रमः '\\'".into()),
+        };
+        match esc {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            '\\' => out.push('\\'),
+            '"' | '\'' => out.push(esc),
+            'u' => {
+                let mut rest = String::new();
+                let mut closed = false;
+                for (_, c) in chars.by_ref() {
+                    rest.push(c);
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                }
+                let code = closed
+                    .then(|| rest.strip_prefix('{')?.strip_suffix('}'))
+                    .flatten()
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("त्रुटिः: अवैधः यूनिकोड-पलायनः '\\u{}' (स्थानम् {})", rest, pos))?;
+                out.push(code);
+            }
+            other => return Err(format!("त्रुटिः: अज्ञातः पलायनक्रमः '\\{}' (स्थानम् {})", other, pos)),
+        }
+    }
+    Ok(out)
+}
+
+/// Reserved mathematical constants, available everywhere at full `f64` precision.
+pub(crate) fn builtin_constant(name: &str) -> Option<f64> {
+    match name {
+        "पाई" => Some(std::f64::consts::PI),
+        "ई" => Some(std::f64::consts::E),
+        "स्वर्णिम" => Some(1.618_033_988_749_895),
+        _ => None,
+    }
+}
+
+pub(crate) fn is_valid_identifier(s: &str) -> bool {
+    if s.is_empty() {
+        return false;
+    }
+    s.chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || (c as u32) > 127)
+}
+
+/// Find the rightmost top-level binary operator among `ops`. An operator is
+/// binary only when it follows an operand, so `-x` and `2 * -3` are left for
+/// unary handling, and the sign in an exponent like `1e-5` is skipped. A `*`
+/// that is part of `**` is not multiplication, and `//` is returned whole.
+pub(crate) fn find_top_level_binary(s: &str, ops: &[char]) -> Option<(usize, &'static str)> {
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    let mut found = None;
+    let mut prev: Option<char> = None;
+    let mut prev2: Option<char> = None;
+    let mut raw_prev: Option<char> = None;
+    for (i, c) in s.char_indices() {
+        if !scan.step(c) {
+            if is_open_bracket(c) {
+                depth += 1;
+            } else if is_close_bracket(c) {
+                depth = depth.saturating_sub(1);
+            } else if depth == 0 && ops.contains(&c) {
+                let follows_operand = matches!(prev, Some(p) if !"+-*/%(,=<>![{:".contains(p));
+                let is_exponent = matches!(prev, Some('e') | Some('E'))
+                    && matches!(prev2, Some(d) if d.is_ascii_digit());
+                let is_power = c == '*' && (raw_prev == Some('*') || s[i + 1..].starts_with('*'));
+                if follows_operand && !is_exponent && !is_power {
+                    found = Some((i, arith_op_str(c, &s[i + 1..])));
+                }
+            }
+        }
+        raw_prev = Some(c);
+        if !c.is_whitespace() {
+            prev2 = prev;
+            prev = Some(c);
+        }
+    }
+    found
+}
+
+/// The operator starting with `c`, given the text that follows it.
+fn arith_op_str(c: char, rest: &str) -> &'static str {
+    match c {
+        '+' => "+",
+        '-' => "-",
+        '*' => "*",
+        '/' if rest.starts_with('/') => "//",
+        '/' => "/",
+        _ => "%",
+    }
+}
+
+pub(crate) fn find_top_level_char(s: &str, target: char) -> Option<usize> {
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    for (i, c) in s.char_indices() {
+        if scan.step(c) {
+            continue;
+        }
+        if is_open_bracket(c) {
+            depth += 1;
+        }
+        if is_close_bracket(c) && depth > 0 {
+            depth -= 1;
+        }
+        if depth == 0 && c == target {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Positions of the top-level comparison operators in `s`, left to right.
+pub(crate) fn find_comparisons(s: &str) -> Vec<(usize, &'static str)> {
+    let mut found = Vec::new();
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    let mut skip_next = false;
+    for (i, c) in s.char_indices() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        if scan.step(c) {
+            continue;
+        } else if is_open_bracket(c) {
+            depth += 1;
+        } else if is_close_bracket(c) {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 {
+            let two = ["==", "!=", ">=", "<="].into_iter().find(|op| s[i..].starts_with(op));
+            if let Some(op) = two {
+                found.push((i, op));
+                skip_next = true;
+            } else if c == '<' || c == '>' {
+                found.push((i, if c == '<' { "<" } else { ">" }));
+            }
+        }
+    }
+    found
+}
+
+/// Like `find_top_level_op`, but only matches `word` standing alone between
+/// spaces, and never at the very start (where it would begin a statement).
+pub(crate) fn find_top_level_keyword(s: &str, word: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(p) = find_top_level_op(&s[offset..], word) {
+        let at = offset + p;
+        let end = at + word.len();
+        let spaced_before = s[..at].ends_with(char::is_whitespace) && !s[..at].trim().is_empty();
+        let spaced_after = s[end..].starts_with(char::is_whitespace);
+        if spaced_before && spaced_after {
+            return Some(at);
+        }
+        offset = end;
+    }
+    None
+}
+
+pub(crate) fn find_top_level_op(s: &str, op: &str) -> Option<usize> {
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    let mut i = 0usize;
+    while i < s.len() {
+        let c = s[i..].chars().next().unwrap();
+        let clen = c.len_utf8();
+        if scan.step(c) {
+            i += clen;
+            continue;
+        }
+        if is_open_bracket(c) {
+            depth += 1;
+            i += clen;
+            continue;
+        }
+        if is_close_bracket(c) {
+            depth = depth.saturating_sub(1);
+            i += clen;
+            continue;
+        }
+        if depth == 0 && s[i..].starts_with(op) {
+            return Some(i);
+        }
+        i += clen;
+    }
+    None
+}
+
+pub(crate) fn split_args(s: &str) -> Result<Vec<&str>, String> {
+    let mut res = Vec::new();
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    let mut start = 0usize;
+    for (i, c) in s.char_indices() {
+        if scan.step(c) {
+            continue;
+        }
+        if is_open_bracket(c) {
+            depth += 1;
+        }
+        if is_close_bracket(c) {
+            depth = depth.saturating_sub(1);
+        }
+        if c == ',' && depth == 0 {
+            res.push(s[start..i].trim());
+            start = i + 1;
+        }
+    }
+    if start <= s.len() {
+        res.push(s[start..].trim());
+    }
+    Ok(res.into_iter().filter(|p| !p.is_empty()).collect())
+}
+
+/// Index of the bracket closing the `(`, `[` or `{` at `open`, skipping string literals.
+pub(crate) fn find_matching_paren(s: &str, open: usize) -> Option<usize> {
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    for (i, c) in s[open..].char_indices() {
+        if scan.step(c) {
+            continue;
+        }
+        if is_open_bracket(c) {
+            depth += 1;
+        }
+        if is_close_bracket(c) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(open + i);
+            }
+        }
+    }
+    None
+}
+
+/// Split `s` at every top-level occurrence of `sep`, outside strings and brackets.
+pub(crate) fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+    while let Some(i) = find_top_level_char(rest, sep) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + sep.len_utf8()..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// For `expr[index]`, the position of the `[` matching the final `]`,
+/// provided something precedes it (otherwise it is a list literal).
+pub(crate) fn find_subscript_open(s: &str) -> Option<usize> {
+    find_postfix_open(s, '[')
+}
+
+/// For a call on an arbitrary expression, `expr(args)`, the position of the
+/// `(` matching the final `)`, provided an operand precedes it.
+pub(crate) fn find_call_open(s: &str) -> Option<usize> {
+    find_postfix_open(s, '(')
+}
+
+fn find_postfix_open(s: &str, open_char: char) -> Option<usize> {
+    let mut scan = StrScan::default();
+    let mut depth = 0usize;
+    let mut last_open = None;
+    for (i, c) in s.char_indices() {
+        if scan.step(c) {
+            continue;
+        }
+        if is_open_bracket(c) {
+            if depth == 0 {
+                last_open = Some(i);
+            }
+            depth += 1;
+        }
+        if is_close_bracket(c) {
+            depth = depth.saturating_sub(1);
+        }
+    }
+    let open = last_open?;
+    if open == 0 || !s[open..].starts_with(open_char) || s[..open].trim().is_empty() {
+        return None;
+    }
+    match s[..open].trim_end().chars().last() {
+        Some(c) if "+-*/%(,=<>![{:".contains(c) => None,
+        _ => Some(open),
+    }
+}
+
+/// `name=expr` inside a call's argument list.
+pub(crate) fn split_kwarg(arg: &str) -> Option<(&str, &str)> {
+    let eq = find_top_level_char(arg, '=')?;
+    let bytes = arg.as_bytes();
+    if bytes.get(eq + 1) == Some(&b'=') {
+        return None;
+    }
+    let key = arg[..eq].trim();
+    if !is_valid_identifier(key) {
+        return None;
+    }
+    Some((key, arg[eq + 1..].trim()))
+}
+
+pub(crate) fn is_open_bracket(c: char) -> bool {
+    matches!(c, '(' | '[' | '{')
+}
+
+pub(crate) fn is_close_bracket(c: char) -> bool {
+    matches!(c, ')' | ']' | '}')
+}
+
+/// `(expr)` -> `expr` when the parentheses enclose the whole string.
+pub(crate) fn strip_outer_parens(s: &str) -> &str {
+    let s = s.trim();
+    if s.starts_with('(') && find_matching_paren(s, 0) == Some(s.len() - 1) {
+        s[1..s.len() - 1].trim()
+    } else {
+        s
+    }
+}
+
+pub(crate) fn outer_paren_bounds(s: &str) -> Option<(usize, usize)> {
+    if !s.starts_with('(') || !s.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0usize;
+    let mut scan = StrScan::default();
+    for (i, c) in s.char_indices() {
+        if scan.step(c) {
+            continue;
+        }
+        if c == '(' {
+            depth += 1;
+        }
+        if c == ')' {
+            depth -= 1;
+            if depth == 0 {
+                return Some((0, i));
+            }
+        }
+    }
+    None
+}
+
+/// Rewrite each `"""..."""` literal as an ordinary one-line string literal:
+/// its line breaks become `\n` escapes and bare `"` are escaped. Blank lines
+/// are added after the joined statement so later lines keep their numbers.
+pub(crate) fn join_triple_quoted(src: &str) -> String {
+    const TRIPLE: &str = "\"\"\"";
+    if !src.contains(TRIPLE) {
+        return src.to_string();
+    }
+    let mut out = String::new();
+    let mut open = false;
+    let mut joined = 0usize;
+    for line in src.lines() {
+        let trimmed = line.trim();
+        if !open && (trimmed.starts_with("!!") || trimmed.starts_with('#')) {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let mut scan = StrScan::default();
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix(TRIPLE).filter(|_| !scan.in_str()) {
+                out.push('"');
+                open = !open;
+                rest = after;
+                continue;
+            }
+            if !open {
+                scan.step(c);
+                out.push(c);
+            } else if c == '"' {
+                out.push_str("\\\"");
+            } else if c == '\\' {
+                // Keep `\"` and `\\` whole so they can't end the literal.
+                out.push(c);
+                if let Some(next) = rest[1..].chars().next().filter(|&n| n == '"' || n == '\\') {
+                    out.push(next);
+                    rest = &rest[1 + next.len_utf8()..];
+                    continue;
+                }
+            } else {
+                out.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+        if open {
+            out.push_str("\\n");
+            joined += 1;
+        } else {
+            out.push('\n');
+            out.push_str(&"\n".repeat(std::mem::take(&mut joined)));
+        }
+    }
+    if open {
+        // Unterminated: leave it for `check_balanced` to report.
+        out.push('\n');
+    }
+    out
+}
+
+/// Convert indentation-based blocks to braces so block extraction works. The
+/// braces are added to existing lines (`{` before a block's first statement,
+/// `}` after its last), so every line keeps its source line number.
+pub(crate) fn preprocess_indentation(src: &str) -> String {
+    let src = join_triple_quoted(src);
+    let mut out: Vec<String> = Vec::new();
+    let mut stack: Vec<usize> = vec![0];
+    let mut prev_ended_colon = false;
+    let mut last_code: Option<usize> = None;
+    for orig in src.lines() {
+        let raw = orig.replace('\t', "  ");
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with("!!") || trimmed.starts_with('#') {
+            out.push(orig.to_string());
+            continue;
+        }
+        let indent = raw.chars().take_while(|c| *c == ' ').count();
+        let curr = *stack.last().unwrap();
+        let mut opens = false;
+        if indent > curr {
+            if prev_ended_colon {
+                opens = true;
+                stack.push(indent);
+            }
+        } else if indent < curr {
+            while indent < *stack.last().unwrap() {
+                stack.pop();
+                if let Some(idx) = last_code {
+                    out[idx].push_str(" }");
+                }
+            }
+        }
+        let mut line = trimmed.to_string();
+        if line.ends_with(':') {
+            line.pop();
+            if line.starts_with("यदि") && !line.contains('(') {
+                line = format!("यदि ({})", line.trim_start_matches("यदि").trim());
+            }
+            if line.starts_with("यावत्") && !line.contains('(') {
+                line = format!("यावत् ({})", line.trim_start_matches("यावत्").trim());
+            }
+            if line.starts_with("अन्यथा") {
+                line = "अन्यथा".to_string();
+            }
+            // परिभ्रमण / कार्य left as-is
+            prev_ended_colon = true;
+        } else {
+            prev_ended_colon = false;
+        }
+        if opens {
+            line.insert_str(0, "{ ");
+        }
+        last_code = Some(out.len());
+        out.push(line);
+    }
+    while stack.len() > 1 {
+        stack.pop();
+        if let Some(idx) = last_code {
+            out[idx].push_str(" }");
+        }
+    }
+    out.into_iter().map(|line| line + "\n").collect()
+}
+
+/// Lines to skip past a header whose block could not run: the header plus its
+/// body when the next line opens one.
+pub(crate) fn skip_block(lines: &[String], start: usize) -> usize {
+    let opens = lines[start].trim_end().ends_with('{')
+        || next_code_line(lines, start + 1).is_some_and(|next| lines[next].trim().starts_with('{'));
+    if opens {
+        if let Ok((_, consumed, _)) = collect_block(lines, start) {
+            return consumed;
+        }
+    }
+    1
+}
+
+/// The block opened by the header at `lines[start]`: its body, the number of
+/// lines consumed from `start`, and the index of the line the body starts on.
+/// Every line of the body is kept, blank or not, so body line `k` is
+/// `lines[first + k]`.
+pub(crate) fn collect_block(lines: &[String], start: usize) -> Result<(String, usize, usize), String> {
+    // Find the '{' opening the block: at the end of the header line, or on a
+    // later line. A '{' elsewhere in the header belongs to a map literal.
+    let mut i = start;
+    let mut found_open: Option<(usize, usize)> = None;
+    while i < lines.len() {
+        let l = lines[i].trim();
+        let pos = if i == start { l.ends_with('{').then(|| l.len() - 1) } else { l.find('{') };
+        if let Some(pos) = pos {
+            found_open = Some((i, pos));
+            break;
+        }
+        i += 1;
+    }
+    let (open_idx, pos) = found_open.ok_or_else(|| "त्रुटिः: अपेक्षितम् '{'".to_string())?;
+    // Track brace depth (ignoring string contents) so nested blocks and
+    // map literals inside the body don't end it early.
+    let mut depth = 1usize;
+    let mut block_lines: Vec<String> = Vec::new();
+    i = open_idx;
+    while i < lines.len() {
+        let l = lines[i].trim();
+        let segment = if i == open_idx { &l[pos + 1..] } else { l };
+        let mut scan = StrScan::default();
+        for (j, c) in segment.char_indices() {
+            if scan.step(c) {
+                continue;
+            } else if c == '{' {
+                depth += 1;
+            } else if c == '}' {
+                depth -= 1;
+                if depth == 0 {
+                    block_lines.push(segment[..j].trim().to_string());
+                    return Ok((block_lines.join("\n"), (i + 1) - start, open_idx));
+                }
+            }
+        }
+        block_lines.push(segment.trim().to_string());
+        i += 1;
+    }
+    Err("त्रुटिः: '}' न लब्धम्".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Var(name.to_string()))
+    }
+
+    #[test]
+    fn test_if_else_parses_to_branches() {
+        let src = "x = 1\nयदि x > 0:\n    दर्श(\"धन\")\nअन्यथा:\n    !! ऋण\n    x = -x\n";
+        let program = parse(src);
+        assert_eq!(program.len(), 2);
+        assert_eq!(program[0].stmt, Stmt::Assign("x".into(), Expr::Number(1.0)));
+        assert_eq!(program[1].number, 2);
+        let Stmt::If { cond, then, otherwise } = &program[1].stmt else {
+            panic!("expected an if: {:?}", program[1]);
+        };
+        assert_eq!(*cond, Cond::Compare(var("x"), vec![(">", Expr::Number(0.0))]));
+        assert_eq!(then.len(), 1);
+        assert_eq!(then[0].number, 3);
+        assert_eq!(then[0].stmt, Stmt::Print(vec![PrintArg::Value(Expr::Str("धन".into()))]));
+        let otherwise = otherwise.as_ref().unwrap();
+        assert_eq!(otherwise.len(), 1);
+        assert_eq!(otherwise[0].number, 6);
+        assert_eq!(otherwise[0].stmt, Stmt::Assign("x".into(), Expr::Neg(var("x"))));
+    }
+
+    #[test]
+    fn test_loops_parse_their_body_once() {
+        let src = "यावत् i < 3 तथा चालू:\n    i = i + 1\nपरिभ्रमण k in परिधि(5) यावत् (k != 2):\n    योग = योग + k * 2\n";
+        let program = parse(src);
+        assert_eq!(program.len(), 2);
+        assert_eq!(
+            program[0].stmt,
+            Stmt::While {
+                label: None,
+                cond: Cond::And(
+                    Box::new(Cond::Compare(var("i"), vec![("<", Expr::Number(3.0))])),
+                    Box::new(Cond::Truthy(Expr::Var("चालू".into()))),
+                ),
+                body: vec![Line {
+                    number: 2,
+                    text: "i = i + 1".into(),
+                    stmt: Stmt::Assign("i".into(), Expr::Arith("+", var("i"), Box::new(Expr::Number(1.0)))),
+                }],
+            }
+        );
+        let Stmt::For { vars, iterable, guard, body, .. } = &program[1].stmt else {
+            panic!("expected a for loop: {:?}", program[1]);
+        };
+        assert_eq!(vars, &["k"]);
+        assert_eq!(*iterable, Expr::Call("परिधि".into(), vec![Arg::Positional(Expr::Number(5.0))]));
+        assert_eq!(*guard, Some(Cond::Compare(var("k"), vec![("!=", Expr::Number(2.0))])));
+        assert_eq!(body[0].number, 4);
+        assert_eq!(
+            body[0].stmt,
+            Stmt::Assign(
+                "योग".into(),
+                Expr::Arith("+", var("योग"), Box::new(Expr::Arith("*", var("k"), Box::new(Expr::Number(2.0))))),
+            )
+        );
+    }
+
+    #[test]
+    fn test_labeled_loops_and_breaks() {
+        let program = parse("चिह्न बाह्य: परिभ्रमण i in [1]:\n    यावत् (सत्य):\n        विरम बाह्य\n    विरम\n");
+        let Stmt::For { label, body, .. } = &program[0].stmt else {
+            panic!("expected a for loop: {:?}", program[0]);
+        };
+        assert_eq!(label.as_deref(), Some("बाह्य"));
+        let Stmt::While { label: None, body: inner, .. } = &body[0].stmt else {
+            panic!("expected an unlabeled while loop: {:?}", body[0]);
+        };
+        assert_eq!(inner[0].stmt, Stmt::Break(Some("बाह्य".into())));
+        assert_eq!(body[1].stmt, Stmt::Break(None));
+
+        assert!(matches!(parse("चिह्न क: यदि (सत्य):\n    x = 1")[0].stmt, Stmt::Invalid(_)));
+        assert!(matches!(parse("विरम क ख")[0].stmt, Stmt::Invalid(_)));
+        // Still plain variables when assigned.
+        assert!(matches!(parse("चिह्न = 1\nविरम = 2")[1].stmt, Stmt::Assign(..)));
+    }
+
+    #[test]
+    fn test_parse_errors_are_deferred_to_the_statement() {
+        let program = parse("दर्श(1)\nx = (1 +\n");
+        assert_eq!(program[0].stmt, Stmt::Print(vec![PrintArg::Value(Expr::Number(1.0))]));
+        assert!(matches!(&program[1].stmt, Stmt::Assign(_, Expr::Invalid(_))), "{:?}", program[1]);
+        assert_eq!(parse("???")[0].stmt, Stmt::Invalid("अज्ञाता आज्ञा: ???".into()));
+    }
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory, next to the output binary, holding the source hash each
+/// binary was built from.
+pub const CACHE_DIR: &str = ".paanini-cache";
+
+/// Hash of everything that decides the binary's contents: the source, the
+/// build mode, and the Paanini version (whose transpiler produced the Rust).
+/// FNV-1a keeps it stable across Rust releases, unlike `DefaultHasher`.
+pub fn cache_key(source: &str, release: bool) -> String {
+    let mode = if release { "release" } else { "debug" };
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in [env!("CARGO_PKG_VERSION"), mode, source] {
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Where the hash for `output` is stored: `<dir>/.paanini-cache/<name>.hash`.
+fn stamp_path(output: &Path) -> PathBuf {
+    let name = output.file_name().unwrap_or(output.as_os_str());
+    let mut stamp = name.to_os_string();
+    stamp.push(".hash");
+    output.with_file_name(CACHE_DIR).join(stamp)
+}
+
+/// Whether `output` exists and was last built from a source with `key`.
+pub fn is_fresh(output: &Path, key: &str) -> bool {
+    output.exists() && fs::read_to_string(stamp_path(output)).is_ok_and(|stored| stored.trim() == key)
+}
+
+/// Remember that `output` was just built from a source with `key`.
+pub fn record(output: &Path, key: &str) -> io::Result<()> {
+    let stamp = stamp_path(output);
+    if let Some(dir) = stamp.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(stamp, format!("{}\n", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_tracks_source_and_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("app");
+        let key = cache_key("दर्श(1)", false);
+        assert_eq!(key, cache_key("दर्श(1)", false));
+        assert_ne!(key, cache_key("दर्श(2)", false));
+        assert_ne!(key, cache_key("दर्श(1)", true));
+
+        record(&output, &key).unwrap();
+        assert!(dir.path().join(CACHE_DIR).join("app.hash").exists());
+        // A stamp without its binary isn't a hit.
+        assert!(!is_fresh(&output, &key));
+        fs::write(&output, "").unwrap();
+        assert!(is_fresh(&output, &key));
+        assert!(!is_fresh(&output, &cache_key("दर्श(2)", false)));
+    }
+}
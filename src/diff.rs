@@ -0,0 +1,42 @@
+use similar::{ChangeTag, TextDiff};
+
+/// Line-by-line diff of two program outputs, or `None` when they match.
+/// Removed lines are prefixed with `-`, added lines with `+`, and unchanged
+/// lines with a space.
+pub fn output_diff(left: &str, right: &str) -> Option<String> {
+    if left == right {
+        return None;
+    }
+    let diff = TextDiff::from_lines(left, right);
+    let mut out = String::new();
+    for change in diff.iter_all_changes() {
+        let sign = match change.tag() {
+            ChangeTag::Delete => '-',
+            ChangeTag::Insert => '+',
+            ChangeTag::Equal => ' ',
+        };
+        out.push(sign);
+        out.push_str(change.value().trim_end_matches('\n'));
+        out.push('\n');
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_identical_outputs_have_no_diff() {
+        assert_eq!(output_diff("a\nb\n", "a\nb\n"), None);
+    }
+
+    #[test]
+    fn test_differing_programs() {
+        let left = Interpreter::default().run("दर्श(\"नमस्ते\")\nदर्श(1 + 1)");
+        let right = Interpreter::default().run("दर्श(\"नमस्ते\")\nदर्श(1 + 2)");
+        let diff = output_diff(&left.output, &right.output).unwrap();
+        assert_eq!(diff, " नमस्ते\n-2\n+3\n");
+    }
+}
@@ -0,0 +1,99 @@
+use crate::interpreter::Interpreter;
+
+/// One `>>>` example from a function's docstring.
+pub struct Doctest {
+    pub function: String,
+    pub code: String,
+    pub expected: String,
+}
+
+pub struct DoctestOutcome {
+    pub test: Doctest,
+    pub output: String,
+    pub errors: Vec<String>,
+}
+
+impl DoctestOutcome {
+    pub fn passed(&self) -> bool {
+        self.errors.is_empty() && self.output.trim_end_matches('\n') == self.test.expected
+    }
+}
+
+/// Split a docstring into examples: each `>>> code` line is run, and the lines
+/// after it (up to the next `>>>`) are its expected output.
+pub fn extract_doctests(function: &str, doc: &[String]) -> Vec<Doctest> {
+    let mut tests: Vec<Doctest> = Vec::new();
+    let mut in_example = false;
+    for line in doc {
+        if let Some(code) = line.trim().strip_prefix(">>>") {
+            tests.push(Doctest {
+                function: function.to_string(),
+                code: code.trim().to_string(),
+                expected: String::new(),
+            });
+            in_example = true;
+        } else if in_example {
+            let expected = &mut tests.last_mut().unwrap().expected;
+            if !expected.is_empty() {
+                expected.push('\n');
+            }
+            expected.push_str(line.trim());
+        }
+    }
+    tests
+}
+
+/// Run `src` once to define its functions, then run every docstring example
+/// against a copy of that state. Errors from the program itself are returned
+/// as `Err`.
+pub fn run_doctests(src: &str) -> Result<Vec<DoctestOutcome>, Vec<String>> {
+    let mut interpreter = Interpreter::default();
+    let setup = interpreter.run(src);
+    if !setup.errors.is_empty() {
+        return Err(setup.errors);
+    }
+    let mut outcomes = Vec::new();
+    for (function, doc) in interpreter.function_docs() {
+        for test in extract_doctests(&function, &doc) {
+            let result = interpreter.clone().run(&test.code);
+            outcomes.push(DoctestOutcome {
+                test,
+                output: result.output,
+                errors: result.errors,
+            });
+        }
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_examples_and_expected_lines() {
+        let doc = ["वर्णनम्", ">>> दर्श(1)", "1", ">>> दर्श(1, 2)", "1 2", ">>> f()"].map(String::from);
+        let tests = extract_doctests("f", &doc);
+        assert_eq!(tests.len(), 3);
+        assert_eq!((tests[0].code.as_str(), tests[0].expected.as_str()), ("दर्श(1)", "1"));
+        assert_eq!((tests[1].code.as_str(), tests[1].expected.as_str()), ("दर्श(1, 2)", "1 2"));
+        assert_eq!(tests[2].expected, "");
+    }
+
+    #[test]
+    fn test_passing_and_failing_doctests() {
+        let src = "कार्य वर्ग(n):\n    \"वर्गं ददाति\"\n    \">>> दर्श(वर्ग(3))\"\n    \"9\"\n    \">>> दर्श(वर्ग(4))\"\n    \"15\"\n    प्रतिफल n * n\n";
+        let outcomes = run_doctests(src).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].passed());
+        assert!(!outcomes[1].passed());
+        assert_eq!(outcomes[1].output, "16\n");
+        assert_eq!(outcomes[1].test.expected, "15");
+    }
+
+    #[test]
+    fn test_program_errors_are_reported() {
+        let errors = run_doctests("अज्ञात(1)").err().unwrap();
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -0,0 +1,47 @@
+use rust_embed::RustEmbed;
+
+/// The sample programs in `examples/`, compiled into the binary so
+/// `paanini example` and `/api/examples` work from anywhere.
+#[derive(RustEmbed)]
+#[folder = "examples"]
+struct Bundled;
+
+/// Shown by `paanini example` when no name is given.
+pub const DEFAULT: &str = "basics";
+
+/// Every bundled example as `(name, source)`, sorted by name. The name is
+/// the file name without `.paanini`.
+pub fn all() -> Vec<(String, String)> {
+    let mut examples: Vec<_> = Bundled::iter()
+        .filter_map(|file| {
+            let name = file.strip_suffix(".paanini")?.to_string();
+            let source = get(&name)?;
+            Some((name, source))
+        })
+        .collect();
+    examples.sort();
+    examples
+}
+
+/// Source of the example called `name`, if there is one.
+pub fn get(name: &str) -> Option<String> {
+    let file = Bundled::get(&format!("{}.paanini", name))?;
+    Some(String::from_utf8_lossy(&file.data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_bundled_examples_run_cleanly() {
+        let examples = all();
+        assert!(examples.iter().any(|(name, _)| name == DEFAULT));
+        for (name, source) in examples {
+            let result = Interpreter::default().run(&source);
+            assert!(result.errors.is_empty(), "{}: {:?}", name, result.errors);
+        }
+        assert!(get("missing").is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::ast::StrScan;
+
+/// Canonical formatting for `.paanini` sources.
+///
+/// Blocks are recognised exactly as `preprocess_indentation` in the interpreter
+/// does (a deeper line opens a block only after a line ending in `:`; tabs count
+/// as two spaces) and are re-emitted with four spaces per level. Within a line,
+/// binary operators get one space on each side, commas are followed by one
+/// space, and runs of whitespace outside strings collapse to a single space.
+/// The lines after the one opening a `"""` string, up to the one closing it,
+/// are kept exactly as written. Formatting an already formatted file returns
+/// it unchanged.
+pub fn format_source(src: &str) -> String {
+    let mut out = String::new();
+    let mut stack: Vec<usize> = vec![0];
+    let mut prev_ended_colon = false;
+    let mut pending_blank = false;
+    let mut in_triple_quoted = false;
+
+    for orig in src.lines() {
+        if in_triple_quoted {
+            out.push_str(orig);
+            out.push('\n');
+            in_triple_quoted = triple_quoted_open_after(orig, true);
+            continue;
+        }
+        let raw = orig.replace('\t', "  ");
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            pending_blank = !out.is_empty();
+            continue;
+        }
+        let indent = raw.chars().take_while(|c| *c == ' ').count();
+        let is_comment = trimmed.starts_with("!!") || trimmed.starts_with('#');
+
+        let level = if is_comment {
+            // Comments do not open or close blocks; indent them like the code around them.
+            if indent > *stack.last().unwrap() && prev_ended_colon {
+                stack.len()
+            } else {
+                stack.iter().filter(|&&s| s <= indent).count() - 1
+            }
+        } else {
+            if indent > *stack.last().unwrap() {
+                if prev_ended_colon {
+                    stack.push(indent);
+                }
+            } else {
+                while indent < *stack.last().unwrap() {
+                    stack.pop();
+                }
+            }
+            prev_ended_colon = trimmed.ends_with(':');
+            stack.len() - 1
+        };
+
+        if pending_blank {
+            out.push('\n');
+            pending_blank = false;
+        }
+        out.push_str(&"    ".repeat(level));
+        if is_comment {
+            out.push_str(trimmed);
+        } else {
+            out.push_str(&format_line(trimmed));
+            in_triple_quoted = triple_quoted_open_after(trimmed, false);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Whether a `"""` string is still open at the end of `line`, given whether
+/// one was open at its start.
+fn triple_quoted_open_after(line: &str, mut open: bool) -> bool {
+    let mut scan = StrScan::default();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix(TRIPLE_QUOTE).filter(|_| !scan.in_str()) {
+            open = !open;
+            rest = after;
+            continue;
+        }
+        if open && c == '\\' {
+            // An escaped character can't end the string.
+            let mut chars = rest.chars();
+            chars.nth(1);
+            rest = chars.as_str();
+            continue;
+        }
+        if !open {
+            scan.step(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    open
+}
+
+const TRIPLE_QUOTE: &str = "\"\"\"";
+
+/// What the previous emitted token was, to decide the spacing before the next one.
+#[derive(Clone, Copy, PartialEq)]
+enum Prev {
+    /// Start of line, an opening bracket, or a unary operator: no space follows.
+    Tight,
+    /// An identifier, number, string, or closing bracket.
+    Operand,
+    /// A comma: exactly one space follows.
+    Comma,
+}
+
+/// Words after which `-` is a sign rather than subtraction: `प्रतिफल -x`.
+const KEYWORDS: &[&str] = &["प्रतिफल", "in", "यदि", "यावत्", "तथा", "वा", "न"];
+
+const TWO_CHAR_OPS: &[&str] = &["==", "!=", "<=", ">=", "**", "//"];
+
+/// Normalise spacing within a single (already trimmed) line of code.
+fn format_line(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut prev = Prev::Tight;
+    let mut pending_space = false;
+    let mut depth = 0usize;
+    let mut word = String::new();
+    let mut last_word = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c != '"' && c != '\'' && !c.is_whitespace() && !is_operator_char(c) && !"()[]{},".contains(c) {
+            if prev == Prev::Comma || (pending_space && prev == Prev::Operand) {
+                out.push(' ');
+            }
+            out.push(c);
+            word.push(c);
+            prev = Prev::Operand;
+            pending_space = false;
+            i += 1;
+            continue;
+        }
+        // Anything else ends the current identifier/number, except the sign of an exponent.
+        let in_exponent = (c == '-' || c == '+') && is_exponent_prefix(&word);
+        if !in_exponent && !word.is_empty() {
+            last_word = std::mem::take(&mut word);
+        }
+
+        match c {
+            // A `"""` string, kept as written up to its end or the line's.
+            '"' if chars[i..].starts_with(&['"', '"', '"']) => {
+                if prev == Prev::Comma || (pending_space && prev == Prev::Operand) {
+                    out.push(' ');
+                }
+                let line_rest: String = chars[i..].iter().collect();
+                let len = 3 + line_rest[3..].find(TRIPLE_QUOTE).map_or(line_rest.len() - 3, |end| end + 3);
+                out.push_str(&line_rest[..len]);
+                i += line_rest[..len].chars().count() - 1;
+                prev = Prev::Operand;
+            }
+            '"' | '\'' => {
+                if prev == Prev::Comma || (pending_space && prev == Prev::Operand) {
+                    out.push(' ');
+                }
+                out.push(c);
+                i += 1;
+                while i < chars.len() {
+                    out.push(chars[i]);
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        out.push(chars[i + 1]);
+                        i += 1;
+                    } else if chars[i] == c {
+                        break;
+                    }
+                    i += 1;
+                }
+                prev = Prev::Operand;
+            }
+            c if c.is_whitespace() => {
+                pending_space = true;
+                i += 1;
+                continue;
+            }
+            '(' | '[' | '{' => {
+                if prev == Prev::Comma || (pending_space && prev == Prev::Operand) {
+                    out.push(' ');
+                }
+                out.push(c);
+                depth += 1;
+                prev = Prev::Tight;
+            }
+            ')' | ']' | '}' => {
+                out.push(c);
+                depth = depth.saturating_sub(1);
+                prev = Prev::Operand;
+            }
+            ',' => {
+                out.push(c);
+                prev = Prev::Comma;
+            }
+            _ if in_exponent => {
+                out.push(c);
+                word.push(c);
+                prev = Prev::Operand;
+            }
+            _ => {
+                let two: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let op = if TWO_CHAR_OPS.contains(&two.as_str()) {
+                    i += 1;
+                    two
+                } else {
+                    c.to_string()
+                };
+                let after_keyword = prev == Prev::Operand && KEYWORDS.contains(&last_word.as_str());
+                if (op == "-" || op == "*") && (prev != Prev::Operand || after_keyword) {
+                    // Unary minus or a `*list` spread.
+                    if prev == Prev::Comma || after_keyword {
+                        out.push(' ');
+                    }
+                    out.push_str(&op);
+                    prev = Prev::Tight;
+                } else if op == "=" && depth > 0 {
+                    // Named argument: `कुंजी=fn`
+                    out.push_str(&op);
+                    prev = Prev::Tight;
+                } else {
+                    out.push(' ');
+                    out.push_str(&op);
+                    out.push(' ');
+                    prev = Prev::Tight;
+                }
+            }
+        }
+        last_word.clear();
+        pending_space = false;
+        i += 1;
+    }
+    out
+}
+
+fn is_operator_char(c: char) -> bool {
+    matches!(c, '=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' | '%')
+}
+
+/// True when `word` is a number awaiting its exponent sign, like `1e` or `2.5E`.
+fn is_exponent_prefix(word: &str) -> bool {
+    match word.strip_suffix(['e', 'E']) {
+        Some(mantissa) => !mantissa.is_empty() && mantissa.parse::<f64>().is_ok(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_indentation_and_spacing() {
+        let src = "x=1+2*3   \nयदि (x>5):\n\tदर्श( \"x  बड़ा\" ,x )\nअन्यथा:\n  दर्श(-x)\n";
+        let expected = "x = 1 + 2 * 3\nयदि (x > 5):\n    दर्श(\"x  बड़ा\", x)\nअन्यथा:\n    दर्श(-x)\n";
+        assert_eq!(format_source(src), expected);
+    }
+
+    #[test]
+    fn test_keeps_named_args_spread_and_exponents_tight() {
+        let src = "दर्श(*सूची)\nक्रमबद्ध(सूची, कुंजी = f)\ny = 1e-5 - x\nz = a!=b\nप्रतिफल -x\nw = - 2";
+        let expected = "दर्श(*सूची)\nक्रमबद्ध(सूची, कुंजी=f)\ny = 1e-5 - x\nz = a != b\nप्रतिफल -x\nw = -2\n";
+        assert_eq!(format_source(src), expected);
+    }
+
+    #[test]
+    fn test_nested_blocks_and_comments() {
+        let src = "कार्य f(n):\n  !! टिप्पणी\n  यदि (n > 0):\n      प्रतिफल n\n\n\n  प्रतिफल 0\nदर्श(f(2))";
+        let expected = "कार्य f(n):\n    !! टिप्पणी\n    यदि (n > 0):\n        प्रतिफल n\n\n    प्रतिफल 0\nदर्श(f(2))\n";
+        assert_eq!(format_source(src), expected);
+    }
+
+    #[test]
+    fn test_multi_line_strings_are_kept_as_written() {
+        let src = "x=\"\"\"a=b\n  c+d,e\n\n\"\"\"\nयदि (x):\n  दर्श(\"\"\"  p+q \"\"\",x)\n";
+        let expected = "x = \"\"\"a=b\n  c+d,e\n\n\"\"\"\nयदि (x):\n    दर्श(\"\"\"  p+q \"\"\", x)\n";
+        assert_eq!(format_source(src), expected);
+        assert_eq!(format_source(expected), expected);
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let src = "सूची=[1,2 ,3]\nकोश = {\"क\":1}\nपरिभ्रमण i in परिधि(3):\n\tयावत् (i<2):\n\t\ti=i+1\n  दर्श(\"i = \"+i)\n";
+        let once = format_source(src);
+        assert_eq!(format_source(&once), once);
+        for example in ["examples/hello.paanini", "examples/loops.paanini"] {
+            if let Ok(src) = std::fs::read_to_string(example) {
+                let once = format_source(&src);
+                assert_eq!(format_source(&once), once, "{}", example);
+            }
+        }
+    }
+}
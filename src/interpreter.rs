@@ -1,716 +1,3390 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::ast::{
+    self, bool_to_sanskrit, decode_escapes, is_close_bracket, is_open_bracket, join_triple_quoted, string_literal,
+    unpack_count_error, Arg, Cond, Expr, Line, PrintArg, Stmt, StrScan,
+};
 
 pub struct RunResult {
     pub output: String,
     pub errors: Vec<String>,
+    /// Executed statements, assignments and loop iterations, when tracing is on.
+    pub trace: Vec<String>,
+    /// Whether the run read the clock or drew random numbers without seeding
+    /// them with `बीज`, so running it again may give different output.
+    pub nondeterministic: bool,
+}
+
+/// One top-level statement run by `Interpreter::steps`.
+#[derive(Debug)]
+pub struct Step {
+    /// Source line the statement starts on.
+    pub line: usize,
+    pub text: String,
+    pub output: String,
+    pub errors: Vec<String>,
+    /// Every variable once the statement has run, shown as `रूप` shows it.
+    pub vars: BTreeMap<String, String>,
+}
+
+/// Runs a program one top-level statement per `next`; see `Interpreter::steps`.
+pub struct Steps<'a> {
+    interpreter: &'a mut Interpreter,
+    program: std::vec::IntoIter<Line>,
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        let line = self.program.next()?;
+        let result = self.interpreter.run_parsed(std::slice::from_ref(&line));
+        let vars = self.interpreter.vars.iter().map(|(name, value)| (name.clone(), repr(value))).collect();
+        Some(Step { line: line.number, text: line.text, output: result.output, errors: result.errors, vars })
+    }
+}
+
+/// How deeply user-function calls may nest before a call fails, unless
+/// changed with `Interpreter::set_recursion_limit`.
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// Most passes a `यावत्`, `कुरु` or `चक्र` loop makes, to stop runaway
+/// loops, unless changed with `Interpreter::set_loop_limit`.
+pub const DEFAULT_LOOP_LIMIT: usize = 10_000;
+
+/// Start of the error for a call past the recursion limit.
+const TOO_DEEP: &str = "त्रुटिः: अतिगभीरः पुनरावर्तनम्";
+
+/// Calls to one user function, collected while profiling (see `set_profile`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub calls: usize,
+    /// Wall-clock time spent in the function, including the functions it
+    /// calls. Recursive calls are timed once, as part of the outermost call.
+    pub time: Duration,
+}
+
+/// The limits and permissions an interpreter runs programs with, as
+/// `/api/info` reports them.
+#[derive(Debug, serde::Serialize)]
+pub struct Capabilities {
+    pub file_access: bool,
+    pub env_access: bool,
+    pub max_iterations: usize,
+    pub recursion_limit: usize,
+    /// Longest one `विश्राम` may sleep, in milliseconds; unlimited when `None`.
+    pub max_sleep_ms: Option<u64>,
+}
+
+/// A finished run as `paanini run --json` prints it, with each error split
+/// into its line and message.
+#[derive(Debug, serde::Serialize)]
+pub struct RunReport {
+    pub output: String,
+    pub errors: Vec<InterpError>,
+    pub exit_code: i32,
+}
+
+impl From<RunResult> for RunReport {
+    fn from(result: RunResult) -> Self {
+        RunReport {
+            exit_code: if result.errors.is_empty() { 0 } else { 1 },
+            output: result.output,
+            errors: result.errors.iter().map(|e| InterpError::from_reported(e)).collect(),
+        }
+    }
+}
+
+/// Why evaluation or checking failed. Errors raised while evaluating an
+/// expression have no line yet; `run_block` prefixes the statement's line.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct InterpError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl InterpError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        InterpError {
+            line: Some(line),
+            message: message.into(),
+        }
+    }
+
+    /// Parse an error as `run` reports it, `Line N: message`, back into its
+    /// parts. Anything after the first prefix (such as the line inside a
+    /// called function) stays in the message.
+    pub fn from_reported(error: &str) -> Self {
+        let parsed = error.strip_prefix("Line ").and_then(|rest| {
+            let (line, message) = rest.split_once(": ")?;
+            Some(InterpError::new(line.parse().ok()?, message))
+        });
+        parsed.unwrap_or_else(|| error.into())
+    }
+}
+
+impl From<String> for InterpError {
+    fn from(message: String) -> Self {
+        InterpError { line: None, message }
+    }
+}
+
+impl From<&str> for InterpError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
 }
 
-#[derive(Clone, Debug)]
+impl std::fmt::Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 enum Value {
     Number(f64),
     Str(String),
     Bool(bool),
     List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+    Function(String),
+    /// A function with leading arguments pre-bound by `अंश`.
+    Partial(String, Vec<Value>),
     Null,
 }
 
+/// The variables saved by `Interpreter::export_state`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct State {
+    vars: BTreeMap<String, Value>,
+    /// Which of `vars` were bound with `ध्रुव`.
+    #[serde(default)]
+    constants: Vec<String>,
+}
+
+/// `रूप(x)`: `x` written the way it would appear in source, so strings are
+/// quoted (and escaped) and nested lists and maps keep their structure.
+fn repr(value: &Value) -> String {
+    match value {
+        Value::Str(s) => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        Value::List(items) => format!("[{}]", items.iter().map(repr).collect::<Vec<_>>().join(", ")),
+        Value::Map(map) => {
+            let entries = map.iter().map(|(k, v)| format!("{}: {}", repr(&Value::Str(k.clone())), repr(v)));
+            format!("{{{}}}", entries.collect::<Vec<_>>().join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
             Value::Str(s) => write!(f, "{}", s),
-            Value::Bool(b) => write!(f, "{}", if *b { "सत्य" } else { "असत्य" }),
+            Value::Bool(b) => write!(f, "{}", bool_to_sanskrit(*b)),
             Value::List(v) => {
                 let s = v.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ");
                 write!(f, "[{}]", s)
             }
-            Value::Null => write!(f, "null"),
+            Value::Map(m) => {
+                let s = m
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{{{}}}", s)
+            }
+            Value::Function(name) => write!(f, "<कार्य {}>", name),
+            Value::Partial(name, bound) => write!(f, "<अंश {} ({} तर्काः)>", name, bound.len()),
+            Value::Null => write!(f, "शून्य"),
         }
     }
 }
 
+/// Keyword arguments of a call, in source order: `कुंजी=fn`.
+type KwArgs = Vec<(String, Value)>;
+
 #[derive(Clone)]
 struct FunctionDef {
     params: Vec<String>,
-    body: String,
+    /// Parsed once when the function is defined; calls share it.
+    body: Arc<Vec<Line>>,
+    /// Docstring: the string-literal lines at the top of the body, unquoted.
+    doc: Vec<String>,
+    /// Names declared `वैश्विक`; the call writes them back to the caller.
+    globals: Vec<String>,
+}
+
+/// Source of lines for `निवेश`; `None` means no more input.
+pub trait InputProvider: Send + Sync {
+    fn read_line(&self, prompt: &str) -> Option<String>;
+}
+
+/// Reads from the process's stdin, printing the prompt first.
+struct StdinInput;
+
+impl InputProvider for StdinInput {
+    fn read_line(&self, prompt: &str) -> Option<String> {
+        print!("{}", prompt);
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+/// Input provider for contexts without a terminal, such as the web server.
+pub struct NoInput;
+
+impl InputProvider for NoInput {
+    fn read_line(&self, _prompt: &str) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct Interpreter {
     vars: HashMap<String, Value>,
+    /// Names bound with `ध्रुव`; assigning to them again is an error.
+    constants: HashSet<String>,
     functions: HashMap<String, FunctionDef>,
+    /// Output produced so far by the current `run`, including nested blocks and calls.
+    output: String,
+    /// Set by `प्रतिफल`; unwinds the enclosing blocks until `call_function` takes it.
+    returning: Option<Value>,
+    call_depth: usize,
+    /// Labels of the loops around the running statement, innermost last;
+    /// `None` for an unlabeled loop. Each call starts with none.
+    loops: Vec<Option<String>>,
+    /// Set by `विरम`, with its label if any; unwinds the enclosing blocks
+    /// until the loop it leaves takes it.
+    breaking: Option<Option<String>>,
+    /// Deepest allowed `call_depth`; `DEFAULT_RECURSION_LIMIT` when unset.
+    recursion_limit: Option<usize>,
+    /// Most passes one loop may make; `DEFAULT_LOOP_LIMIT` when unset.
+    loop_limit: Option<usize>,
+    /// Where `निवेश` reads from; stdin when unset.
+    input: Option<Arc<dyn InputProvider>>,
+    /// Receives each line of output as it is produced (see `run_with_sink`).
+    sink: Option<OutputSink>,
+    /// When another thread sets this, execution stops at the next statement.
+    cancel: Option<Arc<AtomicBool>>,
+    /// Whether `पठ` and `लिख` may touch the file system (see `set_file_access`).
+    file_access: bool,
+    /// Whether `पर्यावरण` may read environment variables (see `set_env_access`).
+    env_access: bool,
+    /// Longest a single `विश्राम` may sleep; unlimited when unset.
+    max_sleep: Option<Duration>,
+    /// Directory that `आयात` paths are relative to; imports are refused when unset.
+    base_dir: Option<PathBuf>,
+    /// Files currently being run, outermost first, to detect cyclic imports.
+    importing: Vec<PathBuf>,
+    /// Number of enclosing `प्रयत्न` blocks; while non-zero the first error is caught.
+    catching: usize,
+    /// The caught error, unwinding the blocks up to its `प्रयत्न`.
+    raised: Option<String>,
+    /// Inside a call, globals that a nested call assigned; they are passed on
+    /// to this call's caller in turn.
+    global_writes: HashSet<String>,
+    /// Whether to record a trace (see `set_trace`).
+    tracing: bool,
+    /// Whether a bare expression ending the source is echoed (see `set_interactive`).
+    interactive: bool,
+    /// Whether to count and time function calls (see `set_profile`).
+    profiling: bool,
+    profile: BTreeMap<String, FunctionProfile>,
+    /// Functions with a call in progress, so a recursive call isn't timed twice.
+    profiling_in: HashSet<String>,
+    /// Generator behind `यादृच्छिक`, created on first use.
+    random: Option<Random>,
+    /// Whether the current run read the clock or drew from an unseeded
+    /// generator.
+    nondeterministic: bool,
+    trace: Vec<String>,
 }
 
+/// Random number state: seeded by `बीज`, or else from the OS.
+#[derive(Clone)]
+struct Random {
+    rng: StdRng,
+    seeded: bool,
+}
+
+/// Library functions written in Paanini itself, loaded by `Interpreter::with_prelude`.
+#[derive(rust_embed::RustEmbed)]
+#[folder = "prelude"]
+struct Prelude;
+
+/// Callback for streaming output: called once per `दर्श` with the printed text.
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
+
 impl Interpreter {
-    pub fn run(&mut self, src: &str) -> RunResult {
-        let mut out = String::new();
-        let mut errs = Vec::new();
+    /// An interpreter with the embedded prelude (`prelude/*.paanini`) already
+    /// run, so its functions can be called without importing anything.
+    /// `Interpreter::default()` starts empty.
+    pub fn with_prelude() -> Self {
+        let mut interpreter = Self::default();
+        let mut files: Vec<_> = Prelude::iter().collect();
+        files.sort();
+        for file in files {
+            let src = Prelude::get(&file).expect("listed prelude file is embedded");
+            let errors = interpreter.run_block(&String::from_utf8_lossy(&src.data), 0);
+            debug_assert!(errors.is_empty(), "prelude {}: {:?}", file, errors);
+        }
+        interpreter
+    }
 
-        let norm = preprocess_indentation(src);
-        let lines: Vec<String> = norm.lines().map(|l| l.to_string()).collect();
-        let mut i = 0usize;
-        while i < lines.len() {
-            let line = lines[i].trim();
-            if line.is_empty() || line.starts_with("!!") || line.starts_with('#') {
-                i += 1;
-                continue;
-            }
+    /// The variables and constants bound so far, as JSON, to be restored
+    /// later with `import_state`. Functions aren't included: a variable
+    /// holding one keeps only its name, which needs the function defined
+    /// again to be called.
+    pub fn export_state(&self) -> String {
+        let mut constants: Vec<String> = self.constants.iter().cloned().collect();
+        constants.sort();
+        let state = State { vars: self.vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect(), constants };
+        serde_json::to_string(&state).expect("values serialize")
+    }
 
-            if line.starts_with("यदि") {
-                match self.handle_if_else(&lines, i) {
-                    Ok((consumed, block_out, block_errs)) => {
-                        out.push_str(&block_out);
-                        errs.extend(block_errs);
-                        i += consumed;
-                        continue;
-                    }
-                    Err(e) => {
-                        errs.push(format!("Line {}: {}", i + 1, e));
-                        i += 1;
-                        continue;
-                    }
-                }
-            }
+    /// Replace every variable and constant with those in `json`, as written
+    /// by `export_state`. Functions are kept.
+    pub fn import_state(&mut self, json: &str) -> Result<(), String> {
+        let state: State = serde_json::from_str(json).map_err(|e| format!("त्रुटिः: अवैधा स्थितिः ({})", e))?;
+        self.vars = state.vars.into_iter().collect();
+        self.constants = state.constants.into_iter().collect();
+        Ok(())
+    }
 
-            if line.starts_with("यावत्") {
-                match self.handle_while(&lines, i) {
-                    Ok((consumed, block_out, block_errs)) => {
-                        out.push_str(&block_out);
-                        errs.extend(block_errs);
-                        i += consumed;
-                        continue;
-                    }
-                    Err(e) => {
-                        errs.push(format!("Line {}: {}", i + 1, e));
-                        i += 1;
-                        continue;
-                    }
-                }
-            }
+    /// Docstrings of the user functions defined so far, sorted by function name.
+    pub fn function_docs(&self) -> Vec<(String, Vec<String>)> {
+        let mut docs: Vec<_> = self
+            .functions
+            .iter()
+            .filter(|(_, def)| !def.doc.is_empty())
+            .map(|(name, def)| (name.clone(), def.doc.clone()))
+            .collect();
+        docs.sort();
+        docs
+    }
 
-            if line.starts_with("परिभ्रमण") {
-                match self.handle_for(&lines, i) {
-                    Ok((consumed, block_out, block_errs)) => {
-                        out.push_str(&block_out);
-                        errs.extend(block_errs);
-                        i += consumed;
-                        continue;
-                    }
-                    Err(e) => {
-                        errs.push(format!("Line {}: {}", i + 1, e));
-                        i += 1;
-                        continue;
-                    }
-                }
-            }
+    /// Share a flag that stops the running program once set, e.g. on a timeout.
+    pub fn set_cancel_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.cancel = Some(flag);
+    }
 
-            if line.starts_with("कार्य") {
-                match self.handle_function_def(&lines, i) {
-                    Ok(consumed) => {
-                        i += consumed;
-                        continue;
-                    }
-                    Err(e) => {
-                        errs.push(format!("Line {}: {}", i + 1, e));
-                        i += 1;
-                        continue;
-                    }
-                }
-            }
+    /// Bind `आदेशतर्क` to `args`, the command-line arguments given after the
+    /// program's file name.
+    pub fn set_args(&mut self, args: &[String]) {
+        let args = args.iter().cloned().map(Value::Str).collect();
+        self.vars.insert(ARGS_VAR.to_string(), Value::List(args));
+    }
 
-            match self.exec_line(line) {
-                Ok(Some(s)) => {
-                    out.push_str(&s);
-                    if !s.ends_with('\n') {
-                        out.push('\n');
-                    }
-                }
-                Ok(None) => {}
-                Err(e) => errs.push(format!("Line {}: {}", i + 1, e)),
-            }
-            i += 1;
-        }
-        RunResult { output: out, errors: errs }
+    /// Allow `पठ` and `लिख` to read and write files, resolving relative paths
+    /// like `आयात` does. Off by default, so untrusted code can't reach the disk.
+    pub fn set_file_access(&mut self, on: bool) {
+        self.file_access = on;
     }
 
-    fn exec_line(&mut self, line: &str) -> Result<Option<String>, String> {
-        let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with("!!") || trimmed.starts_with('#') {
-            return Ok(None);
-        }
-
-        // Assignment: name = expr (but not ==, >=, <=)
-        if let Some(eq) = find_top_level_char(trimmed, '=') {
-            let left_is_cmp = eq > 0 && trimmed.as_bytes().get(eq - 1) == Some(&b'=');
-            let right_is_cmp = trimmed.as_bytes().get(eq + 1) == Some(&b'=');
-            let ge = eq > 0 && trimmed.as_bytes().get(eq - 1) == Some(&b'>');
-            let le = eq > 0 && trimmed.as_bytes().get(eq - 1) == Some(&b'<');
-            if !(left_is_cmp || right_is_cmp || ge || le) {
-                let left = trimmed[..eq].trim();
-                let right = trimmed[eq + 1..].trim();
-                if !is_valid_identifier(left) {
-                    return Err("त्रुटिः: असाइनस्य नाम अवैधम्".into());
-                }
-                let val = self
-                    .eval_expr(right)
-                    .ok_or_else(|| format!("त्रुटिः: अभिव्यक्ति न संगृहीता -> {}", right))?;
-                self.vars.insert(left.to_string(), val);
-                return Ok(None);
-            }
-        }
-
-        // Print: दर्श(expr)
-        if trimmed.starts_with("दर्श") {
-            let rest = trimmed.strip_prefix("दर्श").unwrap().trim_start();
-            if !rest.starts_with('(') || !trimmed.ends_with(')') {
-                return Err("त्रुटिः: दर्श प्रयोगः केवलं दर्श(expr) स्वरूपेण भवेत्".into());
-            }
-            let lp = trimmed.find('(').unwrap();
-            let rp = trimmed.rfind(')').unwrap();
-            let inner = &trimmed[lp + 1..rp];
-            let val = self.eval_expr(inner).unwrap_or(Value::Null);
-            return Ok(Some(format!("{}", val)));
-        }
-
-        // Function call as a statement: name(...)
-        if let Some(lp) = trimmed.find('(') {
-            if trimmed.ends_with(')') {
-                let name = trimmed[..lp].trim();
-                if is_valid_identifier(name) {
-                    let args_str = &trimmed[lp + 1..trimmed.len() - 1];
-                    let args = split_args(args_str)?;
-                    let arg_vals: Vec<Value> = args
-                        .into_iter()
-                        .map(|a| self.eval_expr(a))
-                        .collect::<Option<Vec<_>>>()
-                        .ok_or_else(|| "त्रुटिः: तर्काः न संगृहीताः".to_string())?;
-                    let _ = self.call_function(name, arg_vals)?; // ignore return
-                    return Ok(None);
-                }
-            }
-        }
+    /// Allow `पर्यावरण` to read the process's environment variables. Off by
+    /// default, since they often hold secrets.
+    pub fn set_env_access(&mut self, on: bool) {
+        self.env_access = on;
+    }
 
-        if trimmed == "help" {
-            return Ok(Some(
-                "Paanini आज्ञाः (Python-रूपेण):\n  x = 5\n  नाम = \"नमस्ते\"\n  दर्श(expr)\n  यदि x == 5:\n    दर्श(\"सत्यं\")\n  अन्यथा:\n    दर्श(\"असत्यं\")\n  यावत् x < 5:\n    दर्श(x)\n    x = x + 1\n  परिभ्रमण i in परिधि(5):\n    दर्श(i)\n  कार्य greet(नाम):\n    दर्श(\"नमस्ते \" + नाम)\n  greet(\"विश्व\")\n  !! टिप्पण्यः\n"
-                    .to_string(),
-            ));
-        }
+    /// Cap each `विश्राम` at `max`, e.g. so a server request can't hold a
+    /// thread for long.
+    pub fn set_max_sleep(&mut self, max: Duration) {
+        self.max_sleep = Some(max);
+    }
 
-        Err(format!("अज्ञाता आज्ञा: {}", trimmed))
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|flag| flag.load(AtomicOrdering::Relaxed))
     }
 
-    fn eval_expr(&self, expr: &str) -> Option<Value> {
-        let s = expr.trim();
-        if s.is_empty() {
-            return Some(Value::Null);
-        }
-        // Parentheses unwrap
-        if s.starts_with('(') && s.ends_with(')') {
-            if let Some((start, end)) = outer_paren_bounds(s) {
-                if start == 0 && end == s.len() - 1 {
-                    return self.eval_expr(&s[1..s.len() - 1]);
-                }
-            }
-        }
-        // String literal
-        if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
-            return Some(Value::Str(s[1..s.len() - 1].to_string()));
-        }
-        // Boolean
-        if s == "सत्य" {
-            return Some(Value::Bool(true));
-        }
-        if s == "असत्य" {
-            return Some(Value::Bool(false));
-        }
-        // Function call within expression
-        if let Some(lp) = s.find('(') {
-            if s.ends_with(')') {
-                let name = s[..lp].trim();
-                if is_valid_identifier(name) {
-                    let args_str = &s[lp + 1..s.len() - 1];
-                    let args = match split_args(args_str) { Ok(v) => v, Err(_) => return None };
-                    let arg_vals: Vec<Value> = match args
-                        .into_iter()
-                        .map(|a| self.eval_expr(a))
-                        .collect::<Option<Vec<_>>>() {
-                        Some(v) => v,
-                        None => return None,
-                    };
-                    return self.call_function(name, arg_vals).ok();
-                }
-            }
-        }
-        // Number
-        if let Ok(n) = s.parse::<f64>() {
-            return Some(Value::Number(n));
-        }
-        // Addition/concatenation at top level
-        if let Some(idx) = find_top_level_plus(s) {
-            let lv = self.eval_expr(&s[..idx])?;
-            let rv = self.eval_expr(&s[idx + 1..])?;
-            return match (lv, rv) {
-                (Value::Number(a), Value::Number(b)) => Some(Value::Number(a + b)),
-                (Value::Str(a), Value::Str(b)) => Some(Value::Str(format!("{}{}", a, b))),
-                (Value::Str(a), v) => Some(Value::Str(format!("{}{}", a, v))),
-                (v, Value::Str(b)) => Some(Value::Str(format!("{}{}", v, b))),
-                _ => None,
-            };
-        }
-        // Variable lookup
-        if is_valid_identifier(s) {
-            if let Some(v) = self.vars.get(s) {
-                return Some(v.clone());
+    /// True while `प्रतिफल`, `विरम`, a caught error, or a cancellation is
+    /// unwinding the enclosing blocks.
+    fn unwinding(&self) -> bool {
+        self.returning.is_some() || self.breaking.is_some() || self.raised.is_some() || self.is_cancelled()
+    }
+
+    /// After one pass of the loop labeled `label`: whether to leave it. A
+    /// `विरम` meant for this loop stops here; one for an outer loop carries on
+    /// unwinding.
+    fn leave_loop(&mut self, label: &Option<String>) -> bool {
+        if let Some(target) = &self.breaking {
+            if target.is_none() || target == label {
+                self.breaking = None;
             }
+            return true;
         }
-        None
+        self.unwinding()
     }
 
-    fn eval_condition(&self, cond: &str) -> Result<bool, String> {
-        let ops = ["==", "!=", ">=", "<=", ">", "<"];
-        for op in ops.iter() {
-            if let Some(p) = find_top_level_op(cond, op) {
-                let left = cond[..p].trim();
-                let right = cond[p + op.len()..].trim();
-                let lv = self
-                    .eval_expr(left)
-                    .ok_or_else(|| "त्रुटिः: यदि शर्ता अपठिता".to_string())?;
-                let rv = self
-                    .eval_expr(right)
-                    .ok_or_else(|| "त्रुटिः: यदि शर्ता अपठिता".to_string())?;
-                return match (lv, rv, *op) {
-                    (Value::Number(a), Value::Number(b), "==") => Ok(a == b),
-                    (Value::Number(a), Value::Number(b), "!=") => Ok(a != b),
-                    (Value::Number(a), Value::Number(b), ">") => Ok(a > b),
-                    (Value::Number(a), Value::Number(b), "<") => Ok(a < b),
-                    (Value::Number(a), Value::Number(b), ">=") => Ok(a >= b),
-                    (Value::Number(a), Value::Number(b), "<=") => Ok(a <= b),
-                    _ => Err("त्रुटिः: यदि शर्ते संख्यायाः तुलनाः एव समर्थिताः".into()),
-                };
-            }
+    /// Inside a `प्रयत्न` block, turn the first error into `raised` so the
+    /// blocks unwind to it instead of carrying on with the next statement.
+    fn catch_first_error(&mut self, errs: &[String]) {
+        if self.catching > 0 && self.raised.is_none() {
+            self.raised = errs.first().cloned();
         }
-        Err("त्रुटिः: यदि शर्ता अवैध".into())
     }
 
-    fn handle_if_else(
-        &mut self,
-        lines: &Vec<String>,
-        start: usize,
-    ) -> Result<(usize, String, Vec<String>), String> {
-        let mut output = String::new();
-        let mut errors = Vec::new();
-        let line = lines[start].trim();
-        let lp = line
-            .find('(')
-            .ok_or_else(|| "त्रुटिः: यदि शर्ता ( ) मध्ये भवेत्".to_string())?;
-        let rp = line
-            .rfind(')')
-            .ok_or_else(|| "त्रुटिः: यदि शर्ता ( ) मध्ये भवेत्".to_string())?;
-        let cond_str = &line[lp + 1..rp];
-        let cond = self.eval_condition(cond_str)?;
-        let (then_block, consumed_then) = collect_block(lines, start)?;
-        let mut total = consumed_then;
-
-        // search for else after then block
-        let mut idx = start + consumed_then;
-        while idx < lines.len() {
-            let l = lines[idx].trim();
-            if l.is_empty() || l.starts_with("!!") || l.starts_with('#') {
-                idx += 1;
-                continue;
-            }
-            if l.starts_with("अन्यथा") {
-                let (else_block, consumed_else) = collect_block(lines, idx)?;
-                total = (idx + consumed_else) - start;
-                if cond {
-                    let res = self.run(&then_block);
-                    output.push_str(&res.output);
-                    errors.extend(res.errors);
-                } else {
-                    let res = self.run(&else_block);
-                    output.push_str(&res.output);
-                    errors.extend(res.errors);
-                }
-                return Ok((total, output, errors));
-            }
-            break;
+    /// Allow `आयात`, resolving relative paths against `dir`.
+    pub fn set_base_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.base_dir = Some(dir.into());
+    }
+
+    /// Record that the program being run is the file at `path`: imports resolve
+    /// against its directory, and importing it again is reported as a cycle.
+    pub fn set_source_path(&mut self, path: &Path) {
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        self.set_base_dir(dir);
+        self.importing = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    }
+
+    /// `आयात "path"`: run another file in this interpreter, so its functions
+    /// and variables become available here.
+    fn import(&mut self, spec: &str) -> Result<(), String> {
+        let rel = string_literal(spec).ok_or("त्रुटिः: आयात प्रयोगः आयात \"सञ्चिका\" स्वरूपेण भवेत्")?;
+        let base = self.base_dir.clone().ok_or("त्रुटिः: आयातः अत्र न अनुमतः")?;
+        let rel = decode_escapes(rel)?;
+        let path = base.join(&rel);
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| format!("त्रुटिः: सञ्चिका {} पठितुं न शक्यते ({})", rel, e))?;
+        if self.importing.contains(&canonical) {
+            return Err(format!("त्रुटिः: चक्रीयः आयातः: {}", rel));
         }
-        if cond {
-            let res = self.run(&then_block);
-            output.push_str(&res.output);
-            errors.extend(res.errors);
+        let src = std::fs::read_to_string(&canonical)
+            .map_err(|e| format!("त्रुटिः: सञ्चिका {} पठितुं न शक्यते ({})", rel, e))?;
+        check_balanced(&src).map_err(|e| format!("{}: {}", rel, e))?;
+
+        let dir = canonical.parent().map(Path::to_path_buf);
+        let previous_dir = std::mem::replace(&mut self.base_dir, dir);
+        self.importing.push(canonical);
+        let errors = self.run_block(&src, 0);
+        self.importing.pop();
+        self.base_dir = previous_dir;
+        if !errors.is_empty() {
+            return Err(format!("{}: {}", rel, errors.join("; ")));
         }
-        Ok((total, output, errors))
+        Ok(())
     }
 
-    fn handle_while(
-        &mut self,
-        lines: &Vec<String>,
-        start: usize,
-    ) -> Result<(usize, String, Vec<String>), String> {
-        let mut output = String::new();
-        let mut errors = Vec::new();
-        let line = lines[start].trim();
-        let lp = line
-            .find('(')
-            .ok_or_else(|| "त्रुटिः: यावत् शर्ता ( ) मध्ये भवेत्".to_string())?;
-        let rp = line
-            .rfind(')')
-            .ok_or_else(|| "त्रुटिः: यावत् शर्ता ( ) मध्ये भवेत्".to_string())?;
-        let cond_str = &line[lp + 1..rp];
-        let (body, consumed) = collect_block(lines, start)?;
-        let mut guard = 0usize;
-        while guard < 10000 {
-            guard += 1;
-            if self.eval_condition(cond_str).unwrap_or(false) {
-                let res = self.run(&body);
-                output.push_str(&res.output);
-                errors.extend(res.errors);
-            } else {
-                break;
-            }
-        }
-        Ok((consumed, output, errors))
+    /// Record each executed statement, each assignment with its new value,
+    /// and each loop iteration in `RunResult::trace`.
+    pub fn set_trace(&mut self, on: bool) {
+        self.tracing = on;
     }
 
-    fn handle_for(
-        &mut self,
-        lines: &Vec<String>,
-        start: usize,
-    ) -> Result<(usize, String, Vec<String>), String> {
-        let mut output = String::new();
-        let mut errors = Vec::new();
-        let line = lines[start].trim();
-        // परिभ्रमण x in परिधि(n)
-        let after_kw = line
-            .strip_prefix("परिभ्रमण")
-            .ok_or_else(|| "त्रुटिः: परिभ्रमण वाक्य अवैधम्".to_string())?
-            .trim_start();
-        let in_pos = after_kw
-            .find(" in ")
-            .ok_or_else(|| "त्रुटिः: परिभ्रमण स्वरूपः: परिभ्रमण x in परिधि(n)".to_string())?;
-        let var = after_kw[..in_pos].trim();
-        if !is_valid_identifier(var) {
-            return Err("त्रुटिः: परिभ्रमण चरः अवैधः".into());
-        }
-        let iter_part = after_kw[in_pos + 4..].trim();
-        let lp = iter_part
-            .find('(')
-            .ok_or_else(|| "त्रुटिः: परिभ्रमण परिधि( ) अपेक्षितम्".to_string())?;
-        let rp = iter_part
-            .rfind(')')
-            .ok_or_else(|| "त्रुटिः: परिभ्रमण परिधि( ) अपेक्षितम्".to_string())?;
-        let name = iter_part[..lp].trim();
-        let arg = iter_part[lp + 1..rp].trim();
-        if name != "परिधि" {
-            return Err("त्रुटिः: परिभ्रमण केवलं परिधि(n) सह समर्थितम्".into());
-        }
-        let n = match self.eval_expr(arg) {
-            Some(Value::Number(x)) => x as i64,
-            _ => return Err("त्रुटिः: परिधि(n) मध्ये n संख्या भवेत्".into()),
-        };
-        let (body, consumed) = collect_block(lines, start)?;
-        for i in 0..n {
-            self.vars
-                .insert(var.to_string(), Value::Number(i as f64));
-            let res = self.run(&body);
-            output.push_str(&res.output);
-            errors.extend(res.errors);
-        }
-        Ok((consumed, output, errors))
+    /// Run sources as REPL input: a bare expression (or call) on the last
+    /// line has its value shown as `रूप` would, so `2 + 3` prints `5`. A
+    /// `शून्य` result, such as from a function without `प्रतिफल`, is not shown.
+    /// Also used for `paanini run --echo-last`.
+    pub fn set_interactive(&mut self, on: bool) {
+        self.interactive = on;
     }
 
-    fn handle_function_def(
-        &mut self,
-        lines: &Vec<String>,
-        start: usize,
-    ) -> Result<usize, String> {
-        let line = lines[start].trim();
-        // कार्य name(params)
-        let rest = line
-            .strip_prefix("कार्य")
-            .ok_or_else(|| "त्रुटिः: कार्य स्वरूप अवैधः".to_string())?
-            .trim_start();
-        let lp = rest
-            .find('(')
-            .ok_or_else(|| "त्रुटिः: कार्य नामस्य अनन्तरं ( अपेक्षितम्".to_string())?;
-        let rp = rest
-            .rfind(')')
-            .ok_or_else(|| "त्रुटिः: कार्य तर्काणां ')' न लब्धम्".to_string())?;
-        let name = rest[..lp].trim();
-        if !is_valid_identifier(name) {
-            return Err("त्रुटिः: कार्य नाम अवैधम्".into());
-        }
-        let params_str = &rest[lp + 1..rp];
-        let params = if params_str.trim().is_empty() {
-            Vec::new()
-        } else {
-            params_str
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect()
-        };
-        for p in &params {
-            if !is_valid_identifier(p) {
-                return Err("त्रुटिः: कार्य तर्कस्य नाम अवैधम्".into());
-            }
-        }
-        let (body, consumed) = collect_block(lines, start)?;
-        self.functions
-            .insert(name.to_string(), FunctionDef { params, body });
-        Ok(consumed)
+    /// Fail calls nested more than `limit` deep with "अतिगभीरः पुनरावर्तनम्"
+    /// instead of recursing further. Defaults to `DEFAULT_RECURSION_LIMIT`.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = Some(limit);
     }
 
-    fn call_function(&self, name: &str, args: Vec<Value>) -> Result<Value, String> {
-        // Builtins
-        if name == "परिधि" {
-            if args.len() != 1 {
-                return Err("त्रुटिः: परिधि(n) एकः एव तर्कः".into());
-            }
-            let n = match args[0] {
-                Value::Number(x) => x as i64,
-                _ => return Err("त्रुटिः: परिधि(n) मध्ये n संख्या भवेत्".into()),
-            };
-            let list = (0..n).map(|i| Value::Number(i as f64)).collect::<Vec<_>>();
-            return Ok(Value::List(list));
-        }
-        if name == "दर्श" {
-            return Err("त्रुटिः: दर्श प्रयोगः केवलं दर्श(expr) स्वरूपेण भवेत्".into());
-        }
+    /// Stop `यावत्` and `कुरु` loops after `limit` passes, and fail `चक्र`
+    /// loops that reach it. Defaults to `DEFAULT_LOOP_LIMIT`.
+    pub fn set_loop_limit(&mut self, limit: usize) {
+        self.loop_limit = Some(limit);
+    }
 
-        if let Some(def) = self.functions.get(name) {
-            if def.params.len() != args.len() {
-                return Err("त्रुटिः: कार्य तर्कसंख्या न समा".into());
-            }
-            let mut child = self.clone();
-            for (p, v) in def.params.iter().zip(args.into_iter()) {
-                child.vars.insert(p.clone(), v);
-            }
-            let res = child.run(&def.body);
-            // No return yet
-            let _ = res; // silence unused var in case
-            return Ok(Value::Null);
+    fn loop_limit(&self) -> usize {
+        self.loop_limit.unwrap_or(DEFAULT_LOOP_LIMIT)
+    }
+
+    /// What programs run by this interpreter are allowed to do.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            file_access: self.file_access,
+            env_access: self.env_access,
+            max_iterations: self.loop_limit(),
+            recursion_limit: self.recursion_limit.unwrap_or(DEFAULT_RECURSION_LIMIT),
+            max_sleep_ms: self.max_sleep.map(|max| max.as_millis() as u64),
         }
-        Err(format!("त्रुटिः: अज्ञातः कार्यः: {}", name))
     }
-}
 
-fn is_valid_identifier(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
+    /// Count and time every call to a user-defined function, including calls
+    /// made through `प्रतिचित्र` and the like. The totals accumulate across
+    /// runs and are read with `profile`.
+    pub fn set_profile(&mut self, on: bool) {
+        self.profiling = on;
     }
-    s.chars()
-        .all(|c| c.is_alphanumeric() || c == '_' || (c as u32) > 127)
-}
 
-fn find_top_level_plus(s: &str) -> Option<usize> {
-    find_top_level_char(s, '+')
-}
+    /// Calls so far to each user function, by name, while profiling was on.
+    pub fn profile(&self) -> &BTreeMap<String, FunctionProfile> {
+        &self.profile
+    }
 
-fn find_top_level_char(s: &str, target: char) -> Option<usize> {
-    let mut in_str = false;
-    let mut depth = 0usize;
-    for (i, c) in s.char_indices() {
-        if c == '"' {
-            in_str = !in_str;
-            continue;
-        }
-        if in_str {
-            continue;
-        }
-        if c == '(' {
-            depth += 1;
-        }
-        if c == ')' && depth > 0 {
-            depth -= 1;
-        }
-        if depth == 0 && c == target {
-            return Some(i);
+    fn trace(&mut self, line_no: usize, entry: impl FnOnce() -> String) {
+        if self.tracing {
+            self.trace.push(format!("Line {}: {}", line_no, entry()));
         }
     }
-    None
-}
 
-fn find_top_level_op(s: &str, op: &str) -> Option<usize> {
-    let mut in_str = false;
-    let mut depth = 0usize;
-    let bytes = s.as_bytes();
-    let mut i = 0usize;
-    while i < s.len() {
-        let c = s[i..].chars().next().unwrap();
-        let clen = c.len_utf8();
-        if c == '"' {
-            in_str = !in_str;
-            i += clen;
-            continue;
-        }
-        if in_str {
-            i += clen;
-            continue;
+    /// Trace the value just assigned to `name`.
+    fn trace_assignment(&mut self, line_no: usize, name: &str) {
+        if !self.tracing {
+            return;
         }
-        if c == '(' {
-            depth += 1;
-            i += clen;
-            continue;
+        if let Some(value) = self.vars.get(name) {
+            let entry = format!("{} ← {}", name, value);
+            self.trace(line_no, || entry);
         }
-        if c == ')' {
-            if depth > 0 {
-                depth -= 1;
+    }
+
+    pub fn set_input_provider(&mut self, provider: Arc<dyn InputProvider>) {
+        self.input = Some(provider);
+    }
+
+    fn read_input(&self, prompt: &str) -> Result<String, String> {
+        let line = match &self.input {
+            Some(provider) => provider.read_line(prompt),
+            None => StdinInput.read_line(prompt),
+        };
+        line.ok_or_else(|| "त्रुटिः: निवेशः अनुपलब्धः".to_string())
+    }
+
+    /// `संख्या_निवेश(prompt, पुनःप्रश्न=सत्य)`: read a number, optionally re-asking on invalid input.
+    fn builtin_number_input(&mut self, args: Vec<Value>, kwargs: KwArgs) -> Result<Value, String> {
+        let prompt = match args.as_slice() {
+            [] => String::new(),
+            [Value::Str(p)] => p.clone(),
+            _ => return Err("त्रुटिः: संख्या_निवेश(prompt) पाठ्यम् एव स्वीकरोति".into()),
+        };
+        let mut reprompt = false;
+        for (key, val) in kwargs {
+            match (key.as_str(), val) {
+                ("पुनःप्रश्न", Value::Bool(b)) => reprompt = b,
+                ("पुनःप्रश्न", _) => return Err("त्रुटिः: पुनःप्रश्न सत्य/असत्य भवेत्".into()),
+                (other, _) => {
+                    return Err(format!("त्रुटिः: संख्या_निवेश अज्ञातं नामित-तर्कं {} न स्वीकरोति", other))
+                }
             }
-            i += clen;
-            continue;
         }
-        if depth == 0 {
-            if i + op.len() <= bytes.len() && &s[i..i + op.len()] == op {
-                return Some(i);
+        loop {
+            let line = self.read_input(&prompt)?;
+            match parse_number(&line) {
+                Some(n) => return Ok(Value::Number(n)),
+                None if reprompt => continue,
+                None => return Err(format!("त्रुटिः: निवेशः '{}' संख्या न", line)),
             }
         }
-        i += clen;
     }
-    None
-}
 
-fn split_args(s: &str) -> Result<Vec<&str>, String> {
-    let mut res = Vec::new();
-    let mut in_str = false;
-    let mut depth = 0usize;
-    let mut start = 0usize;
-    for (i, c) in s.char_indices() {
-        if c == '"' {
-            in_str = !in_str;
-            continue;
-        }
-        if in_str {
-            continue;
+    /// Parse `src` the way `run` does, without executing anything, and
+    /// return every problem found (see `ast::problems`). Line numbers follow
+    /// the same convention as `run`.
+    pub fn check(&self, src: &str) -> Vec<InterpError> {
+        if let Err(e) = check_balanced(src) {
+            return vec![e];
         }
-        if c == '(' {
-            depth += 1;
+        ast::problems(&self.parse_program(src))
+            .into_iter()
+            .map(|(line, msg)| InterpError::new(line, msg))
+            .collect()
+    }
+
+    pub fn run(&mut self, src: &str) -> RunResult {
+        self.run_with_sink(src, Arc::new(|_: &str| {}))
+    }
+
+    /// Like `run`, but also passes each piece of output to `sink` as soon as it
+    /// is printed, so callers can stream long-running programs.
+    pub fn run_with_sink(&mut self, src: &str, sink: OutputSink) -> RunResult {
+        let previous = self.sink.replace(sink);
+        let result = self.run_parsed(&self.parse_program(src));
+        self.sink = previous;
+        result
+    }
+
+    /// Run `src` one top-level statement at a time, for stepping through it
+    /// in a debugger. Each step reports what its statement printed and the
+    /// variables afterwards; a block statement, such as a loop, is one step.
+    pub fn steps(&mut self, src: &str) -> Steps<'_> {
+        let program = self.parse_program(src).into_iter();
+        Steps { interpreter: self, program }
+    }
+
+    fn parse_program(&self, src: &str) -> Vec<Line> {
+        if self.interactive {
+            ast::parse_interactive(src)
+        } else {
+            ast::parse(src)
         }
-        if c == ')' {
-            if depth > 0 {
-                depth -= 1;
-            }
+    }
+
+    fn run_parsed(&mut self, program: &[Line]) -> RunResult {
+        let start = self.output.len();
+        let trace_start = self.trace.len();
+        let mut errors = self.exec_block(program);
+        if self.is_cancelled() {
+            errors.push("त्रुटिः: निष्पादनं निरस्तम्".to_string());
         }
-        if c == ',' && depth == 0 {
-            res.push(s[start..i].trim());
-            start = i + 1;
+        RunResult {
+            output: self.output.split_off(start),
+            errors,
+            trace: self.trace.split_off(trace_start),
+            nondeterministic: std::mem::take(&mut self.nondeterministic),
         }
     }
-    if start <= s.len() {
-        res.push(s[start..].trim());
+
+    fn emit(&mut self, text: &str) {
+        self.output.push_str(text);
+        if let Some(sink) = &self.sink {
+            sink(text);
+        }
     }
-    Ok(res.into_iter().filter(|p| !p.is_empty()).collect())
-}
 
-fn outer_paren_bounds(s: &str) -> Option<(usize, usize)> {
-    if !s.starts_with('(') || !s.ends_with(')') {
-        return None;
+    /// Parse and run a block of statements. Line `i` (from 0) of `src` is line
+    /// `line_base + i + 1` of the source file, so errors in nested blocks and
+    /// function bodies report the user's line numbers.
+    fn run_block(&mut self, src: &str, line_base: usize) -> Vec<String> {
+        let block = ast::parse_block(src, line_base);
+        self.exec_block(&block)
     }
-    let mut depth = 0usize;
-    for (i, c) in s.char_indices() {
-        if c == '(' {
-            depth += 1;
-        }
-        if c == ')' {
-            depth -= 1;
-            if depth == 0 {
-                return Some((0, i));
+
+    /// Run parsed statements, returning their errors prefixed with their lines.
+    fn exec_block(&mut self, block: &[Line]) -> Vec<String> {
+        let mut errs = Vec::new();
+        for line in block {
+            self.catch_first_error(&errs);
+            if self.unwinding() {
+                break;
+            }
+            self.trace(line.number, || line.text.clone());
+            match self.exec_stmt(line, &mut errs) {
+                Ok(Some(s)) => {
+                    if s.ends_with('\n') {
+                        self.emit(&s);
+                    } else {
+                        self.emit(&format!("{}\n", s));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => errs.push(format!("Line {}: {}", line.number, e)),
             }
         }
+        self.catch_first_error(&errs);
+        errs
     }
-    None
-}
 
-// Convert indentation-based blocks to synthetic braces lines so block extraction works
-fn preprocess_indentation(src: &str) -> String {
-    let mut out = String::new();
-    let mut stack: Vec<usize> = vec![0];
-    let mut prev_ended_colon = false;
-    for orig in src.lines() {
-        let raw = orig.replace('\t', "  ");
-        let trimmed = raw.trim();
-        if trimmed.is_empty() || trimmed.starts_with("!!") || trimmed.starts_with('#') {
-            out.push_str(orig);
-            out.push('\n');
-            continue;
-        }
-        let indent = raw.chars().take_while(|c| *c == ' ').count();
-        let curr = *stack.last().unwrap();
-        if indent > curr {
-            if prev_ended_colon {
-                out.push_str("{\n");
-                stack.push(indent);
+    /// Run a `यावत्`, `परिभ्रमण`, `कुरु` or `चक्र` loop; `self.loops` already
+    /// holds its label.
+    fn exec_loop(&mut self, line: &Line, errs: &mut Vec<String>) -> Result<(), InterpError> {
+        match &line.stmt {
+            Stmt::While { label, cond, body } => {
+                let (mut guard, limit) = (0usize, self.loop_limit());
+                while guard < limit {
+                    guard += 1;
+                    match self.eval_cond(cond) {
+                        Ok(true) => {
+                            self.trace(line.number, || format!("पुनरावृत्तिः {}", guard));
+                            errs.extend(self.exec_block(body));
+                            if self.leave_loop(label) {
+                                break;
+                            }
+                        }
+                        Ok(false) => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            Stmt::For { label, vars, iterable, guard, body } => {
+                for var in vars {
+                    self.check_not_constant(var)?;
+                }
+                let items = match self.eval(iterable)? {
+                    Value::List(items) => items,
+                    _ => return Err("त्रुटिः: परिभ्रमण सूचीं अपेक्षते, यथा परिधि(n) आइटम(कोश) वा".into()),
+                };
+                for (n, item) in items.into_iter().enumerate() {
+                    self.trace(line.number, || format!("पुनरावृत्तिः {}: {} ← {}", n + 1, vars.join(", "), item));
+                    match vars.as_slice() {
+                        [var] => {
+                            self.vars.insert(var.clone(), item);
+                        }
+                        _ => {
+                            for (var, value) in vars.iter().zip(unpack_values(item, vars.len())?) {
+                                self.vars.insert(var.clone(), value);
+                            }
+                        }
+                    }
+                    if let Some(cond) = guard {
+                        if !self.eval_cond(cond)? {
+                            break;
+                        }
+                    }
+                    errs.extend(self.exec_block(body));
+                    if self.leave_loop(label) {
+                        break;
+                    }
+                }
+            }
+            // Like `यावत्`, but the condition is tested after each pass.
+            Stmt::DoWhile { label, body, cond } => {
+                let (mut guard, limit) = (0usize, self.loop_limit());
+                while guard < limit {
+                    guard += 1;
+                    self.trace(line.number, || format!("पुनरावृत्तिः {}", guard));
+                    errs.extend(self.exec_block(body));
+                    if self.leave_loop(label) || !self.eval_cond(cond)? {
+                        break;
+                    }
+                }
             }
-        } else if indent < curr {
-            while indent < *stack.last().unwrap() {
-                stack.pop();
-                out.push_str("}\n");
+            // Only `विरम` (or `प्रतिफल`) ends it; running into the guard is
+            // an error rather than a silent stop.
+            Stmt::Loop { label, body } => {
+                let limit = self.loop_limit();
+                for n in 1..=limit {
+                    self.trace(line.number, || format!("पुनरावृत्तिः {}", n));
+                    errs.extend(self.exec_block(body));
+                    if self.leave_loop(label) {
+                        return Ok(());
+                    }
+                }
+                return Err(format!("त्रुटिः: चक्रम् {} पुनरावृत्तिभ्यः परं न समाप्तम्; विरम अपेक्षितम्", limit).into());
             }
+            _ => unreachable!("exec_loop runs only loops"),
         }
-        let mut line = trimmed.to_string();
-        if line.ends_with(':') {
-            line.pop();
-            if line.starts_with("यदि") && !line.contains('(') {
-                line = format!("यदि ({})", line.trim_start_matches("यदि").trim());
+        Ok(())
+    }
+
+    /// Run one statement, returning any text it prints. Errors from the
+    /// bodies of block statements are added to `errs`; an error in the
+    /// statement itself (or a block's header) is returned.
+    fn exec_stmt(&mut self, line: &Line, errs: &mut Vec<String>) -> Result<Option<String>, InterpError> {
+        match &line.stmt {
+            Stmt::Return(expr) => {
+                if self.call_depth == 0 {
+                    return Err("त्रुटिः: प्रतिफल केवलं कार्यस्य अन्तः प्रयोक्तव्यम्".into());
+                }
+                self.returning = Some(self.eval(expr)?);
+            }
+            Stmt::Break(label) => {
+                if self.loops.is_empty() {
+                    return Err("त्रुटिः: विरम केवलं पुनरावृत्तेः अन्तः प्रयोक्तव्यम्".into());
+                }
+                if let Some(name) = label {
+                    if !self.loops.iter().any(|l| l.as_ref() == Some(name)) {
+                        return Err(format!("त्रुटिः: अज्ञातं चिह्नम्: {}", name).into());
+                    }
+                }
+                self.breaking = Some(label.clone());
             }
-            if line.starts_with("यावत्") && !line.contains('(') {
-                line = format!("यावत् ({})", line.trim_start_matches("यावत्").trim());
+            // Raise: an error carrying just the given message
+            Stmt::Raise(expr) => {
+                let message = match self.eval(expr)? {
+                    Value::Str(message) => message,
+                    other => other.to_string(),
+                };
+                return Err(message.into());
             }
-            if line.starts_with("अन्यथा") {
-                line = "अन्यथा".to_string();
+            Stmt::Assert { cond, message, text } => {
+                if self.eval_cond(cond)? {
+                    return Ok(None);
+                }
+                let message = match message.as_ref().map(|m| self.eval(m)).transpose()? {
+                    Some(Value::Str(message)) => message,
+                    Some(other) => other.to_string(),
+                    None => text.clone(),
+                };
+                return Err(format!("त्रुटिः: निश्चयः विफलः: {}", message).into());
             }
-            // परिभ्रमण / कार्य left as-is
-            prev_ended_colon = true;
-        } else {
-            prev_ended_colon = false;
-        }
-        out.push_str(&line);
-        out.push('\n');
-    }
-    while stack.len() > 1 {
-        stack.pop();
-        out.push_str("}\n");
-    }
-    out
-}
-
-fn collect_block(lines: &Vec<String>, start: usize) -> Result<(String, usize), String> {
-    // Find a '{' at or after start
-    let mut i = start;
-    let mut found_open: Option<usize> = None;
-    let mut first_after_open = String::new();
-    while i < lines.len() {
-        let l = lines[i].trim();
-        if let Some(pos) = l.find('{') {
-            found_open = Some(i);
-            if let Some(close_pos) = l[pos + 1..].find('}') {
-                let inner = l[pos + 1..pos + 1 + close_pos].trim();
-                return Ok((inner.to_string(), (i + 1) - start));
-            }
-            first_after_open = l[pos + 1..].to_string();
-            break;
-        }
-        i += 1;
-    }
-    let open_idx = found_open.ok_or_else(|| "त्रुटिः: अपेक्षितम् '{'".to_string())?;
-    let mut block_lines: Vec<String> = Vec::new();
-    if !first_after_open.trim().is_empty() {
-        block_lines.push(first_after_open);
-    }
-    i = open_idx + 1;
-    while i < lines.len() {
-        let l = lines[i].trim();
-        if l.contains('}') {
-            let before = l.split('}').next().unwrap_or("").trim();
-            if !before.is_empty() {
-                block_lines.push(before.to_string());
-            }
-            return Ok((block_lines.join("\n"), (i + 1) - start));
-        } else {
-            block_lines.push(l.to_string());
-        }
-        i += 1;
+            Stmt::Const(name, expr) => {
+                self.check_not_constant(name)?;
+                let val = self.eval(expr)?;
+                self.vars.insert(name.clone(), val);
+                self.constants.insert(name.clone());
+                self.trace_assignment(line.number, name);
+            }
+            Stmt::Import(spec) => self.import(spec)?,
+            Stmt::Delete(name, index) => self.delete(name, index.as_ref())?,
+            Stmt::Assign(name, expr) => {
+                self.check_not_constant(name)?;
+                let val = self.eval(expr)?;
+                self.vars.insert(name.clone(), val);
+                self.trace_assignment(line.number, name);
+            }
+            // Every value is evaluated before any name is bound, so
+            // `a, b = b, a` swaps.
+            Stmt::Unpack { targets, values } => {
+                for name in targets {
+                    self.check_not_constant(name)?;
+                }
+                // The parser has checked that several values match the names.
+                let values = match values.as_slice() {
+                    [single] => unpack_values(self.eval(single)?, targets.len())?,
+                    many => many.iter().map(|e| self.eval(e)).collect::<Result<_, _>>()?,
+                };
+                for (name, value) in targets.iter().zip(values) {
+                    self.vars.insert(name.clone(), value);
+                    self.trace_assignment(line.number, name);
+                }
+            }
+            // Print, with *सूची spreading a list into the arguments
+            Stmt::Print(args) => {
+                let mut parts = Vec::new();
+                for arg in args {
+                    match arg {
+                        PrintArg::Value(expr) => parts.push(self.eval(expr)?.to_string()),
+                        PrintArg::Spread(text, expr) => match self.eval(expr)? {
+                            Value::List(items) => parts.extend(items.iter().map(|v| v.to_string())),
+                            _ => return Err(format!("त्रुटिः: *{} सूची न अस्ति", text).into()),
+                        },
+                    }
+                }
+                return Ok(Some(parts.join(" ")));
+            }
+            Stmt::Call(name, args) => {
+                let (args, kwargs) = self.eval_args(args)?;
+                self.call_function_kw(name, args, kwargs)?;
+            }
+            Stmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::Echo(expr) => match self.eval(expr)? {
+                Value::Null => {}
+                value => return Ok(Some(repr(&value))),
+            },
+            Stmt::Help => {
+                return Ok(Some(
+                    "Paanini आज्ञाः (Python-रूपेण):\n  x = 5\n  नाम = \"नमस्ते\"\n  दर्श(expr)\n  यदि x == 5:\n    दर्श(\"सत्यं\")\n  अन्यथा:\n    दर्श(\"असत्यं\")\n  यावत् x < 5:\n    दर्श(x)\n    x = x + 1\n  परिभ्रमण i in परिधि(5):\n    दर्श(i)\n  कार्य greet(नाम):\n    दर्श(\"नमस्ते \" + नाम)\n  greet(\"विश्व\")\n  !! टिप्पण्यः\n"
+                        .to_string(),
+                ));
+            }
+            Stmt::If { cond, then, otherwise } => {
+                if self.eval_cond(cond)? {
+                    errs.extend(self.exec_block(then));
+                } else if let Some(otherwise) = otherwise {
+                    errs.extend(self.exec_block(otherwise));
+                }
+            }
+            Stmt::While { label, .. }
+            | Stmt::For { label, .. }
+            | Stmt::DoWhile { label, .. }
+            | Stmt::Loop { label, .. } => {
+                self.loops.push(label.clone());
+                let result = self.exec_loop(line, errs);
+                self.loops.pop();
+                result?;
+            }
+            // The first error in the body (including in functions it calls)
+            // stops it and runs the handler instead, with the error message
+            // bound to `name`.
+            Stmt::Try { body, name, handler } => {
+                self.catching += 1;
+                let body_errs = self.exec_block(body);
+                self.catching -= 1;
+                let Some(error) = self.raised.take() else {
+                    errs.extend(body_errs);
+                    return Ok(None);
+                };
+                if let Some(name) = name {
+                    self.check_not_constant(name)?;
+                    self.vars.insert(name.clone(), Value::Str(error_message(&error).to_string()));
+                }
+                errs.extend(self.exec_block(handler));
+            }
+            // Runs the first arm with a value equal to the scrutinee, else the
+            // `अन्यथा` arm if any.
+            Stmt::Match { scrutinee, arms } => {
+                let scrutinee = self.eval(scrutinee)?;
+                for arm in arms {
+                    let matched = match &arm.values {
+                        None => true,
+                        Some(values) => {
+                            let mut matched = false;
+                            for value in values {
+                                if values_equal(&scrutinee, &self.eval(value)?) {
+                                    matched = true;
+                                    break;
+                                }
+                            }
+                            matched
+                        }
+                    };
+                    if matched {
+                        errs.extend(self.exec_block(&arm.body));
+                        break;
+                    }
+                }
+            }
+            Stmt::Function { name, params, body, doc, globals } => {
+                self.functions.insert(
+                    name.clone(),
+                    FunctionDef {
+                        params: params.clone(),
+                        body: Arc::clone(body),
+                        doc: doc.clone(),
+                        globals: globals.clone(),
+                    },
+                );
+            }
+            Stmt::Invalid(e) => return Err(e.clone().into()),
+        }
+        Ok(None)
+    }
+
+    fn check_not_constant(&self, name: &str) -> Result<(), String> {
+        if self.constants.contains(name) {
+            return Err(format!("त्रुटिः: ध्रुवं परिवर्तयितुं न शक्यते: {}", name));
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, name: &str, index: Option<&Expr>) -> Result<(), InterpError> {
+        self.check_not_constant(name)?;
+        let Some(index) = index else {
+            return match self.vars.remove(name) {
+                Some(_) => Ok(()),
+                None => Err(format!("त्रुटिः: अपरिभाषितः चरः: {}", name).into()),
+            };
+        };
+        let index = self.eval(index)?;
+        let value = self
+            .vars
+            .get_mut(name)
+            .ok_or_else(|| format!("त्रुटिः: अपरिभाषितः चरः: {}", name))?;
+        match (value, &index) {
+            (Value::Map(map), Value::Str(key)) => match map.remove(key) {
+                Some(_) => Ok(()),
+                None => Err(format!("त्रुटिः: कुंजी '{}' नास्ति", key).into()),
+            },
+            (Value::List(items), Value::Number(n)) => match resolve_index(*n, items.len()) {
+                Some(i) if i < items.len() => {
+                    items.remove(i);
+                    Ok(())
+                }
+                _ => Err(format!("त्रुटिः: अनुक्रमणिका {} सीमातः बहिः (दीर्घता {})", n, items.len()).into()),
+            },
+            (value, index) => Err(format!(
+                "त्रुटिः: {} तः {} द्वारा विलोपः न शक्यः",
+                type_name(value),
+                type_name(index)
+            )
+            .into()),
+        }
+    }
+
+    fn eval_args(&mut self, parsed: &[Arg]) -> Result<(Vec<Value>, KwArgs), InterpError> {
+        let mut args = Vec::new();
+        let mut kwargs = Vec::new();
+        for arg in parsed {
+            match arg {
+                Arg::Positional(expr) => args.push(self.eval(expr)?),
+                Arg::Keyword(key, expr) => kwargs.push((key.clone(), self.eval(expr)?)),
+            }
+        }
+        Ok((args, kwargs))
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, InterpError> {
+        match expr {
+            Expr::Null => Ok(Value::Null),
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Var(name) => {
+                if let Some(v) = self.vars.get(name) {
+                    return Ok(v.clone());
+                }
+                if self.functions.contains_key(name) || BUILTINS.contains(&name.as_str()) {
+                    return Ok(Value::Function(name.clone()));
+                }
+                Err(format!("त्रुटिः: अपरिभाषितः चरः: {}", name).into())
+            }
+            Expr::List(items) => Ok(Value::List(items.iter().map(|e| self.eval(e)).collect::<Result<_, _>>()?)),
+            Expr::Map(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key = match self.eval(key)? {
+                        Value::Str(k) => k,
+                        other => return Err(format!("त्रुटिः: कोशस्य कुंजी पाठ्यं भवेत्, न {}", type_name(&other)).into()),
+                    };
+                    let val = self.eval(value)?;
+                    map.insert(key, val);
+                }
+                Ok(Value::Map(map))
+            }
+            Expr::Neg(operand) => match self.eval(operand)? {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                other => Err(format!("त्रुटिः: '-' {} प्रति न प्रयोज्यम्", type_name(&other)).into()),
+            },
+            Expr::Arith(op, left, right) => {
+                let lv = self.eval(left)?;
+                let rv = self.eval(right)?;
+                Ok(apply_arith(op, lv, rv)?)
+            }
+            Expr::Pow(base, exp) => match (self.eval(base)?, self.eval(exp)?) {
+                (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a.powf(b))),
+                (a, b) => Err(format!("त्रुटिः: '**' {} {} च मध्ये न प्रयोज्यः", type_name(&a), type_name(&b)).into()),
+            },
+            Expr::In(item, collection) => {
+                let item = self.eval(item)?;
+                let collection = self.eval(collection)?;
+                Ok(Value::Bool(contains(&collection, &item)?))
+            }
+            Expr::Condition(cond) => Ok(Value::Bool(self.eval_cond(cond)?)),
+            Expr::IfElse { cond, then, otherwise } => {
+                if self.eval_cond(cond)? {
+                    self.eval(then)
+                } else {
+                    self.eval(otherwise)
+                }
+            }
+            Expr::Call(name, args) => {
+                let (args, kwargs) = self.eval_args(args)?;
+                Ok(self.call_function_kw(name, args, kwargs)?)
+            }
+            Expr::CallValue(callee, args) => {
+                let callee = self.eval(callee)?;
+                let (args, kwargs) = self.eval_args(args)?;
+                Ok(self.call_value(&callee, args, kwargs)?)
+            }
+            Expr::Index(target, index) => {
+                let target = self.eval(target)?;
+                let index = self.eval(index)?;
+                Ok(index_value(&target, &index)?)
+            }
+            Expr::Slice(target, bounds) => {
+                let target = self.eval(target)?;
+                let bounds = bounds.iter().map(|b| self.eval(b)).collect::<Result<Vec<_>, _>>()?;
+                Ok(slice_value(&target, &bounds)?)
+            }
+            Expr::Invalid(e) => Err(e.clone().into()),
+        }
+    }
+
+    /// Evaluate a condition. `वा` and `तथा` short-circuit, and a comparison
+    /// chain like `0 < x <= 10` evaluates each operand once and stops at the
+    /// first false link.
+    fn eval_cond(&mut self, cond: &Cond) -> Result<bool, InterpError> {
+        match cond {
+            Cond::Or(left, right) => Ok(self.eval_cond(left)? || self.eval_cond(right)?),
+            Cond::And(left, right) => Ok(self.eval_cond(left)? && self.eval_cond(right)?),
+            Cond::Not(inner) => Ok(!self.eval_cond(inner)?),
+            Cond::Compare(first, links) => {
+                let mut left = self.eval(first)?;
+                for (op, operand) in links {
+                    let right = self.eval(operand)?;
+                    let holds = match (&left, &right) {
+                        (Value::Number(a), Value::Number(b)) => match *op {
+                            "==" => a == b,
+                            "!=" => a != b,
+                            ">" => a > b,
+                            "<" => a < b,
+                            ">=" => a >= b,
+                            _ => a <= b,
+                        },
+                        // Any two values can be tested for equality; lists and
+                        // maps compare element by element, and values of
+                        // different types are never equal.
+                        _ if matches!(*op, "==" | "!=") => (*op == "==") == values_equal(&left, &right),
+                        _ => return Err("त्रुटिः: यदि शर्ते संख्यायाः तुलनाः एव समर्थिताः".into()),
+                    };
+                    if !holds {
+                        return Ok(false);
+                    }
+                    left = right;
+                }
+                Ok(true)
+            }
+            // Any other value by its truthiness: यदि (सूची) tests for a non-empty list
+            Cond::Truthy(expr) => Ok(is_truthy(&self.eval(expr)?)),
+        }
+    }
+
+    fn call_function_kw(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        kwargs: KwArgs,
+    ) -> Result<Value, String> {
+        if name == "क्रमबद्ध" {
+            return self.builtin_sorted(args, kwargs);
+        }
+        if name == "संख्या_निवेश" {
+            return self.builtin_number_input(args, kwargs);
+        }
+        if name == "अंश" {
+            return self.builtin_partial(args);
+        }
+        if name == "प्रतिचित्र" || name == "छानन" {
+            return self.builtin_map_filter(name, args);
+        }
+        // A variable holding a callable, e.g. `add5 = अंश(योग, 5)`
+        if !self.functions.contains_key(name) {
+            if let Some(f @ (Value::Function(_) | Value::Partial(..))) = self.vars.get(name).cloned() {
+                return self.call_value(&f, args, kwargs);
+            }
+        }
+        if let Some((key, _)) = kwargs.first() {
+            if !self.functions.contains_key(name) {
+                return Err(format!("त्रुटिः: {} अज्ञातं नामित-तर्कं {} न स्वीकरोति", name, key));
+            }
+        }
+
+        // Builtins
+        if name == "परिधि" {
+            return builtin_range(&args);
+        }
+        if name == "दर्श" {
+            return Err("त्रुटिः: दर्श प्रयोगः केवलं दर्श(expr) स्वरूपेण भवेत्".into());
+        }
+        if name == "निवेश" {
+            return match args.as_slice() {
+                [] => self.read_input("").map(Value::Str),
+                [Value::Str(prompt)] => self.read_input(prompt).map(Value::Str),
+                _ => Err("त्रुटिः: निवेश(prompt) पाठ्यम् एव स्वीकरोति".into()),
+            };
+        }
+        if name == "संख्या" {
+            return match args.as_slice() {
+                [Value::Number(n)] => Ok(Value::Number(*n)),
+                [Value::Bool(b)] => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
+                [Value::Str(text)] => parse_number(text)
+                    .map(Value::Number)
+                    .ok_or_else(|| format!("त्रुटिः: '{}' संख्या न", text)),
+                _ => Err("त्रुटिः: संख्या(x) एकं पाठ्यं संख्यां वा अपेक्षते".into()),
+            };
+        }
+        if let Some(res) = self.random_builtin(name, &args) {
+            return res;
+        }
+        if name == "स्वरूप" {
+            return builtin_format(&args);
+        }
+        if name == "विश्राम" {
+            return self.builtin_sleep(&args);
+        }
+        if name == "पठ" || name == "लिख" {
+            return self.builtin_file(name, &args);
+        }
+        if name == "पर्यावरण" {
+            if !self.env_access {
+                return Err("त्रुटिः: पर्यावरण अननुमतम्".into());
+            }
+            return match args.as_slice() {
+                [Value::Str(var)] => Ok(std::env::var(var).map_or(Value::Null, Value::Str)),
+                _ => Err("त्रुटिः: पर्यावरण(नाम) पाठ्यम् एव स्वीकरोति".into()),
+            };
+        }
+        if name == "कालः" || name == "कालमुद्रा" {
+            if !args.is_empty() {
+                return Err(format!("त्रुटिः: {}() तर्कान् न स्वीकरोति", name));
+            }
+            self.nondeterministic = true;
+            let now = since_epoch();
+            return Ok(if name == "कालः" {
+                Value::Number(now.as_secs_f64())
+            } else {
+                Value::Str(format_timestamp(now.as_secs()))
+            });
+        }
+        if name == "रूप" {
+            return match args.as_slice() {
+                [value] => Ok(Value::Str(repr(value))),
+                _ => Err("त्रुटिः: रूप(x) एकं तर्कम् अपेक्षते".into()),
+            };
+        }
+        if let Some(res) = math_builtin(name, &args) {
+            return res;
+        }
+        if let Some(res) = string_builtin(name, &args) {
+            return res;
+        }
+        if let Some(res) = list_builtin(name, &args) {
+            return res;
+        }
+
+        if let Some(def) = self.functions.get(name).cloned() {
+            if def.params.len() != args.len() + kwargs.len() {
+                return Err("त्रुटिः: कार्य तर्कसंख्या न समा".into());
+            }
+            let limit = self.recursion_limit.unwrap_or(DEFAULT_RECURSION_LIMIT);
+            if self.call_depth >= limit {
+                return Err(format!("{}: {} ({} स्तरेभ्यः अधिकम्)", TOO_DEEP, name, limit));
+            }
+            // The child starts with an empty trace, added to ours when it returns.
+            let trace = std::mem::take(&mut self.trace);
+            let mut child = self.clone();
+            self.trace = trace;
+            child.output.clear();
+            child.call_depth += 1;
+            // A `विरम` in the body can't leave the caller's loops.
+            child.loops.clear();
+            for p in &def.params {
+                // A parameter shadows a constant of the same name.
+                child.constants.remove(p);
+            }
+            for (p, v) in def.params.iter().zip(args) {
+                child.vars.insert(p.clone(), v);
+            }
+            for (key, v) in kwargs {
+                if !def.params.contains(&key) {
+                    return Err(format!("त्रुटिः: {} कार्यस्य {} नाम तर्कः नास्ति", name, key));
+                }
+                child.vars.insert(key, v);
+            }
+            child.global_writes.clear();
+            let started = self.profiling.then(Instant::now);
+            let outermost = started.is_some() && child.profiling_in.insert(name.to_string());
+            // Each call nests several native frames; grow the stack rather
+            // than let deep (but allowed) recursion overflow it.
+            let errors = stacker::maybe_grow(256 * 1024, 4 * 1024 * 1024, || child.exec_block(&def.body));
+            // Continue the random sequence where the call left it.
+            self.random = child.random.take();
+            self.nondeterministic = child.nondeterministic;
+            if let Some(started) = started {
+                self.profile = std::mem::take(&mut child.profile);
+                let entry = self.profile.entry(name.to_string()).or_default();
+                entry.calls += 1;
+                if outermost {
+                    entry.time += started.elapsed();
+                }
+            }
+            // The child shares our sink, so its output has already been streamed.
+            self.output.push_str(&child.output);
+            self.trace.append(&mut child.trace);
+            for global in def.globals.iter().chain(&child.global_writes) {
+                match child.vars.remove(global) {
+                    Some(value) => self.vars.insert(global.clone(), value),
+                    None => self.vars.remove(global),
+                };
+                if self.call_depth > 0 {
+                    self.global_writes.insert(global.clone());
+                }
+            }
+            // Pass the recursion error up as is, rather than behind one
+            // "Line n:" per level.
+            if let Some(e) = errors.iter().find_map(|e| e.find(TOO_DEEP).map(|at| &e[at..])) {
+                return Err(e.split("; ").next().unwrap_or(e).to_string());
+            }
+            if !errors.is_empty() {
+                return Err(errors.join("; "));
+            }
+            return Ok(child.returning.take().unwrap_or(Value::Null));
+        }
+        Err(self.unknown_function_error(name))
+    }
+
+    /// Explain an unknown call, suggesting the closest builtin or user function.
+    fn unknown_function_error(&self, name: &str) -> String {
+        let candidates = BUILTINS
+            .iter()
+            .map(|b| (b.to_string(), "अन्तर्निर्मितम्"))
+            .chain(self.functions.keys().map(|f| (f.clone(), "उपयोक्तृ-कार्यम्")));
+        let mut best: Option<(usize, String, &str)> = None;
+        for (candidate, kind) in candidates {
+            let d = edit_distance(name, &candidate);
+            if best.as_ref().is_none_or(|(bd, _, _)| d < *bd) {
+                best = Some((d, candidate, kind));
+            }
+        }
+        let searched = "अन्तर्निर्मित-कार्येषु उपयोक्तृ-कार्येषु च अन्विष्टम्";
+        match best {
+            Some((d, candidate, kind)) if d <= (name.chars().count() / 3).max(1) => format!(
+                "त्रुटिः: अज्ञातः कार्यः: {} ({}); किं '{}' ({}) अभिप्रेतम्?",
+                name, searched, candidate, kind
+            ),
+            _ => format!("त्रुटिः: अज्ञातः कार्यः: {} ({})", name, searched),
+        }
+    }
+
+    /// Call a function value, prepending any arguments bound by `अंश`.
+    fn call_value(&mut self, f: &Value, args: Vec<Value>, kwargs: KwArgs) -> Result<Value, String> {
+        match f {
+            Value::Function(name) => self.call_function_kw(name, args, kwargs),
+            Value::Partial(name, bound) => {
+                let all = bound.iter().cloned().chain(args).collect();
+                self.call_function_kw(name, all, kwargs)
+            }
+            other => Err(format!("त्रुटिः: {} कार्यं न", other)),
+        }
+    }
+
+    /// `यादृच्छिक()` (a number in [0, 1)), `यादृच्छिकसंख्या(a, b)` (a whole
+    /// number from `a` to `b` inclusive) and `बीज(n)`, which seeds them so
+    /// the numbers that follow are the same on every run.
+    fn random_builtin(&mut self, name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+        if !matches!(name, "यादृच्छिक" | "यादृच्छिकसंख्या" | "बीज") {
+            return None;
+        }
+        if name == "बीज" {
+            return Some(match args {
+                [Value::Number(n)] if n.fract() == 0.0 => {
+                    self.random = Some(Random { rng: StdRng::seed_from_u64(*n as i64 as u64), seeded: true });
+                    Ok(Value::Null)
+                }
+                _ => Err("त्रुटिः: बीज(n) एकां पूर्णसंख्याम् अपेक्षते".into()),
+            });
+        }
+        let random = self.random.get_or_insert_with(|| Random { rng: StdRng::from_entropy(), seeded: false });
+        self.nondeterministic |= !random.seeded;
+        Some(match (name, args) {
+            ("यादृच्छिक", []) => Ok(Value::Number(random.rng.gen::<f64>())),
+            ("यादृच्छिक", _) => Err("त्रुटिः: यादृच्छिक() तर्कान् न स्वीकरोति".into()),
+            // Past 2^53 not every whole number is a float, and the bounds
+            // must convert to `i64` exactly.
+            (_, [Value::Number(a), Value::Number(b)])
+                if a.fract() == 0.0 && b.fract() == 0.0 && a <= b && a.abs().max(b.abs()) <= MAX_EXACT_INT =>
+            {
+                Ok(Value::Number(random.rng.gen_range(*a as i64..=*b as i64) as f64))
+            }
+            _ => Err("त्रुटिः: यादृच्छिकसंख्या(a, b) पूर्णसंख्ये a <= b अपेक्षते".into()),
+        })
+    }
+
+    /// `पठ(path)` returns a file's text; `लिख(path, text)` replaces it.
+    fn builtin_file(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        if !self.file_access {
+            return Err(format!("त्रुटिः: {} अननुमतम्", name));
+        }
+        let resolve = |rel: &str| match &self.base_dir {
+            Some(base) => base.join(rel),
+            None => PathBuf::from(rel),
+        };
+        match (name, args) {
+            ("पठ", [Value::Str(rel)]) => std::fs::read_to_string(resolve(rel))
+                .map(Value::Str)
+                .map_err(|e| format!("त्रुटिः: सञ्चिका {} पठितुं न शक्यते ({})", rel, e)),
+            ("पठ", _) => Err("त्रुटिः: पठ(सञ्चिका) पाठ्यम् एव स्वीकरोति".into()),
+            (_, [Value::Str(rel), Value::Str(text)]) => std::fs::write(resolve(rel), text)
+                .map(|_| Value::Null)
+                .map_err(|e| format!("त्रुटिः: सञ्चिका {} लेखितुं न शक्यते ({})", rel, e)),
+            _ => Err("त्रुटिः: लिख(सञ्चिका, पाठ्यम्) पाठ्यद्वयम् अपेक्षते".into()),
+        }
+    }
+
+    /// `विश्राम(seconds)`: pause, waking early if the run is cancelled.
+    fn builtin_sleep(&self, args: &[Value]) -> Result<Value, String> {
+        let mut left = match args {
+            [Value::Number(secs)] if *secs < 0.0 => {
+                return Err("त्रुटिः: विश्राम ऋणात्मकं कालं न स्वीकरोति".into())
+            }
+            [Value::Number(secs)] => Duration::try_from_secs_f64(*secs)
+                .map_err(|_| "त्रुटिः: विश्राम अतिदीर्घः".to_string())?,
+            _ => return Err("त्रुटिः: विश्राम(क्षणाः) एकां संख्याम् अपेक्षते".into()),
+        };
+        if let Some(max) = self.max_sleep {
+            left = left.min(max);
+        }
+        // A browser tab has one thread, and blocking it would freeze the page.
+        if cfg!(all(target_arch = "wasm32", target_os = "unknown")) && !left.is_zero() {
+            return Err("त्रुटिः: विश्राम अस्मिन् पर्यावरणे न उपलभ्यते".into());
+        }
+        // Sleep in slices so a cancelled run doesn't keep its thread.
+        while !left.is_zero() && !self.is_cancelled() {
+            let slice = left.min(Duration::from_millis(20));
+            std::thread::sleep(slice);
+            left -= slice;
+        }
+        Ok(Value::Null)
+    }
+
+    /// `प्रतिचित्र(fn, सूची)` applies `fn` to each element; `छानन(fn, सूची)` keeps
+    /// the elements for which `fn` returns a truthy value. `fn` may be a function
+    /// value or the function's name as a string.
+    fn builtin_map_filter(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let (f, items) = match args.as_slice() {
+            [Value::Str(fname), Value::List(items)] => (Value::Function(fname.clone()), items.clone()),
+            [f @ (Value::Function(_) | Value::Partial(..)), Value::List(items)] => (f.clone(), items.clone()),
+            _ => return Err(format!("त्रुटिः: {}(fn, सूची) कार्यं सूचीं च अपेक्षते", name)),
+        };
+        let mut out = Vec::with_capacity(items.len());
+        for item in items {
+            let result = self.call_value(&f, vec![item.clone()], Vec::new())?;
+            if name == "प्रतिचित्र" {
+                out.push(result);
+            } else if is_truthy(&result) {
+                out.push(item);
+            }
+        }
+        Ok(Value::List(out))
+    }
+
+    /// `अंश(fn, a, ...)`: bind leading arguments of `fn`, returning a new callable.
+    fn builtin_partial(&self, args: Vec<Value>) -> Result<Value, String> {
+        let mut args = args.into_iter();
+        let (name, mut bound) = match args.next() {
+            Some(Value::Function(name)) => (name, Vec::new()),
+            Some(Value::Partial(name, bound)) => (name, bound),
+            _ => return Err("त्रुटिः: अंश(fn, ...) प्रथमः तर्कः कार्यं भवेत्".into()),
+        };
+        bound.extend(args);
+        if let Some(def) = self.functions.get(&name) {
+            if bound.len() > def.params.len() {
+                return Err(format!(
+                    "त्रुटिः: {} कार्यं {} तर्कान् स्वीकरोति, {} पूर्वबद्धाः",
+                    name,
+                    def.params.len(),
+                    bound.len()
+                ));
+            }
+        }
+        Ok(Value::Partial(name, bound))
+    }
+
+    /// `क्रमबद्ध(सूची, कुंजी=fn, अवरोह=सत्य)`: a stable sort, optionally comparing
+    /// `fn(item)` instead of the item, in descending order when `अवरोह` is set.
+    fn builtin_sorted(&mut self, args: Vec<Value>, kwargs: KwArgs) -> Result<Value, String> {
+        let items = match args.as_slice() {
+            [Value::List(items)] => items.clone(),
+            _ => return Err("त्रुटिः: क्रमबद्ध(सूची) एकां सूचीम् अपेक्षते".into()),
+        };
+        let mut key_fn = None;
+        let mut descending = false;
+        for (key, val) in kwargs {
+            match (key.as_str(), val) {
+                ("कुंजी", f @ (Value::Function(_) | Value::Partial(..))) => key_fn = Some(f),
+                ("कुंजी", _) => return Err("त्रुटिः: कुंजी कार्यं भवेत्".into()),
+                ("अवरोह", Value::Bool(b)) => descending = b,
+                ("अवरोह", _) => return Err("त्रुटिः: अवरोह सत्य/असत्य भवेत्".into()),
+                (other, _) => return Err(format!("त्रुटिः: क्रमबद्ध अज्ञातं नामित-तर्कं {} न स्वीकरोति", other)),
+            }
+        }
+        let keys = match key_fn {
+            Some(f) => items
+                .iter()
+                .map(|item| self.call_value(&f, vec![item.clone()], Vec::new()))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => items.clone(),
+        };
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        let mut err = None;
+        order.sort_by(|&a, &b| {
+            // Swapping the operands keeps equal elements in their original order.
+            let (a, b) = if descending { (b, a) } else { (a, b) };
+            compare_values(&keys[a], &keys[b]).unwrap_or_else(|e| {
+                err.get_or_insert(e);
+                Ordering::Equal
+            })
+        });
+        if let Some(e) = err {
+            return Err(e);
+        }
+        Ok(Value::List(order.into_iter().map(|i| items[i].clone()).collect()))
+    }
+}
+
+/// The elements of `value`, a list to be unpacked into `count` names.
+fn unpack_values(value: Value, count: usize) -> Result<Vec<Value>, String> {
+    match value {
+        Value::List(items) if items.len() == count => Ok(items),
+        Value::List(items) => Err(unpack_count_error(count, items.len())),
+        other => Err(format!("त्रुटिः: {} विभक्तुं न शक्यते, सूची अपेक्षिता", type_name(&other))),
+    }
+}
+
+/// An error without the `Line N: ` prefixes added by each enclosing block or call.
+fn error_message(error: &str) -> &str {
+    let mut rest = error;
+    while let Some(after) = rest.strip_prefix("Line ") {
+        match after.split_once(": ") {
+            Some((n, msg)) if n.chars().all(|c| c.is_ascii_digit()) => rest = msg,
+            _ => break,
+        }
+    }
+    rest
+}
+
+/// Check that brackets and quotes are balanced, reporting the line of the
+/// first unmatched delimiter. Strings end at the end of their line; brackets
+/// may span lines so brace-delimited blocks are allowed.
+pub fn check_balanced(src: &str) -> Result<(), InterpError> {
+    let src = join_triple_quoted(src);
+    let mut stack: Vec<(char, usize)> = Vec::new();
+    for (idx, line) in src.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = line.trim();
+        if trimmed.starts_with("!!") || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut scan = StrScan::default();
+        for c in trimmed.chars() {
+            if scan.step(c) {
+                continue;
+            }
+            if is_open_bracket(c) {
+                stack.push((c, line_no));
+            } else if is_close_bracket(c) {
+                let expected = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                match stack.pop() {
+                    Some((open, _)) if open == expected => {}
+                    Some((open, open_line)) => {
+                        return Err(InterpError::new(
+                            line_no,
+                            format!("त्रुटिः: '{}' (पङ्क्तिः {}) '{}' इत्यनेन न मिलति", open, open_line, c),
+                        ))
+                    }
+                    None => {
+                        return Err(InterpError::new(line_no, format!("त्रुटिः: '{}' इत्यस्य आरम्भः नास्ति", c)))
+                    }
+                }
+            }
+        }
+        if let Some(q) = scan.quote() {
+            return Err(InterpError::new(line_no, format!("त्रुटिः: '{}' उद्धरणं न समाप्तम्", q)));
+        }
+    }
+    match stack.pop() {
+        Some((open, line_no)) => Err(InterpError::new(line_no, format!("त्रुटिः: '{}' न समाप्तम्", open))),
+        None => Ok(()),
+    }
+}
+
+/// The list of command-line arguments set by `set_args`.
+pub const ARGS_VAR: &str = "आदेशतर्क";
+
+/// Names handled directly by `call_function_kw`, used for typo suggestions.
+const BUILTINS: &[&str] = &[
+    "परिधि", "दर्श", "क्रमबद्ध", "वर्गमूल", "घात", "ज्या", "कोज्या", "उच्च", "निम्न",
+    "परिष्कृत", "प्रतिस्थापन", "विभाजन", "संयोजन", "निवेश", "संख्या", "संख्या_निवेश",
+    "अंश", "आइटम", "खण्ड", "भाग", "प्रतिचित्र", "छानन", "रूप", "क्रमसूची", "अद्वितीय", "स्वरूप", "यादृच्छिक", "यादृच्छिकसंख्या", "बीज",
+    "कालः", "कालमुद्रा", "विश्राम", "पठ", "लिख", "पर्यावरण",
+];
+
+/// Parse user-supplied text as a finite number.
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Levenshtein distance over chars, so Devanagari matras count as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Ordering used by sorting: numbers numerically, strings lexicographically.
+fn compare_values(a: &Value, b: &Value) -> Result<Ordering, String> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .partial_cmp(y)
+            .ok_or_else(|| "त्रुटिः: NaN इत्यस्य तुलना न शक्या".to_string()),
+        (Value::Str(x), Value::Str(y)) => Ok(x.cmp(y)),
+        _ => Err("त्रुटिः: भिन्नप्रकारयोः मूल्ययोः तुलना न शक्या".into()),
+    }
+}
+
+fn index_value(target: &Value, index: &Value) -> Result<Value, String> {
+    let out_of_range = |n: f64, len: usize| format!("त्रुटिः: अनुक्रमणिका {} सीमातः बहिः (दीर्घता {})", n, len);
+    match (target, index) {
+        (Value::List(items), Value::Number(n)) => resolve_index(*n, items.len())
+            .and_then(|i| items.get(i).cloned())
+            .ok_or_else(|| out_of_range(*n, items.len())),
+        (Value::Str(text), Value::Number(n)) => {
+            let chars: Vec<char> = text.chars().collect();
+            resolve_index(*n, chars.len())
+                .and_then(|i| chars.get(i).map(|c| Value::Str(c.to_string())))
+                .ok_or_else(|| out_of_range(*n, chars.len()))
+        }
+        (Value::Map(map), Value::Str(key)) => map
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("त्रुटिः: कुंजी '{}' नास्ति", key)),
+        _ => Err(format!(
+            "त्रुटिः: {} {} द्वारा अनुक्रमितुं न शक्यते",
+            type_name(target),
+            type_name(index)
+        )),
+    }
+}
+
+/// `target[start:stop:step]` with Python semantics: omitted (`null`) bounds
+/// default to the ends, negative ones count from the end, and out-of-range
+/// bounds are clamped, so an empty or reversed slice is empty.
+fn slice_value(target: &Value, bounds: &[Value]) -> Result<Value, String> {
+    let mut nums = [None; 3];
+    for (slot, bound) in nums.iter_mut().zip(bounds) {
+        *slot = match bound {
+            Value::Null => None,
+            Value::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => return Err("त्रुटिः: खण्डस्य सीमाः पूर्णसंख्याः भवेयुः".into()),
+        };
+    }
+    let step = nums[2].unwrap_or(1);
+    if step == 0 {
+        return Err("त्रुटिः: खण्डस्य पदं शून्यं न भवेत्".into());
+    }
+    let positions = |len: usize| -> Vec<usize> {
+        let len = len as i64;
+        // Bounds and steps past `i64` saturate, so sums must not overflow.
+        let resolve = |n: i64, low: i64, high: i64| (if n < 0 { n.saturating_add(len) } else { n }).clamp(low, high);
+        let mut out = Vec::new();
+        if step > 0 {
+            let mut i = nums[0].map_or(0, |n| resolve(n, 0, len));
+            let stop = nums[1].map_or(len, |n| resolve(n, 0, len));
+            while i < stop {
+                out.push(i as usize);
+                let Some(next) = i.checked_add(step) else { break };
+                i = next;
+            }
+        } else {
+            let mut i = nums[0].map_or(len - 1, |n| resolve(n, -1, len - 1));
+            let stop = nums[1].map_or(-1, |n| resolve(n, -1, len - 1));
+            while i > stop {
+                out.push(i as usize);
+                let Some(next) = i.checked_add(step) else { break };
+                i = next;
+            }
+        }
+        out
+    };
+    match target {
+        Value::List(items) => Ok(Value::List(positions(items.len()).into_iter().map(|i| items[i].clone()).collect())),
+        Value::Str(text) => {
+            let chars: Vec<char> = text.chars().collect();
+            Ok(Value::Str(positions(chars.len()).into_iter().map(|i| chars[i]).collect()))
+        }
+        other => Err(format!("त्रुटिः: {} खण्डयितुं न शक्यते", type_name(other))),
+    }
+}
+
+/// Turn a possibly negative (from-the-end) index into a position.
+fn resolve_index(n: f64, len: usize) -> Option<usize> {
+    if n.fract() != 0.0 {
+        return None;
+    }
+    let i = if n < 0.0 { len as f64 + n } else { n };
+    if i < 0.0 {
+        return None;
+    }
+    Some(i as usize)
+}
+
+/// Floating-point math builtins. Returns `None` when `name` is not one of them.
+fn math_builtin(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let arity = match name {
+        "वर्गमूल" | "ज्या" | "कोज्या" => 1,
+        "घात" | "खण्ड" | "भाग" => 2,
+        _ => return None,
+    };
+    if args.len() != arity {
+        return Some(Err(format!("त्रुटिः: {} {} तर्कान् अपेक्षते", name, arity)));
+    }
+    let mut nums = Vec::with_capacity(arity);
+    for a in args {
+        match a {
+            Value::Number(n) => nums.push(*n),
+            _ => return Some(Err(format!("त्रुटिः: {} संख्याः एव स्वीकरोति", name))),
+        }
+    }
+    let res = match name {
+        "वर्गमूल" => {
+            if nums[0] < 0.0 {
+                return Some(Err("त्रुटिः: ऋणसंख्यायाः वर्गमूलं न शक्यम्".into()));
+            }
+            nums[0].sqrt()
+        }
+        "घात" => nums[0].powf(nums[1]),
+        "खण्ड" | "भाग" if nums[1] == 0.0 => return Some(Err("त्रुटिः: शून्येन भागः न शक्यः".into())),
+        "खण्ड" => (nums[0] / nums[1]).floor(),
+        "भाग" => floor_mod(nums[0], nums[1]),
+        "ज्या" => nums[0].sin(),
+        "कोज्या" => nums[0].cos(),
+        _ => unreachable!(),
+    };
+    Some(Ok(Value::Number(res)))
+}
+
+/// Floor modulo, used by `%` and `भाग`: `a == b * floor(a / b) + floor_mod(a, b)`,
+/// so the result takes the sign of `b`.
+fn floor_mod(a: f64, b: f64) -> f64 {
+    a - b * (a / b).floor()
+}
+
+/// Time since the Unix epoch. `SystemTime::now` panics on
+/// `wasm32-unknown-unknown`, so there the browser's clock is used.
+fn since_epoch() -> Duration {
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    return Duration::from_secs_f64(js_sys::Date::now() / 1000.0);
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Seconds since the Unix epoch as a UTC `YYYY-MM-DD HH:MM:SS` string, for
+/// `कालमुद्रा`.
+fn format_timestamp(secs: u64) -> String {
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant's
+    // `civil_from_days`, with eras of 400 years starting on 0000-03-01.
+    let z = days + 719_468;
+    let (era, doe) = (z / 146_097, z % 146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, rem / 3_600, rem % 3_600 / 60, rem % 60)
+}
+
+/// `स्वरूप(x, decimals)`: `x` as text rounded to `decimals` places and padded
+/// with zeros, so `स्वरूप(2, 2)` is `"2.00"`.
+fn builtin_format(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Number(x), Value::Number(d)] if (0.0..=100.0).contains(d) && d.fract() == 0.0 => {
+            Ok(Value::Str(format!("{:.*}", *d as usize, x)))
+        }
+        [Value::Number(_), Value::Number(_)] => {
+            Err("त्रुटिः: स्वरूप दशमलवस्थानानि 0 तः 100 पर्यन्तं पूर्णसंख्या भवेत्".into())
+        }
+        _ => Err("त्रुटिः: स्वरूप(x, दशमलवाः) संख्याद्वयम् अपेक्षते".into()),
+    }
+}
+
+/// `परिधि(stop)`, `परिधि(start, stop)` or `परिधि(start, stop, step)`: the
+/// numbers from `start` (default 0) towards `stop` (excluded) by `step`
+/// (default 1). A negative step counts down; bounds and step may be fractional.
+fn builtin_range(args: &[Value]) -> Result<Value, String> {
+    let mut nums = Vec::with_capacity(args.len());
+    for a in args {
+        match a {
+            Value::Number(n) if n.is_finite() => nums.push(*n),
+            Value::Number(_) => return Err("त्रुटिः: परिधि सीमाः परिमिताः संख्याः भवेयुः".into()),
+            _ => return Err("त्रुटिः: परिधि संख्याः एव स्वीकरोति".into()),
+        }
+    }
+    let (start, stop, step) = match nums[..] {
+        [stop] => (0.0, stop, 1.0),
+        [start, stop] => (start, stop, 1.0),
+        [start, stop, step] => (start, stop, step),
+        _ => return Err("त्रुटिः: परिधि एकं त्रीन् वा तर्कान् अपेक्षते".into()),
+    };
+    if step == 0.0 {
+        return Err("त्रुटिः: परिधि पदं शून्यं न भवेत्".into());
+    }
+    // Compute each element from the start so fractional steps don't drift.
+    let count = ((stop - start) / step).ceil().max(0.0);
+    if count > MAX_RANGE_LEN as f64 {
+        return Err(format!("त्रुटिः: परिधि {} तः अधिकानि तत्त्वानि न रचयति", MAX_RANGE_LEN));
+    }
+    let count = count as usize;
+    Ok(Value::List((0..count).map(|i| Value::Number(start + i as f64 * step)).collect()))
+}
+
+/// Largest magnitude up to which every whole number is exactly a float, 2^53.
+const MAX_EXACT_INT: f64 = 9_007_199_254_740_992.0;
+
+/// Longest list `परिधि` builds, so a huge range is an error rather than an
+/// allocation failure that takes down the whole process.
+const MAX_RANGE_LEN: usize = 1_000_000;
+
+/// Text builtins operating on `Value::Str`. Returns `None` when `name` is not one of them.
+fn string_builtin(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let arity = match name {
+        "उच्च" | "निम्न" | "परिष्कृत" => 1,
+        "विभाजन" => 2,
+        "प्रतिस्थापन" => 3,
+        _ => return None,
+    };
+    if args.len() != arity {
+        return Some(Err(format!("त्रुटिः: {} {} तर्कान् अपेक्षते", name, arity)));
+    }
+    let mut strs = Vec::with_capacity(arity);
+    for a in args {
+        match a {
+            Value::Str(s) => strs.push(s.as_str()),
+            _ => return Some(Err(format!("त्रुटिः: {} पाठ्यम् (string) एव स्वीकरोति", name))),
+        }
+    }
+    let res = match name {
+        "उच्च" => Value::Str(strs[0].to_uppercase()),
+        "निम्न" => Value::Str(strs[0].to_lowercase()),
+        "परिष्कृत" => Value::Str(strs[0].trim().to_string()),
+        "प्रतिस्थापन" => Value::Str(strs[0].replace(strs[1], strs[2])),
+        "विभाजन" if strs[1].is_empty() => {
+            Value::List(strs[0].chars().map(|c| Value::Str(c.to_string())).collect())
+        }
+        "विभाजन" => Value::List(strs[0].split(strs[1]).map(|p| Value::Str(p.to_string())).collect()),
+        _ => unreachable!(),
+    };
+    Some(Ok(res))
+}
+
+/// Builtins taking a `Value::List`. Returns `None` when `name` is not one of them.
+fn list_builtin(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    match name {
+        "आइटम" => Some(match args {
+            [Value::Map(map)] => Ok(Value::List(
+                map.iter()
+                    .map(|(k, v)| Value::List(vec![Value::Str(k.clone()), v.clone()]))
+                    .collect(),
+            )),
+            _ => Err("त्रुटिः: आइटम(कोश) एकं कोशम् अपेक्षते".into()),
+        }),
+        "क्रमसूची" => Some(match args {
+            [Value::List(items)] => Ok(Value::List(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| Value::List(vec![Value::Number(i as f64), v.clone()]))
+                    .collect(),
+            )),
+            _ => Err("त्रुटिः: क्रमसूची(सूची) एकां सूचीम् अपेक्षते".into()),
+        }),
+        // Keeps the first of equal values, comparing as `==` does.
+        "अद्वितीय" => Some(match args {
+            [Value::List(items)] => {
+                let mut unique: Vec<Value> = Vec::new();
+                for item in items {
+                    if !unique.iter().any(|seen| values_equal(seen, item)) {
+                        unique.push(item.clone());
+                    }
+                }
+                Ok(Value::List(unique))
+            }
+            _ => Err("त्रुटिः: अद्वितीय(सूची) एकां सूचीम् अपेक्षते".into()),
+        }),
+        "संयोजन" => Some(match args {
+            [Value::List(items), Value::Str(sep)] => Ok(Value::Str(
+                items.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(sep),
+            )),
+            _ => Err("त्रुटिः: संयोजन(सूची, sep) सूचीं पाठ्यं च अपेक्षते".into()),
+        }),
+        _ => None,
+    }
+}
+
+fn apply_arith(op: &str, lv: Value, rv: Value) -> Result<Value, String> {
+    match (op, lv, rv) {
+        ("+", Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+        ("+", Value::Str(a), Value::Str(b)) => Ok(Value::Str(format!("{}{}", a, b))),
+        ("+", Value::Str(a), v) => Ok(Value::Str(format!("{}{}", a, v))),
+        ("+", v, Value::Str(b)) => Ok(Value::Str(format!("{}{}", v, b))),
+        ("-", Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+        ("*", Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+        ("*", Value::Str(text), Value::Number(n)) | ("*", Value::Number(n), Value::Str(text)) => {
+            repeat_str(&text, n)
+        }
+        ("/" | "//" | "%", Value::Number(_), Value::Number(0.0)) => Err("त्रुटिः: शून्येन भागः न शक्यः".into()),
+        ("/", Value::Number(a), Value::Number(b)) => Ok(Value::Number(a / b)),
+        ("//", Value::Number(a), Value::Number(b)) => Ok(Value::Number((a / b).floor())),
+        ("%", Value::Number(a), Value::Number(b)) => Ok(Value::Number(floor_mod(a, b))),
+        (op, lv, rv) => Err(format!(
+            "त्रुटिः: '{}' {} {} च मध्ये न प्रयोज्यः",
+            op,
+            type_name(&lv),
+            type_name(&rv)
+        )),
+    }
+}
+
+/// Whether a value counts as true in a condition or for `छानन`: `असत्य`,
+/// `शून्य`, zero, and empty strings, lists and maps are false; everything else
+/// is true.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => *n != 0.0,
+        Value::Str(s) => !s.is_empty(),
+        Value::List(items) => !items.is_empty(),
+        Value::Map(map) => !map.is_empty(),
+        Value::Function(_) | Value::Partial(..) => true,
+        Value::Null => false,
+    }
+}
+
+/// Structural equality: lists and maps compare element by element.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x == y,
+        (Value::Str(x), Value::Str(y)) => x == y,
+        (Value::Bool(x), Value::Bool(y)) => x == y,
+        (Value::List(x), Value::List(y)) => x.len() == y.len() && x.iter().zip(y).all(|(a, b)| values_equal(a, b)),
+        (Value::Map(x), Value::Map(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|((ka, va), (kb, vb))| ka == kb && values_equal(va, vb))
+        }
+        (Value::Function(x), Value::Function(y)) => x == y,
+        (Value::Partial(x, xs), Value::Partial(y, ys)) => x == y && values_equal(&Value::List(xs.clone()), &Value::List(ys.clone())),
+        (Value::Null, Value::Null) => true,
+        _ => false,
+    }
+}
+
+/// `item मध्ये collection`: an element of a list, a substring of a string, or a key of a map.
+fn contains(collection: &Value, item: &Value) -> Result<bool, String> {
+    match (collection, item) {
+        (Value::List(items), _) => Ok(items.iter().any(|v| values_equal(v, item))),
+        (Value::Str(text), Value::Str(part)) => Ok(text.contains(part.as_str())),
+        (Value::Map(map), Value::Str(key)) => Ok(map.contains_key(key)),
+        (Value::Map(_), _) => Ok(false),
+        _ => Err(format!(
+            "त्रुटिः: {} {} मध्ये अन्वेष्टुं न शक्यते",
+            type_name(item),
+            type_name(collection)
+        )),
+    }
+}
+
+/// Longest string (in bytes) that `"ab" * n` may build.
+const MAX_REPEAT_LEN: usize = 16 * 1024 * 1024;
+
+/// `"ab" * 3`: the count is floored, and zero or negative counts give "".
+fn repeat_str(text: &str, count: f64) -> Result<Value, String> {
+    if !count.is_finite() {
+        return Err("त्रुटिः: पाठ्यस्य पुनरावृत्तिः परिमितसंख्यया एव".into());
+    }
+    let count = count.floor().max(0.0);
+    if text.len() as f64 * count > MAX_REPEAT_LEN as f64 {
+        return Err(format!("त्रुटिः: पुनरावृत्तं पाठ्यम् {} बाइट्-भ्यः दीर्घतरं न भवेत्", MAX_REPEAT_LEN));
+    }
+    Ok(Value::Str(text.repeat(count as usize)))
+}
+
+/// Sanskrit name of a value's type, for error messages.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "संख्या",
+        Value::Str(_) => "पाठ्यम्",
+        Value::Bool(_) => "सत्यासत्यम्",
+        Value::List(_) => "सूची",
+        Value::Map(_) => "कोशः",
+        Value::Function(_) | Value::Partial(..) => "कार्यम्",
+        Value::Null => "शून्य",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{sanskrit_to_bool, split_assignment};
+
+    fn run(src: &str) -> RunResult {
+        Interpreter::default().run(src)
+    }
+
+    struct ScriptedInput(std::sync::Mutex<Vec<&'static str>>);
+
+    impl InputProvider for ScriptedInput {
+        fn read_line(&self, _prompt: &str) -> Option<String> {
+            let mut lines = self.0.lock().unwrap();
+            if lines.is_empty() {
+                None
+            } else {
+                Some(lines.remove(0).to_string())
+            }
+        }
+    }
+
+    fn run_with_input(src: &str, lines: Vec<&'static str>) -> RunResult {
+        let mut interp = Interpreter::default();
+        interp.set_input_provider(Arc::new(ScriptedInput(std::sync::Mutex::new(lines))));
+        interp.run(src)
+    }
+
+    #[test]
+    fn test_pi_constant() {
+        let result = run("दर्श(पाई)");
+        assert!(result.errors.is_empty());
+        assert!(result.output.starts_with("3.14159"));
+    }
+
+    #[test]
+    fn test_constants_in_arithmetic() {
+        let result = run("x = पाई + 1\nदर्श(x)");
+        assert!(result.errors.is_empty());
+        assert!(result.output.starts_with("4.14159"));
+    }
+
+    #[test]
+    fn test_sqrt_and_pow() {
+        let result = run("यदि (वर्गमूल(9) == 3):\n    दर्श(\"ok\")\nयदि (घात(2, 10) == 1024):\n    दर्श(\"ok\")");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "ok\nok\n");
+    }
+
+    #[test]
+    fn test_sqrt_composes_with_arithmetic() {
+        let result = run("x = 3\ny = 4\nदर्श(वर्गमूल(x*x + y*y))\nदर्श(2 + 3 * 4 - 10 / 5)");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "5\n12\n");
+    }
+
+    #[test]
+    fn test_sqrt_of_negative_errors() {
+        let result = run("x = वर्गमूल(0 - 4)");
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_string_case_builtins() {
+        let result = run("दर्श(उच्च(\"Namaste\"))\nदर्श(निम्न(\"NAMASTE\"))\nदर्श(उच्च(\"नमस्ते\"))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "NAMASTE\nnamaste\nनमस्ते\n");
+    }
+
+    #[test]
+    fn test_trim_strips_both_ends() {
+        let result = run("दर्श(\"[\" + परिष्कृत(\"  नमस्ते विश्व \") + \"]\")");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "[नमस्ते विश्व]\n");
+    }
+
+    #[test]
+    fn test_replace_builtin() {
+        let result = run("दर्श(प्रतिस्थापन(\"नमस्ते विश्व\", \"विश्व\", \"भारत\"))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "नमस्ते भारत\n");
+    }
+
+    #[test]
+    fn test_string_builtin_rejects_non_string() {
+        let result = run("x = उच्च(5)");
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_split_on_separator() {
+        let result = run("भागाः = विभाजन(\"a,b,c\", \",\")\nदर्श(भागाः)\nदर्श(भागाः[2])");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "[a, b, c]\nc\n");
+    }
+
+    #[test]
+    fn test_split_into_characters() {
+        let result = run("दर्श(विभाजन(\"abc\", \"\"))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "[a, b, c]\n");
+    }
+
+    #[test]
+    fn test_join_stringifies_elements() {
+        let result = run("दर्श(संयोजन([1, 2, 3], \"-\"))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "1-2-3\n");
+    }
+
+    #[test]
+    fn test_join_round_trips_split() {
+        let result = run("दर्श(संयोजन(विभाजन(\"a,b\", \",\"), \",\") + \"|\" + संयोजन([], \",\") + \"|\")");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "a,b||\n");
+    }
+
+    #[test]
+    fn test_print_multiple_arguments() {
+        let result = run("योग = 15\nदर्श(\"योग:\", योग)");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "योग: 15\n");
+    }
+
+    #[test]
+    fn test_print_spreads_list() {
+        let result = run("सूची = [1, \"दो\", 3]\nदर्श(*सूची)\nदर्श(\"->\", *सूची)");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "1 दो 3\n-> 1 दो 3\n");
+    }
+
+    #[test]
+    fn test_print_spread_requires_list() {
+        let result = run("x = 5\nदर्श(*x)");
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_number_input() {
+        let result = run_with_input("x = संख्या_निवेश(\"संख्या: \")\nदर्श(x + 1)", vec!["41"]);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "42\n");
+    }
+
+    #[test]
+    fn test_number_input_invalid_errors() {
+        let result = run_with_input("संख्या_निवेश()", vec!["abc"]);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("'abc' संख्या न"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_number_input_reprompts() {
+        let result = run_with_input("x = संख्या_निवेश(पुनःप्रश्न=सत्य)\nदर्श(x)", vec!["abc", "7"]);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "7\n");
+    }
+
+    #[test]
+    fn test_input_and_number_cast() {
+        let result = run_with_input("नाम = निवेश()\nदर्श(\"नमस्ते \" + नाम)\nदर्श(संख्या(\"2.5\") + 1)", vec!["राम"]);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "नमस्ते राम\n3.5\n");
+    }
+
+    #[test]
+    fn test_unknown_builtin_suggests_closest() {
+        let result = run("परिधी(5)");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("'परिधि' (अन्तर्निर्मितम्)"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_unknown_user_function_suggests_closest() {
+        let result = run("कार्य नमस्कार(x):\n    दर्श(x)\nनमस्कर(1)");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("'नमस्कार' (उपयोक्तृ-कार्यम्)"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_for_with_inline_break_condition() {
+        let result = run("परिभ्रमण i in परिधि(10) यावत् (i < 3):\n    दर्श(i)\nदर्श(\"अन्तः\")");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "0\n1\n2\nअन्तः\n");
+    }
+
+    #[test]
+    fn test_partial_application() {
+        let src = "कार्य योग(a, b):\n    प्रतिफल a + b\nadd5 = अंश(योग, 5)\nदर्श(add5(3))\nsix = अंश(add5, 1)\nदर्श(six())\npow2 = अंश(घात, 2)\nदर्श(pow2(10))";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "8\n6\n1024\n");
+    }
+
+    #[test]
+    fn test_partial_too_many_arguments() {
+        let result = run("कार्य योग(a, b):\n    प्रतिफल a + b\nअंश(योग, 1, 2, 3)");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("पूर्वबद्धाः"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_for_over_map_items() {
+        // Rebuild the map's textual form from its pairs and compare.
+        let src = "कोश = {\"ख\": 2, \"क\": 1}\nपाठ = \"\"\nगणक = 0\nपरिभ्रमण युग्म in आइटम(कोश):\n    यदि (गणक > 0):\n        पाठ = पाठ + \", \"\n    गणक = गणक + 1\n    पाठ = पाठ + युग्म[0] + \": \" + युग्म[1]\nदर्श(\"{\" + पाठ + \"}\")\nदर्श(कोश)\nदर्श(आइटम(कोश))";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "{क: 1, ख: 2}\n{क: 1, ख: 2}\n[[क, 1], [ख, 2]]\n");
+    }
+
+    #[test]
+    fn test_for_rejects_non_list() {
+        let result = run("परिभ्रमण x in 5:\n    दर्श(x)");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("सूचीं अपेक्षते"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_run_with_sink_streams_each_print() {
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = chunks.clone();
+        let mut interp = Interpreter::default();
+        let src = "कार्य f():\n    दर्श(\"अन्तः\")\nदर्श(1)\nf()\nदर्श(2)";
+        let result = interp.run_with_sink(src, Arc::new(move |text: &str| seen.lock().unwrap().push(text.to_string())));
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(*chunks.lock().unwrap(), vec!["1\n", "अन्तः\n", "2\n"]);
+        assert_eq!(result.output, "1\nअन्तः\n2\n");
+    }
+
+    #[test]
+    fn test_function_docstring_is_recorded_not_run() {
+        let mut interp = Interpreter::default();
+        let src = "कार्य वर्ग(n):\n    \"वर्गं ददाति\"\n    \">>> दर्श(वर्ग(3))\"\n    प्रतिफल n * n\nदर्श(वर्ग(4))";
+        let result = interp.run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "16\n");
+        assert_eq!(
+            interp.function_docs(),
+            vec![("वर्ग".to_string(), vec!["वर्गं ददाति".to_string(), ">>> दर्श(वर्ग(3))".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_cancel_flag_stops_execution() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut interp = Interpreter::default();
+        interp.set_cancel_flag(flag.clone());
+        assert!(interp.run("दर्श(1)").errors.is_empty());
+
+        flag.store(true, AtomicOrdering::Relaxed);
+        let result = interp.run("i = 0\nयावत् (i < 5):\n    दर्श(i)\n    i = i + 1");
+        assert_eq!(result.output, "");
+        assert_eq!(result.errors, vec!["त्रुटिः: निष्पादनं निरस्तम्".to_string()]);
+    }
+
+    #[test]
+    fn test_labeled_break_leaves_the_outer_loop() {
+        let src = "चिह्न बाह्य: परिभ्रमण i in परिधि(3):\n    j = 0\n    यावत् (j < 3):\n        यदि (i * j == 2):\n            विरम बाह्य\n        दर्श(i, j)\n        j = j + 1\nदर्श(\"अन्ते\", i, j)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "0 0\n0 1\n0 2\n1 0\n1 1\nअन्ते 1 2\n");
+        assert_eq!(Interpreter::default().check(src), vec![]);
+
+        // A plain विरम only leaves the innermost loop.
+        let result = run("परिभ्रमण i in परिधि(2):\n    परिभ्रमण j in परिधि(5):\n        यदि (j == 1):\n            विरम\n        दर्श(i, j)");
+        assert_eq!(result.output, "0 0\n1 0\n");
+
+        for (src, error) in [
+            ("विरम", "पुनरावृत्तेः अन्तः"),
+            ("यावत् (सत्य):\n    विरम अन्य", "अज्ञातं चिह्नम्: अन्य"),
+            // Breaking can't cross a function call.
+            ("कार्य f():\n    विरम\nपरिभ्रमण i in [1]:\n    f()", "पुनरावृत्तेः अन्तः"),
+        ] {
+            let result = run(src);
+            assert!(result.errors.iter().any(|e| e.contains(error)), "{}: {:?}", src, result.errors);
+        }
+    }
+
+    #[test]
+    fn test_do_while_runs_its_body_at_least_once() {
+        let result = run("x = 10\nकुरु:\n    दर्श(x)\n    x = x + 1\nयावत् (x < 3)\nदर्श(\"अन्ते\", x)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "10\nअन्ते 11\n");
+
+        let src = "i = 0\nकुरु:\n    i = i + 1\n    यदि (i == 4):\n        विरम\nयावत् i < 10\nदर्श(i)";
+        assert_eq!(run(src).output, "4\n");
+        assert_eq!(Interpreter::default().check(src), vec![]);
+
+        let src = "कुरु:\n    दर्श(1)\nदर्श(2)";
+        assert!(run(src).errors[0].contains("कुरु-खण्डात्"), "{:?}", run(src).errors);
+        assert_eq!(Interpreter::default().check(src).len(), 1);
+    }
+
+    #[test]
+    fn test_endless_loop_runs_until_break() {
+        let src = "i = 0\nचक्र:\n    i = i + 1\n    यदि (i % 5 == 0):\n        विरम\n    दर्श(i)\nदर्श(\"अन्ते\", i)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1\n2\n3\n4\nअन्ते 5\n");
+        assert_eq!(Interpreter::default().check(src), vec![]);
+
+        let result = run("i = 0\nचक्र:\n    i = i + 1\nदर्श(i)");
+        assert_eq!(result.output, "10000\n");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].starts_with("Line 2: त्रुटिः: चक्रम् 10000"), "{:?}", result.errors);
+
+        let mut interp = Interpreter::default();
+        interp.set_loop_limit(3);
+        assert_eq!(interp.run("i = 0\nयावत् सत्य:\n    i = i + 1\nदर्श(i)").output, "3\n");
+        assert!(interp.run("चक्र:\n    दर्श(1)").errors[0].contains("चक्रम् 3 "));
+    }
+
+    #[test]
+    fn test_steps_snapshot_variables_after_each_statement() {
+        let mut interp = Interpreter::default();
+        let steps: Vec<Step> = interp.steps("x = 1\nदर्श(x)\n\nनाम = \"राम\"").collect();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps.iter().map(|s| s.line).collect::<Vec<_>>(), [1, 2, 4]);
+        assert_eq!(steps[1].text, "दर्श(x)");
+        assert_eq!(steps[1].output, "1\n");
+        assert_eq!(steps[0].vars, BTreeMap::from([("x".to_string(), "1".to_string())]));
+        assert_eq!(steps[1].vars, steps[0].vars);
+        assert_eq!(steps[2].vars["नाम"], "\"राम\"");
+
+        // A loop is one step, and errors stay with their statement.
+        let steps: Vec<Step> = interp.steps("परिभ्रमण i in परिधि(2):\n    दर्श(i)\nदर्श(अज्ञात)").collect();
+        assert_eq!(steps[0].output, "0\n1\n");
+        assert!(steps[0].errors.is_empty());
+        assert_eq!(steps[1].errors.len(), 1);
+        // Bindings made earlier in the session are still there.
+        assert_eq!(steps[1].vars["x"], "1");
+    }
+
+    #[test]
+    fn test_exported_state_imports_to_the_same_bindings() {
+        let mut interp = Interpreter::default();
+        let src = "कार्य योग(a, b):\n    प्रतिफल a + b\nध्रुव सीमा = 10\nx = 1.5\nनाम = \"रा\\\"म\"\nसूची = [1, [सत्य, शून्य], {\"क\": \"ख\"}]\nf = योग\ng = अंश(योग, 1)";
+        assert!(interp.run(src).errors.is_empty());
+        let state = interp.export_state();
+
+        let mut restored = Interpreter::default();
+        restored.import_state(&state).unwrap();
+        let show = "दर्श(रूप([सीमा, x, नाम, सूची, f, g]))";
+        assert_eq!(restored.run(show).output, interp.run(show).output);
+        assert_eq!(restored.export_state(), state);
+        assert!(restored.run("सीमा = 1").errors[0].contains("ध्रुव"));
+        // Functions aren't part of the state.
+        assert_eq!(restored.run("दर्श(योग(1, 2))").errors.len(), 1);
+
+        assert!(restored.import_state("{\"vars\": 5}").unwrap_err().contains("अवैधा स्थितिः"));
+    }
+
+    #[test]
+    fn test_check_valid_program_has_no_errors() {
+        let src = "कार्य f(n):\n    \"डॉक\"\n    यदि (n > 0):\n        प्रतिफल n\n    अन्यथा:\n        प्रतिफल 0\nयावत् (1 < 2):\n    दर्श(f(1))\nपरिभ्रमण i in परिधि(3):\n    x = i";
+        let interp = Interpreter::default();
+        assert_eq!(interp.check(src), vec![]);
+    }
+
+    #[test]
+    fn test_check_reports_malformed_blocks_without_running() {
+        let src = "दर्श(1)\nकार्य (a):\n    दर्श(a)\nपरिभ्रमण i परिधि(3):\n    दर्श(i)\nप्रतिफल 1\na + b = x\nअन्यथा:\n    दर्श(2)";
+        let interp = Interpreter::default();
+        let errors = interp.check(src);
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(messages[0].contains("कार्य नाम अवैधम्"));
+        assert!(messages[1].contains("परिभ्रमण स्वरूपः"));
+        assert!(messages[2].contains("प्रतिफल केवलं"));
+        assert!(messages[3].contains("असाइनस्य नाम"));
+        assert!(messages[4].contains("अन्यथा"));
+        // Nothing was executed, so no function was defined.
+        assert!(interp.functions.is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_what_run_would_reject() {
+        let interp = Interpreter::default();
+        let src = "दर्श(1) दर्श(2)
+x = 1 +
+यावत् (x >):
+    विरम
+विरम
+कार्य f():
+    परिभ्रमण i in [1]:
+        प्रतिफल i";
+        let errors = interp.check(src);
+        let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [Some(1), Some(2), Some(3), Some(5)], "{:?}", errors);
+        assert!(errors[1].message.contains("अपूर्णा अभिव्यक्तिः"), "{:?}", errors);
+        assert!(errors[3].message.contains("विरम केवलं"), "{:?}", errors);
+        // Each problem is one `run` reports too.
+        for line in src.lines().take(2) {
+            assert!(!run(line).errors.is_empty(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_undefined_variable_is_named() {
+        let result = run("x = 1\ny = x + अज्ञात_चर * 2");
+        assert_eq!(result.errors, vec!["Line 2: त्रुटिः: अपरिभाषितः चरः: अज्ञात_चर".to_string()]);
+        let result = run("दर्श(1, गुम)");
+        assert_eq!(result.errors, vec!["Line 1: त्रुटिः: अपरिभाषितः चरः: गुम".to_string()]);
+        assert_eq!(result.output, "");
+    }
+
+    #[test]
+    fn test_expression_errors_are_specific() {
+        let result = run("x = [1, 2] - 1\ny = 1 / 0\nz = [1, 2][5]\nw = {\"क\": 1}[\"ख\"]\nदर्श(-\"क\")");
+        assert_eq!(result.errors.len(), 5, "{:?}", result.errors);
+        assert!(result.errors[0].contains("सूची संख्या"), "{}", result.errors[0]);
+        assert!(result.errors[1].contains("शून्येन"), "{}", result.errors[1]);
+        assert!(result.errors[2].contains("अनुक्रमणिका 5"), "{}", result.errors[2]);
+        assert!(result.errors[3].contains("कुंजी 'ख'"), "{}", result.errors[3]);
+        assert!(result.errors[4].contains("'-'"), "{}", result.errors[4]);
+    }
+
+    #[test]
+    fn test_errors_inside_function_calls_propagate() {
+        let result = run("कार्य f(n):\n    प्रतिफल n + गुम\nदर्श(f(1))");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("अपरिभाषितः चरः: गुम"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_conditional_expression_selects_branch() {
+        let result = run("x = 7\ny = 3\nचरम = x यदि x > y अन्यथा y\nदर्श(चरम)\nलघु = x यदि x < y अन्यथा y\nदर्श(लघु)\nदर्श(\"सम\" यदि x % 2 == 0 अन्यथा \"विषम\")");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "7\n3\nविषम\n");
+    }
+
+    #[test]
+    fn test_conditional_expression_skips_untaken_branch() {
+        // The untaken branch would error (undefined variable, division by zero).
+        let result = run("x = 1 यदि सत्य अन्यथा गुम\ny = (1 / 0) यदि 1 > 2 अन्यथा 2\nदर्श(x, y)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1 2\n");
+        let result = run("z = 1 यदि 1 > 2");
+        assert!(result.errors[0].contains("अन्यथा अपेक्षितम्"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let result = run("x = 5\nयदि (0 < x < 10):\n    दर्श(\"अन्तः\")\nयदि (0 < x < 3):\n    दर्श(\"न\")\nयदि (1 <= x <= 5 == 5):\n    दर्श(\"मिश्र\")");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "अन्तः\nमिश्र\n");
+    }
+
+    #[test]
+    fn test_chained_comparison_evaluates_middle_once_and_short_circuits() {
+        let src = "गणक = 0\nकार्य मध्य():\n    गणक = गणक + 1\n    दर्श(\"मध्य\")\n    प्रतिफल 5\nयदि (0 < मध्य() < 10):\n    दर्श(\"हाँ\")\nयदि (9 < मध्य() < गुम):\n    दर्श(\"न\")";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "मध्य\nहाँ\nमध्य\n");
+    }
+
+    #[test]
+    fn test_logical_operators_in_conditions() {
+        let src = "x = 5\nयदि (x > 1 तथा x < 3 वा x == 5):\n    दर्श(1)\nयदि (न x > 1):\n    दर्श(2)\nयदि ((x > 1) तथा न (x > 9)):\n    दर्श(3)\nयदि (x > 9 तथा गुम > 0):\n    दर्श(4)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1\n3\n");
+    }
+
+    #[test]
+    fn test_untaken_branches_are_not_evaluated() {
+        // छाप prints when called, so any evaluation of an untaken branch
+        // shows up in the output.
+        let prelude = "कार्य छाप(x):\n    दर्श(\"छापितम्\")\n    प्रतिफल x\n";
+        let cases = [
+            ("यदि (असत्य तथा छाप(सत्य)):\n    दर्श(1)", ""),
+            ("यदि (सत्य वा छाप(असत्य)):\n    दर्श(1)", "1\n"),
+            ("यदि (1 > 2 तथा छाप(1) > 0 वा 2 > 1):\n    दर्श(1)", "1\n"),
+            ("यदि (न (सत्य वा छाप(सत्य))):\n    दर्श(1)", ""),
+            ("x = असत्य तथा छाप(सत्य)\nदर्श(x)", "असत्य\n"),
+            ("x = सत्य वा छाप(असत्य)\nदर्श(x)", "सत्य\n"),
+            ("दर्श(1 यदि सत्य अन्यथा छाप(2))", "1\n"),
+            ("दर्श(छाप(1) यदि असत्य अन्यथा 2)", "2\n"),
+            ("दर्श(1 यदि 1 > 0 वा छाप(1) > 0 अन्यथा छाप(2))", "1\n"),
+            ("यदि (सत्य):\n    दर्श(1)\nअन्यथा:\n    यदि (छाप(सत्य)):\n        दर्श(2)", "1\n"),
+            ("यदि (1 > 2 > छाप(0)):\n    दर्श(1)", ""),
+        ];
+        for (src, expected) in cases {
+            let result = run(&format!("{}{}", prelude, src));
+            assert!(result.errors.is_empty(), "{}: {:?}", src, result.errors);
+            assert_eq!(result.output, expected, "{}", src);
+        }
+        // The taken branch still runs exactly once.
+        let result = run(&format!("{}दर्श(सत्य तथा छाप(सत्य))\nदर्श(छाप(1) यदि सत्य अन्यथा 2)", prelude));
+        assert_eq!(result.output, "छापितम्\nसत्य\nछापितम्\n1\n");
+    }
+
+    #[test]
+    fn test_list_and_map_equality() {
+        let cases = [
+            ("[1, 2] == [1, 2]", "सत्य"),
+            ("[1, 2] != [1, 2]", "असत्य"),
+            ("[1, 2] == [2, 1]", "असत्य"),
+            ("[1, 2] == [1, 2, 3]", "असत्य"),
+            ("[1, 2, 3] != [1, 2]", "सत्य"),
+            ("[] == []", "सत्य"),
+            ("[[1, [2]], \"क\"] == [[1, [2]], \"क\"]", "सत्य"),
+            ("[[1, [2]]] == [[1, [3]]]", "असत्य"),
+            ("[1] == [\"1\"]", "असत्य"),
+            ("{\"a\": [1]} == {\"a\": [1]}", "सत्य"),
+            ("{\"a\": 1} == {\"b\": 1}", "असत्य"),
+            ("{\"a\": 1, \"b\": 2} == {\"b\": 2, \"a\": 1}", "सत्य"),
+            ("\"a\" == \"a\"", "सत्य"),
+            ("\"a\" == \"b\"", "असत्य"),
+            ("\"a\" != \"b\"", "सत्य"),
+            ("सत्य == सत्य", "सत्य"),
+            ("सत्य == असत्य", "असत्य"),
+            ("\"1\" == 1", "असत्य"),
+            ("[1] == {}", "असत्य"),
+        ];
+        for (expr, expected) in cases {
+            let result = run(&format!("x = {}\nदर्श(x)", expr));
+            assert!(result.errors.is_empty(), "{}: {:?}", expr, result.errors);
+            assert_eq!(result.output, format!("{}\n", expected), "{}", expr);
+        }
+        let result = run("a = [1, [2, 3]]\nयदि (a == [1, [2, 3]]):\n    दर्श(\"समम्\")");
+        assert_eq!(result.output, "समम्\n");
+
+        let result = run("नाम = \"राम\"\nयदि नाम == \"राम\":\n    दर्श(\"स्वागतम्\")");
+        assert_eq!(result.output, "स्वागतम्\n");
+        // Only numbers have an order.
+        for src in ["यदि ([1] < [2]):\n    दर्श(1)", "यदि \"a\" < \"b\":\n    दर्श(1)"] {
+            let result = run(src);
+            assert!(result.errors[0].contains("संख्यायाः तुलनाः एव"), "{}", result.errors[0]);
+        }
+    }
+
+    #[test]
+    fn test_repr_quotes_strings_and_keeps_structure() {
+        let result = run("दर्श(रूप(\"a\"))\nदर्श(रूप(5), रूप(\"5\"))\nदर्श(रूप([1, \"1\", [सत्य, शून्य]]))\nदर्श(रूप({\"क\": [\"ख\"]}))\nदर्श(रूप(\"a\\\"b\\\\\\n\"))\nदर्श(रूप([]), रूप(\"\"))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            result.output,
+            "\"a\"\n5 \"5\"\n[1, \"1\", [सत्य, शून्य]]\n{\"क\": [\"ख\"]}\n\"a\\\"b\\\\\\n\"\n[] \"\"\n"
+        );
+        // The result is itself a string.
+        let result = run("x = \"पं\\tक्ति\"\nदर्श(रूप(रूप(x)))\nदर्श(रूप([x]))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "\"\\\"पं\\\\tक्ति\\\"\"\n[\"पं\\tक्ति\"]\n");
+
+        let result = run("रूप(1, 2)");
+        assert!(result.errors[0].contains("रूप(x) एकं तर्कम्"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_interactive_mode_echoes_a_trailing_expression() {
+        let mut interp = Interpreter::default();
+        interp.set_interactive(true);
+        let result = interp.run("2 + 3");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "5\n");
+
+        let src = "कार्य वर्ग(n):\n    प्रतिफल n * n\nकार्य नमः():\n    दर्श(\"नमः\")";
+        assert!(interp.run(src).errors.is_empty());
+        for (input, expected) in [
+            ("x = [1, \"क\"]", ""),
+            ("x", "[1, \"क\"]\n"),
+            ("वर्ग(4)", "16\n"),
+            ("x[0] < 2", "सत्य\n"),
+            ("\"पाठ\"", "\"पाठ\"\n"),
+            ("नमः()", "नमः\n"),
+            ("वर्ग(2)\nवर्ग(3)", "9\n"),
+            ("दर्श(1)", "1\n"),
+        ] {
+            let result = interp.run(input);
+            assert!(result.errors.is_empty(), "{}: {:?}", input, result.errors);
+            assert_eq!(result.output, expected, "{}", input);
+        }
+        let result = interp.run("अज्ञात + 1");
+        assert!(result.errors[0].contains("अज्ञात"), "{:?}", result.errors);
+        assert_eq!(interp.run("परिधि(1,").errors.len(), 1);
+
+        // Running a file never echoes.
+        let result = run("2 + 3\nवर्ग = 4\nवर्ग");
+        assert_eq!(result.output, "");
+        assert!(result.errors[0].contains("अज्ञाता आज्ञा: 2 + 3"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_profile_counts_calls() {
+        let src = "कार्य वर्ग(n):\n    प्रतिफल n * n\nकार्य तथ्य(n):\n    यदि (n <= 1):\n        प्रतिफल 1\n    प्रतिफल n * तथ्य(n - 1)\nपरिभ्रमण i in परिधि(10):\n    वर्ग(i)\nदर्श(प्रतिचित्र(वर्ग, [1, 2]))\nदर्श(तथ्य(5))";
+        let mut interp = Interpreter::default();
+        interp.set_profile(true);
+        let result = interp.run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[1, 4]\n120\n");
+        let calls: Vec<_> = interp.profile().iter().map(|(name, p)| (name.as_str(), p.calls)).collect();
+        assert_eq!(calls, [("तथ्य", 5), ("वर्ग", 12)]);
+        // Times are recorded too.
+        assert!(interp.profile()["तथ्य"].time > Duration::ZERO);
+
+        // Profiling is off by default.
+        let mut interp = Interpreter::default();
+        interp.run(src);
+        assert!(interp.profile().is_empty());
+    }
+
+    #[test]
+    fn test_infinite_recursion_reports_an_error() {
+        // Runs on the test thread's small stack: calls below the limit must
+        // not overflow it either.
+        let src = "कार्य अनन्त(n):\n    प्रतिफल अनन्त(n + 1)\nअनन्त(0)\nदर्श(\"अग्रे\")";
+        let result = run(src);
+        assert_eq!(result.output, "अग्रे\n");
+        assert_eq!(
+            result.errors,
+            [format!("Line 3: त्रुटिः: अतिगभीरः पुनरावर्तनम्: अनन्त ({} स्तरेभ्यः अधिकम्)", DEFAULT_RECURSION_LIMIT)]
+        );
+
+        let depth = "कार्य गभीर(n):\n    यदि (n == 0):\n        प्रतिफल 0\n    प्रतिफल 1 + गभीर(n - 1)\n";
+        let mut interp = Interpreter::default();
+        interp.set_recursion_limit(10);
+        let result = interp.run(&format!("{}दर्श(गभीर(9))\nदर्श(गभीर(10))", depth));
+        assert_eq!(result.output, "9\n");
+        assert!(result.errors[0].starts_with("Line 6: त्रुटिः: अतिगभीरः पुनरावर्तनम्: गभीर (10 "), "{:?}", result.errors);
+
+        // The error can be caught like any other.
+        let result = interp.run("प्रयत्न:\n    गभीर(50)\nदोषे e:\n    दर्श(\"गृहीतम्\")");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "गृहीतम्\n");
+    }
+
+    #[test]
+    fn test_power_operator() {
+        let result = run("दर्श(2 ** 3 ** 2)\nदर्श((2 ** 3) ** 2)\nदर्श(2 ** 0.5)\nदर्श(-2 ** 2)\nदर्श(2 * 3 ** 2)\nदर्श(2 ** -1)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let lines: Vec<&str> = result.output.lines().collect();
+        assert_eq!(lines[..2], ["512", "64"]);
+        assert!(lines[2].starts_with("1.41421356"), "{}", lines[2]);
+        assert_eq!(lines[3..], ["-4", "18", "0.5"]);
+    }
+
+    #[test]
+    fn test_floor_division() {
+        let result = run("दर्श(7 // 2)\nदर्श(-7 // 2)\nदर्श(1 + 9 // 2 * 2)\nदर्श(खण्ड(-7, 2), भाग(-7, 2))\nदर्श(भाग(7, -2), 7 / 2)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "3\n-4\n9\n-4 1\n-1 3.5\n");
+
+        // `%` is the remainder that goes with `//`, like `भाग`.
+        let result = run("दर्श(-7 % 2, 7 % -2, -7 % 3 == भाग(-7, 3), 7 % 3)\nदर्श((-7 // 2) * 2 + -7 % 2)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1 -1 सत्य 1\n-7\n");
+
+        for src in ["दर्श(7 // 0)", "दर्श(खण्ड(7, 0))", "दर्श(भाग(7, 0))"] {
+            let result = run(src);
+            assert_eq!(result.errors.len(), 1, "{}", src);
+            assert!(result.errors[0].contains("शून्येन भागः"), "{}", result.errors[0]);
+        }
+    }
+
+    #[test]
+    fn test_constants() {
+        let result = run("ध्रुव दर = 3.14\nदर्श(दर * 2)\nकार्य f(दर):\n    दर = दर + 1\n    प्रतिफल दर\nदर्श(f(1))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "6.28\n2\n");
+
+        for src in [
+            "ध्रुव क = 1\nक = 2",
+            "ध्रुव क = 1\nध्रुव क = 2",
+            "ध्रुव क = 1\nपरिभ्रमण क in परिधि(2):\n    दर्श(क)",
+        ] {
+            let result = run(src);
+            assert_eq!(result.errors.len(), 1, "{}", src);
+            assert!(result.errors[0].contains("ध्रुवं परिवर्तयितुं न शक्यते"), "{}", result.errors[0]);
+        }
+        let result = run("ध्रुव क = 1\nक = 2\nदर्श(क)");
+        assert_eq!(result.output, "1\n");
+
+        let errors = Interpreter::default().check("ध्रुव क\nध्रुव x = 1");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_delete() {
+        let result = run("x = 5\nदर्श(x)\nविलोप x\ny = x");
+        assert_eq!(result.output, "5\n");
+        assert_eq!(result.errors, vec!["Line 4: त्रुटिः: अपरिभाषितः चरः: x".to_string()]);
+
+        let result = run("m = {\"क\": 1, \"ख\": 2}\nविलोप m[\"क\"]\nदर्श(m)\nl = [1, 2, 3]\nविलोप l[-1]\nदर्श(l)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "{ख: 2}\n[1, 2]\n");
+
+        for (src, expected) in [
+            ("विलोप अज्ञात", "अपरिभाषितः चरः"),
+            ("m = {}\nविलोप m[\"क\"]", "कुंजी 'क' नास्ति"),
+            ("ध्रुव क = 1\nविलोप क", "ध्रुवं परिवर्तयितुं न शक्यते"),
+        ] {
+            let result = run(src);
+            assert_eq!(result.errors.len(), 1, "{}", src);
+            assert!(result.errors[0].contains(expected), "{}", result.errors[0]);
+        }
+    }
+
+    #[test]
+    fn test_import_defines_functions_and_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        std::fs::write(dir.path().join("lib/गणित.paanini"), "आयात \"स्थिर.paanini\"\nकार्य वर्ग(n):\n    प्रतिफल n * n\n").unwrap();
+        std::fs::write(dir.path().join("lib/स्थिर.paanini"), "आधार = 10\n").unwrap();
+        let main = dir.path().join("main.paanini");
+        std::fs::write(&main, "").unwrap();
+
+        let mut interpreter = Interpreter::default();
+        interpreter.set_source_path(&main);
+        let result = interpreter.run("आयात \"lib/गणित.paanini\"\nदर्श(वर्ग(आधार))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "100\n");
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.paanini"), "आयात \"b.paanini\"\n").unwrap();
+        std::fs::write(dir.path().join("b.paanini"), "आयात \"a.paanini\"\n").unwrap();
+        let main = dir.path().join("a.paanini");
+
+        let mut interpreter = Interpreter::default();
+        interpreter.set_source_path(&main);
+        let result = interpreter.run("आयात \"b.paanini\"");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("चक्रीयः आयातः: a.paanini"), "{}", result.errors[0]);
+
+        let result = interpreter.run("आयात \"नास्ति.paanini\"");
+        assert!(result.errors[0].contains("पठितुं न शक्यते"), "{}", result.errors[0]);
+
+        // Without a base directory (e.g. in the web server) imports are refused.
+        let result = run("आयात \"a.paanini\"");
+        assert!(result.errors[0].contains("आयातः अत्र न अनुमतः"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_prelude_functions() {
+        let mut interpreter = Interpreter::with_prelude();
+        let result = interpreter.run("सूची = [3, 9, 4, 8]\nदर्श(वर्ग(4), गणना(सूची), योगफल(सूची), माध्य(सूची))\nदर्श(अधिकतम(सूची), न्यूनतम(सूची))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "16 4 24 6\n9 3\n");
+
+        for (function, doc) in interpreter.function_docs() {
+            for test in crate::doctest::extract_doctests(&function, &doc) {
+                let result = interpreter.clone().run(&test.code);
+                assert_eq!(result.output.trim_end(), test.expected, "{}", test.code);
+            }
+        }
+
+        let result = run("दर्श(वर्ग(4))");
+        assert_eq!(result.errors.len(), 1, "the default interpreter has no prelude");
+    }
+
+    #[test]
+    fn test_range_start_stop_step() {
+        let result = run("परिभ्रमण i in परिधि(5, 0, -1):\n    दर्श(i)\nदर्श(परिधि(2, 5))\nदर्श(परिधि(0, 1, 0.25))\nदर्श(परिधि(3, 0))\nदर्श(परिधि(-2))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "5\n4\n3\n2\n1\n[2, 3, 4]\n[0, 0.25, 0.5, 0.75]\n[]\n[]\n");
+
+        for (src, expected) in [
+            ("परिभ्रमण i in परिधि(0, 5, 0):\n    दर्श(i)", "पदं शून्यं"),
+            ("दर्श(परिधि(0 / 1, 1e400))", "परिमिताः"),
+            ("दर्श(परिधि(1, 2, 3, 4))", "एकं त्रीन् वा"),
+            ("दर्श(परिधि(\"5\"))", "संख्याः एव"),
+            ("x = परिधि(1e12)", "अधिकानि तत्त्वानि"),
+        ] {
+            let result = run(src);
+            assert_eq!(result.errors.len(), 1, "{}", src);
+            assert!(result.errors[0].contains(expected), "{}", result.errors[0]);
+        }
+    }
+
+    #[test]
+    fn test_string_repeat() {
+        let result = run("दर्श(\"ab\" * 3)\nदर्श(2 * \"न\")\nदर्श(\"[\" + \"x\" * 0 + \"]\")\nदर्श(\"[\" + \"x\" * -2 + \"]\")\nदर्श(\"ab\" * 2.9)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "ababab\nनन\n[]\n[]\nabab\n");
+
+        let result = run("दर्श(\"ab\" * \"c\")");
+        assert!(result.errors[0].contains("'*' पाठ्यम् पाठ्यम् च मध्ये न प्रयोज्यः"), "{}", result.errors[0]);
+        let result = run("x = \"ab\" * 1e11");
+        assert!(result.errors[0].contains("दीर्घतरं न भवेत्"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_slicing() {
+        let result = run("सूची = [1, 2, 3, 4]\nदर्श([1, 2, 3, 4][1:3])\nदर्श(सूची[-2:])\nदर्श(सूची[:-1])\nदर्श(सूची[::2], सूची[::-1])\nदर्श(सूची[3:1], सूची[10:20])\nदर्श(\"नमस्ते\"[1:3])\nदर्श(\"abcdef\"[-1:0:-2])");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[2, 3]\n[3, 4]\n[1, 2, 3]\n[1, 3] [4, 3, 2, 1]\n[] []\nमस\nfdb\n");
+
+        for (src, expected) in [
+            ("दर्श([1, 2][::0])", "पदं शून्यं"),
+            ("दर्श([1, 2][0.5:])", "पूर्णसंख्याः"),
+            ("दर्श(5[1:2])", "खण्डयितुं न शक्यते"),
+            ("दर्श([1, 2][1:2:3:4])", "[start:stop:step]"),
+        ] {
+            let result = run(src);
+            assert_eq!(result.errors.len(), 1, "{}", src);
+            assert!(result.errors[0].contains(expected), "{}", result.errors[0]);
+        }
+
+        // Steps and bounds too large for an integer take one element at most.
+        let result = run("दर्श([1, 2, 3][1::1e300], \"abc\"[1::1e300])\nदर्श([1, 2, 3][::-1e300], \"abc\"[-1e300:1e300:-1e300])");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[2] b\n[3] \n");
+    }
+
+    #[test]
+    fn test_membership() {
+        let src = "सूची = [1, 2, [3]]\nकोश = {\"क\": 1}\nयदि (2 मध्ये सूची):\n    दर्श(\"सूच्याम्\")\nयदि (\"मस्\" मध्ये \"नमस्ते\"):\n    दर्श(\"पाठ्ये\")\nयदि (न 5 मध्ये सूची):\n    दर्श(\"न सूच्याम्\")\nदर्श([3] मध्ये सूची, \"क\" मध्ये कोश, \"ख\" मध्ये कोश, 1 + 1 मध्ये [2])";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "सूच्याम्\nपाठ्ये\nन सूच्याम्\nसत्य सत्य असत्य सत्य\n");
+
+        let result = run("दर्श(1 मध्ये 5)");
+        assert!(result.errors[0].contains("अन्वेष्टुं न शक्यते"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_check_balanced_accepts_valid_program() {
+        assert_eq!(check_balanced("x = [1, (2 + 3)]\nदर्श(\"(\", x)\n!! ( टिप्पणी"), Ok(()));
+    }
+
+    #[test]
+    fn test_check_balanced_unmatched_quote() {
+        let err = check_balanced("x = 1\nदर्श(\"नमस्ते)\ny = 2").unwrap_err();
+        assert_eq!(err.line, Some(2), "{}", err);
+    }
+
+    #[test]
+    fn test_check_balanced_unmatched_bracket() {
+        let err = check_balanced("x = 1\ny = [1, 2\nदर्श(y)").unwrap_err();
+        assert_eq!(err.line, Some(2), "{}", err);
+        let err = check_balanced("x = (1 + 2))").unwrap_err();
+        assert_eq!(err.line, Some(1), "{}", err);
+    }
+
+    #[test]
+    fn test_function_output_and_return() {
+        let result = run("कार्य योगफल(a, b):\n    दर्श(\"योग\")\n    प्रतिफल a + b\nदर्श(योगफल(2, 3))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "योग\n5\n");
+    }
+
+    #[test]
+    fn test_repeated_calls_reuse_the_parsed_body() {
+        // A function with a long body, called many times, runs the body
+        // parsed once at definition rather than re-parsing it on each call.
+        let body: String = (0..40).map(|i| format!("    x{} = (n + {}) * 2 - n % 3\n", i, i)).collect();
+        let src = format!("कार्य f(n):\n{}    प्रतिफल x39\nयोग = 0\ni = 0\nयावत् i < 300:\n    योग = योग + f(i)\n    i = i + 1\nदर्श(योग)", body);
+        let result = run(&src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, format!("{}\n", (0..300).map(|n| (n + 39) * 2 - n % 3).sum::<i64>()));
+
+        let mut interp = Interpreter::default();
+        interp.run(&src);
+        let def = &interp.functions["f"];
+        let body = Arc::clone(&def.body);
+        interp.run("f(1)\nf(2)");
+        assert!(Arc::ptr_eq(&body, &interp.functions["f"].body));
+    }
+
+    #[test]
+    fn test_nested_blocks() {
+        let result = run("i = 0\nयावत् (i < 3):\n    यदि (i == 1):\n        दर्श(\"एक\")\n    i = i + 1\nदर्श(i)");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "एक\n3\n");
+    }
+
+    #[test]
+    fn test_sorted_numbers() {
+        let result = run("दर्श(क्रमबद्ध([3, 1, 2]))");
+        assert!(result.errors.is_empty());
+        assert_eq!(result.output, "[1, 2, 3]\n");
+    }
+
+    #[test]
+    fn test_sorted_descending() {
+        let result = run("दर्श(क्रमबद्ध([3, 1, 2], अवरोह=सत्य))\nदर्श(क्रमबद्ध([\"ख\", \"क\", \"ग\"], अवरोह=सत्य))\nदर्श(क्रमबद्ध([], अवरोह=सत्य))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[3, 2, 1]\n[ग, ख, क]\n[]\n");
+
+        // Equal keys keep their original order.
+
+        let result = run("कार्य प्रथम(x):\n    प्रतिफल x[0]\nदर्श(क्रमबद्ध([[1, \"a\"], [2, \"b\"], [1, \"c\"]], कुंजी=प्रथम, अवरोह=सत्य))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[[2, b], [1, a], [1, c]]\n");
+
+        let result = run("क्रमबद्ध([1], अवरोह=1)");
+        assert!(result.errors[0].contains("अवरोह सत्य/असत्य"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_map_and_filter() {
+        let src = "कार्य द्विगुण(x):\n    प्रतिफल x * 2\nकार्य सम(x):\n    प्रतिफल सत्य यदि x % 2 == 0 अन्यथा असत्य\nकार्य शेष(x):\n    प्रतिफल x % 2\nकार्य योग(a, b):\n    प्रतिफल a + b\nदर्श(प्रतिचित्र(द्विगुण, [1, 2, 3]))\nदर्श(प्रतिचित्र(\"द्विगुण\", []))\nदर्श(छानन(सम, [1, 2, 3, 4, 5]))\nदर्श(छानन(शेष, [1, 2, 3, 4, 5]))\nदर्श(प्रतिचित्र(अंश(योग, 10), [1, 2]))";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[2, 4, 6]\n[]\n[2, 4]\n[1, 3, 5]\n[11, 12]\n");
+
+        let result = run("दर्श(प्रतिचित्र(\"अज्ञात\", [1]))");
+        assert!(result.errors[0].contains("अज्ञातः कार्यः: अज्ञात"), "{}", result.errors[0]);
+        let result = run("दर्श(छानन(5, [1]))");
+        assert!(result.errors[0].contains("कार्यं सूचीं च अपेक्षते"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_first_class_functions() {
+        let src = "कार्य greet(नाम):\n    प्रतिफल \"नमस्ते \" + नाम\nकार्य योग(a, b):\n    प्रतिफल a + b\nकार्य योजक(n):\n    प्रतिफल अंश(योग, n)\nf = greet\nदर्श(f(\"भारत\"))\nकोश = {\"अभिवादन\": greet, \"मूल\": वर्गमूल}\nदर्श(कोश[\"अभिवादन\"](\"विश्व\"), कोश[\"मूल\"](9))\nदर्श(योजक(2)(3), [greet][0](\"राम\"))\nकोश[\"अभिवादन\"](\"x\")\nदर्श(f)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "नमस्ते भारत\nनमस्ते विश्व 3\n5 नमस्ते राम\n<कार्य greet>\n");
+
+        let result = run("x = 5\nदर्श([x][0](1))");
+        assert!(result.errors[0].contains("कार्यं न"), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_truthiness_in_conditions() {
+        let src = "कार्य परीक्षा(x):\n    यदि (x):\n        प्रतिफल \"सत्यवत्\"\n    अन्यथा:\n        प्रतिफल \"असत्यवत्\"\nपरिभ्रमण v in [[1], [], 0, 2, \"\", \"a\", {}, {\"क\": 1}, सत्य, असत्य]:\n    दर्श(परीक्षा(v))\nसूची = [3]\nयावत् (सूची):\n    सूची = सूची[1:]\n    दर्श(\"एकवारम्\")\nयदि (न सूची तथा 1):\n    दर्श(\"रिक्ता\")";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let expected = ["सत्यवत्", "असत्यवत्", "असत्यवत्", "सत्यवत्", "असत्यवत्", "सत्यवत्", "असत्यवत्", "सत्यवत्", "सत्यवत्", "असत्यवत्", "एकवारम्", "रिक्ता"];
+        assert_eq!(result.output, expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn test_match() {
+        let src = "कार्य नाम(n):\n    विकल्प n:\n        स्थिति 1:\n            प्रतिफल \"एकम्\"\n        स्थिति 2, 3:\n            प्रतिफल \"अल्पम्\"\n        अन्यथा:\n            प्रतिफल \"बहु\"\nपरिभ्रमण i in [1, 2, 3, 7]:\n    दर्श(नाम(i))\nविकल्प \"ख\":\n    स्थिति \"क\":\n        दर्श(\"क\")\n    स्थिति \"ख\":\n        दर्श(\"ख\")\n    स्थिति \"ख\":\n        दर्श(\"पुनः ख\")\nविकल्प 5:\n    स्थिति 1:\n        दर्श(\"न\")\nदर्श(\"अन्तः\")";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "एकम्\nअल्पम्\nअल्पम्\nबहु\nख\nअन्तः\n");
+
+        let result = run("विकल्प 1:\n    अन्यथा:\n        दर्श(1)\n    स्थिति 1:\n        दर्श(2)\nदर्श(3)");
+        assert_eq!(result.output, "3\n");
+        assert!(result.errors[0].contains("अन्यथा अन्तिमं"), "{:?}", result.errors);
+
+        let errors = Interpreter::default().check("विकल्प 1:\n    दर्श(1)\nस्थिति 2:\n    दर्श(2)");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_try_catches_errors() {
+        let src = "प्रयत्न:\n    दर्श(\"पूर्वम्\")\n    x = 1 / 0\n    दर्श(\"न मुद्रितम्\")\nदोषे e:\n    दर्श(\"दोषः:\", e)\nदर्श(\"अनन्तरम्\")";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "पूर्वम्\nदोषः: त्रुटिः: शून्येन भागः न शक्यः\nअनन्तरम्\n");
+
+        // Errors inside calls and loops are caught, and nested blocks catch their own.
+        let src = "कार्य f(n):\n    प्रतिफल [1, 2][n]\nप्रयत्न:\n    परिभ्रमण i in परिधि(5):\n        प्रयत्न:\n            दर्श(अज्ञात)\n        दोषे:\n            दर्श(\"आन्तरम्\", i)\n        दर्श(f(i))\nदोषे बाह्य:\n    दर्श(बाह्य)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "आन्तरम् 0\n1\nआन्तरम् 1\n2\nआन्तरम् 2\nत्रुटिः: अनुक्रमणिका 2 सीमातः बहिः (दीर्घता 2)\n");
+
+        // Without an error the दोषे block is skipped; an error in it is not caught by itself.
+        let result = run("प्रयत्न:\n    दर्श(1)\nदोषे e:\n    दर्श(2)\nप्रयत्न:\n    दर्श(1 / 0)\nदोषे e:\n    दर्श(अज्ञात)\nदर्श(3)");
+        assert_eq!(result.output, "1\n3\n");
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+
+        let result = run("प्रयत्न:\n    दर्श(1)\nदर्श(2)");
+        assert!(result.errors[0].contains("दोषे अपेक्षितम्"), "{:?}", result.errors);
+        let errors = Interpreter::default().check("प्रयत्न:\n    दर्श(1)\nदोषे 5 + 1:\n    दर्श(2)\nदोषे:\n    दर्श(3)\nप्रयत्न:\n    दर्श(4)");
+        let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [Some(1), Some(5), Some(7)], "{:?}", errors);
+    }
+
+    #[test]
+    fn test_raise() {
+        let src = "कार्य मूल(x):\n    यदि (x < 0):\n        दोषं कृ(\"ऋणसंख्या: \" + x)\n    प्रतिफल वर्गमूल(x)\nप्रयत्न:\n    दर्श(मूल(9))\n    दर्श(मूल(-4))\nदोषे e:\n    दर्श(\"दोषः\", e)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "3\nदोषः ऋणसंख्या: -4\n");
+
+        let result = run("दोषं कृ(\"असफलम्\")\nदर्श(\"अग्रे\")");
+        assert_eq!(result.output, "अग्रे\n");
+        assert_eq!(result.errors, vec!["Line 1: असफलम्".to_string()]);
+
+        let errors = Interpreter::default().check("दोषं कृ()\nदोषं कृ \"x\"");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_assertions() {
+        let result = run("x = 5\nनिश्चय (x == 5), \"x पञ्च भवेत्\"\nनिश्चय (x > 1)\nनिश्चय [1]\nदर्श(\"सफलम्\")");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "सफलम्\n");
+
+        let result = run("x = 5\nनिश्चय (x < 0), \"x ऋणम् भवेत्, प्राप्तम् \" + x\nनिश्चय (x == 4)\nप्रयत्न:\n    निश्चय ([])\nदोषे e:\n    दर्श(e)");
+        assert_eq!(result.output, "त्रुटिः: निश्चयः विफलः: ([])\n");
+        assert_eq!(
+            result.errors,
+            vec![
+                "Line 2: त्रुटिः: निश्चयः विफलः: x ऋणम् भवेत्, प्राप्तम् 5".to_string(),
+                "Line 3: त्रुटिः: निश्चयः विफलः: (x == 4)".to_string(),
+            ]
+        );
+
+        let errors = Interpreter::default().check("निश्चय\nनिश्चय (1),\nनिश्चय (1), \"ठीकम्\"\nनिश्चयः = 1");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_global_declarations() {
+        let src = "गणक = 0\nकार्य वर्धय(n):\n    \"गणकं वर्धयति\"\n    वैश्विक गणक\n    गणक = गणक + n\nकार्य द्विवारम्():\n    वर्धय(1)\n    वर्धय(1)\n    दर्श(\"अन्तः\", गणक)\nकार्य स्थानीय():\n    गणक = 100\nवर्धय(5)\nद्विवारम्()\nस्थानीय()\nदर्श(गणक)\nकार्य नूतन():\n    वैश्विक क, ख\n    क = 1\n    ख = 2\nनूतन()\nदर्श(क, ख)";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "अन्तः 7\n7\n1 2\n");
+
+        let result = run("कार्य f():\n    x = 1\n    वैश्विक x\nf()");
+        assert!(result.errors[0].contains("आरम्भे एव"), "{:?}", result.errors);
+        let errors = Interpreter::default().check("कार्य f():\n    वैश्विक 5 + 1\nवैश्विक x");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_assigning_comparisons() {
+        let result = run("y = 2\nz = 2\nx = y == z\nदर्श(x)\nw = y != z\nदर्श(w)\nv = 1 < y <= 2 तथा न w\nदर्श(v)\nu = (y >= 3)\nदर्श(u)\nदर्श(y == 2, y + 1 > 5)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "सत्य\nअसत्य\nसत्य\nअसत्य\nसत्य असत्य\n");
+
+        assert_eq!(split_assignment("x = y == z"), Some(("x", "y == z")));
+        assert_eq!(split_assignment("x == y"), None);
+        assert_eq!(split_assignment("x <= y"), None);
+        assert_eq!(split_assignment("f(a=1)"), None);
+    }
+
+    #[test]
+    fn test_multiple_assignment() {
+        let result = run("a, b = 1, 2\nदर्श(a, b)\na, b = b, a\nदर्श(a, b)\nx, y, z = [\"क\", [1], a + b]\nदर्श(x, y, z)\nक, ख = परिधि(2)\nदर्श(क, ख)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1 2\n2 1\nक [1] 3\n0 1\n");
+
+        let result = run("a, b = 1, 2, 3");
+        assert!(result.errors[0].contains("2 नामानि किन्तु 3 मूल्यानि"), "{:?}", result.errors);
+        let result = run("सूची = [1]\na, b = सूची\nदर्श(\"अग्रे\")");
+        assert!(result.errors[0].contains("2 नामानि किन्तु 1 मूल्यानि"), "{:?}", result.errors);
+        let result = run("a, b = 5");
+        assert!(result.errors[0].contains("विभक्तुं न शक्यते"), "{:?}", result.errors);
+        let result = run("a, b c = 1, 2");
+        assert!(result.errors[0].contains("असाइनस्य नाम अवैधम्"), "{:?}", result.errors);
+        let result = run("ध्रुव क = 1\nख, क = 2, 3\nदर्श(ख)");
+        assert!(result.errors[0].contains("परिवर्तयितुं न शक्यते"), "{:?}", result.errors);
+        assert!(result.output.is_empty(), "a failed unpacking binds nothing");
+
+        assert!(Interpreter::default().check("a, b = 1, 2\nc, d = [1, 2]").is_empty());
+        assert_eq!(Interpreter::default().check("a, b = 1, 2, 3").len(), 1);
+    }
+
+    #[test]
+    fn test_enumerate_with_loop_unpacking() {
+        let result = run("परिभ्रमण i, v in क्रमसूची([\"a\", \"b\"]):\n    दर्श(i, v)\nदर्श(क्रमसूची([]))\nदर्श(क्रमसूची([[1]]))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "0 a\n1 b\n[]\n[[0, [1]]]\n");
+
+        let result = run("परिभ्रमण कुंजी, मूल्य in आइटम({\"क\": 1, \"ख\": 2}) यावत् (मूल्य < 2):\n    दर्श(कुंजी, मूल्य)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "क 1\n");
+
+        let result = run("परिभ्रमण a, b in [[1, 2], [3]]:\n    दर्श(a + b)");
+        assert_eq!(result.output, "3\n");
+        assert!(result.errors[0].contains("2 नामानि किन्तु 1 मूल्यानि"), "{:?}", result.errors);
+        let result = run("परिभ्रमण a, b in [1]:\n    दर्श(a)");
+        assert!(result.errors[0].contains("विभक्तुं न शक्यते"), "{:?}", result.errors);
+        let result = run("क्रमसूची(5)");
+        assert!(result.errors[0].contains("क्रमसूची(सूची)"), "{:?}", result.errors);
+        assert_eq!(Interpreter::default().check("परिभ्रमण a, in [1]:\n    दर्श(a)").len(), 1);
+    }
+
+    #[test]
+    fn test_unique_keeps_first_occurrences() {
+        let result = run("दर्श(अद्वितीय([1, 2, 2, 3, 1]))\nदर्श(अद्वितीय([\"ख\", \"क\", \"ख\"]))\nदर्श(अद्वितीय([]))\nदर्श(अद्वितीय([1, \"1\", [1], [1], सत्य, 1]))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[1, 2, 3]\n[ख, क]\n[]\n[1, 1, [1], सत्य]\n");
+
+        let result = run("अद्वितीय(\"कक\")");
+        assert!(result.errors[0].contains("अद्वितीय(सूची)"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_format_fixed_decimals() {
+        let cases = [
+            ("स्वरूप(3.14159, 2)", "3.14"),
+            ("स्वरूप(2.71828, 3)", "2.718"),
+            ("स्वरूप(1.996, 2)", "2.00"),
+            ("स्वरूप(0.999, 0)", "1"),
+            ("स्वरूप(7, 3)", "7.000"),
+            ("स्वरूप(-1.5, 1)", "-1.5"),
+            ("स्वरूप(1234.5678, 1) + \" रू\"", "1234.6 रू"),
+        ];
+        for (expr, expected) in cases {
+            let result = run(&format!("दर्श(रूप({}))", expr));
+            assert!(result.errors.is_empty(), "{}: {:?}", expr, result.errors);
+            assert_eq!(result.output, format!("\"{}\"\n", expected), "{}", expr);
+        }
+        for bad in ["स्वरूप(1, -1)", "स्वरूप(1, 1.5)", "स्वरूप(1, 101)"] {
+            let result = run(bad);
+            assert!(result.errors[0].contains("दशमलवस्थानानि"), "{}: {:?}", bad, result.errors);
+        }
+        let result = run("स्वरूप(\"1\", 2)");
+        assert!(result.errors[0].contains("संख्याद्वयम्"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_clock_builtins() {
+        let result = run("दर्श(कालः())\nदर्श(कालमुद्रा())");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(result.nondeterministic);
+        let lines: Vec<&str> = result.output.lines().collect();
+        // Later than 2020-01-01.
+        assert!(lines[0].parse::<f64>().unwrap() > 1_577_836_800.0, "{}", lines[0]);
+        assert_eq!(lines[1].len(), "2020-01-01 00:00:00".len(), "{}", lines[1]);
+        assert!(!run("दर्श(1)").nondeterministic);
+
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_704_067_199), "2023-12-31 23:59:59");
+        assert_eq!(run("कालः(1)").errors.len(), 1);
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut interp = Interpreter::default();
+        interp.set_base_dir(dir.path());
+        interp.set_file_access(true);
+        let result = interp.run("लिख(\"नोट.txt\", \"नमस्ते\\nलोक\")\nपाठ = पठ(\"नोट.txt\")\nदर्श(विभाजन(पाठ, \"\\n\"))");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[नमस्ते, लोक]\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("नोट.txt")).unwrap(), "नमस्ते\nलोक");
+
+        let result = interp.run("पठ(\"अनुपस्थित.txt\")");
+        assert!(result.errors[0].contains("पठितुं न शक्यते"), "{:?}", result.errors);
+        assert_eq!(interp.run("लिख(\"क.txt\", 5)").errors.len(), 1);
+    }
+
+    #[test]
+    fn test_file_builtins_are_disabled_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("x.txt");
+        std::fs::write(&path, "गुप्तम्").unwrap();
+        let src = format!("दर्श(पठ(\"{}\"))\nलिख(\"{}\", \"\")", path.display(), path.display());
+        let result = run(&src);
+        assert_eq!(result.output, "");
+        assert_eq!(result.errors.len(), 2);
+        assert!(result.errors.iter().all(|e| e.contains("अननुमतम्")), "{:?}", result.errors);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "गुप्तम्");
+    }
+
+    #[test]
+    fn test_environment_variables() {
+        std::env::set_var("PAANINI_TEST_ENV", "मूल्यम्");
+        let src = "दर्श(पर्यावरण(\"PAANINI_TEST_ENV\"))\nदर्श(पर्यावरण(\"PAANINI_TEST_UNSET\"))";
+        let mut interp = Interpreter::default();
+        interp.set_env_access(true);
+        let result = interp.run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "मूल्यम्\nशून्य\n");
+
+        let result = run(src);
+        assert_eq!(result.output, "");
+        assert!(result.errors[0].contains("अननुमतम्"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_sleep_pauses_then_continues() {
+        let start = Instant::now();
+        let result = run("दर्श(1)\nविश्राम(0.03)\nदर्श(2)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "1\n2\n");
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        let mut interp = Interpreter::default();
+        interp.set_max_sleep(Duration::from_millis(10));
+        let start = Instant::now();
+        assert!(interp.run("विश्राम(60)").errors.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        for bad in ["विश्राम(-1)", "विश्राम(\"1\")", "विश्राम()"] {
+            assert_eq!(run(bad).errors.len(), 1, "{}", bad);
+        }
+        assert!(run("विश्राम(-1)").errors[0].contains("ऋणात्मकं"));
+    }
+
+    #[test]
+    fn test_seeded_random_numbers_repeat() {
+        let src = "बीज(42)\nदर्श(यादृच्छिक(), यादृच्छिक())\nकार्य पासा():\n    प्रतिफल यादृच्छिकसंख्या(1, 6)\nदर्श([पासा(), पासा(), पासा(), पासा(), पासा(), पासा()])";
+        let first = run(src);
+        assert!(first.errors.is_empty(), "{:?}", first.errors);
+        assert!(!first.nondeterministic);
+        let second = run(src);
+        assert_eq!(first.output, second.output);
+        // A different seed gives a different sequence.
+        assert_ne!(run(&src.replace("42", "7")).output, first.output);
+
+        let lines: Vec<&str> = first.output.lines().collect();
+        let floats: Vec<f64> = lines[0].split(' ').map(|n| n.parse().unwrap()).collect();
+        assert!(floats.iter().all(|x| (0.0..1.0).contains(x)), "{:?}", floats);
+        assert_ne!(floats[0], floats[1]);
+        // The sequence carries on across calls instead of restarting in each.
+        let rolls: Vec<i64> = lines[1].trim_matches(['[', ']']).split(", ").map(|n| n.parse().unwrap()).collect();
+        assert!(rolls.iter().all(|r| (1..=6).contains(r)), "{:?}", rolls);
+        assert!(rolls.windows(2).any(|w| w[0] != w[1]), "{:?}", rolls);
+
+        let result = run("दर्श(यादृच्छिकसंख्या(3, 3))");
+        assert_eq!(result.output, "3\n");
+        assert!(result.nondeterministic);
+        let result = run("दर्श(यादृच्छिकसंख्या(-9007199254740992, -9007199254740992))");
+        assert_eq!(result.output, "-9007199254740992\n");
+        for bad in [
+            "यादृच्छिकसंख्या(2, 1)",
+            "यादृच्छिकसंख्या(0.5, 1)",
+            "यादृच्छिकसंख्या(1e300, 1e300)",
+            "यादृच्छिकसंख्या(-1e17, 0)",
+            "बीज(\"क\")",
+            "यादृच्छिक(1)",
+        ] {
+            assert_eq!(run(bad).errors.len(), 1, "{}", bad);
+        }
+        assert!(run("यादृच्छिकसंख्या(1e300, 1e300)").errors[0].contains("a <= b"));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let result = run(r#"दर्श("क\nख")
+दर्श("क\tख")
+उक्तिः = "सः \"नमः\" इति वदति, (\\)"
+दर्श(उक्तिः)
+दर्श("\u{0905}")"#);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "क\nख\nक\tख\nसः \"नमः\" इति वदति, (\\)\nअ\n");
+
+        let result = run(r#"दर्श("क\qख")"#);
+        assert!(result.errors[0].contains("'\\q' (स्थानम् 2)"), "{:?}", result.errors);
+        let result = run(r#"दर्श("\u{zz}")"#);
+        assert!(result.errors[0].contains("यूनिकोड"), "{:?}", result.errors);
+        assert!(check_balanced(r#"x = "\"(""#).is_ok());
+    }
+
+    #[test]
+    fn test_triple_quoted_strings() {
+        let mut interp = Interpreter::default();
+        let src = "पत्रम् = \"\"\"प्रिय \"मित्र\",\nकुशलम्\\t!\"\"\"\nदर्श(पत्रम्)\nकार्य f():\n    \"\"\"प्रथमम्\n    >>> दर्श(1)\n    1\"\"\"\n    प्रतिफल 1\nदर्श(\"\"\"एक\"\"\" + f())";
+        let result = interp.run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "प्रिय \"मित्र\",\nकुशलम्\t!\nएक1\n");
+        let doc = ["प्रथमम्", ">>> दर्श(1)", "1"].map(String::from).to_vec();
+        assert_eq!(interp.function_docs(), vec![("f".to_string(), doc)]);
+
+        assert!(check_balanced(src).is_ok());
+        assert_eq!(check_balanced("x = \"\"\"क\nख").unwrap_err().line, Some(1));
+        let result = run("x = \"\"\"क\nख\nग\"\"\"\nअज्ञात");
+        assert!(result.errors[0].starts_with("Line 4:"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_trace_records_statements_assignments_and_iterations() {
+        let mut interp = Interpreter::default();
+        interp.set_trace(true);
+        let result = interp.run("x = 1\nपरिभ्रमण i in परिधि(2):\n    x = x + i\nदर्श(x)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "2\n");
+        assert_eq!(
+            result.trace,
+            [
+                "Line 1: x = 1",
+                "Line 1: x ← 1",
+                "Line 2: परिभ्रमण i in परिधि(2)",
+                "Line 2: पुनरावृत्तिः 1: i ← 0",
+                "Line 3: x = x + i",
+                "Line 3: x ← 1",
+                "Line 2: पुनरावृत्तिः 2: i ← 1",
+                "Line 3: x = x + i",
+                "Line 3: x ← 2",
+                "Line 4: दर्श(x)",
+            ]
+        );
+
+        // Statements inside a function are traced where the call runs them.
+        let result = interp.run("कार्य f(n):\n    x = n * 2\n    प्रतिफल x\ny = f(3)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(
+            result.trace,
+            [
+                "Line 1: कार्य f(n)",
+                "Line 4: y = f(3)",
+                "Line 2: x = n * 2",
+                "Line 2: x ← 6",
+                "Line 3: प्रतिफल x",
+                "Line 4: y ← 6",
+            ]
+        );
+
+        assert!(run("x = 1").trace.is_empty());
+    }
+
+    #[test]
+    fn test_run_report_json() {
+        let report = RunReport::from(run("दर्श(1)\nदर्श(अज्ञात)"));
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "output": "1\n",
+                "errors": [{"line": 2, "message": "त्रुटिः: अपरिभाषितः चरः: अज्ञात"}],
+                "exit_code": 1,
+            })
+        );
+        assert_eq!(RunReport::from(run("दर्श(1)")).exit_code, 0);
+        assert_eq!(InterpError::from_reported("त्रुटिः: x").line, None);
+    }
+
+    #[test]
+    fn test_errors_in_blocks_report_source_lines() {
+        let src = "x = 0\n\nपरिभ्रमण i in परिधि(2):\n    # टिप्पणी\n    x = x + i\n\n    यदि (i == 1):\n        दर्श(अज्ञात)\n    अन्यथा:\n        x = x / 0\nकार्य f():\n    \"वर्णनम्\"\n    दर्श(अपरम्)\nf()";
+        let result = run(src);
+        assert_eq!(
+            result.errors,
+            [
+                "Line 10: त्रुटिः: शून्येन भागः न शक्यः",
+                "Line 8: त्रुटिः: अपरिभाषितः चरः: अज्ञात",
+                "Line 14: Line 13: त्रुटिः: अपरिभाषितः चरः: अपरम्",
+            ]
+        );
+        let errors = Interpreter::default().check("यावत् (सत्य):\n    x = 1\n\n    यदि (x):\n        प्रतिफल 1");
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), [Some(5)], "{:?}", errors);
+    }
+
+    #[test]
+    fn test_booleans_print_in_sanskrit_and_round_trip() {
+        let result = run("x = 3\ny = 4\nदर्श(x == y, x < y)\nपाठः = \"\" + (x != y)\nदर्श(पाठः)");
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "असत्य सत्य\nसत्य\n");
+
+        for b in [true, false] {
+            assert_eq!(sanskrit_to_bool(&Value::Bool(b).to_string()), Some(b));
+        }
+        assert_eq!(sanskrit_to_bool("true"), None);
+    }
+
+    #[test]
+    fn test_null_literal() {
+        let src = "कार्य मौनम्():\n    x = 1\nफलम् = मौनम्()\nरिक्तम् = शून्य\nदर्श(रिक्तम्, फलम् == शून्य, 0 == शून्य, शून्य != 0)\nयदि (मौनम्() == शून्य):\n    दर्श(\"न किमपि\")\nयदि (रिक्तम्):\n    दर्श(\"सत्यम्\")\nअन्यथा:\n    दर्श(\"मिथ्या\")";
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "शून्य सत्य असत्य सत्य\nन किमपि\nमिथ्या\n");
+
+        let result = run("दर्श(शून्य < 1)");
+        assert!(result.errors[0].contains("संख्यायाः तुलनाः"), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_single_quoted_strings() {
+        let result = run(r#"उक्तिः = 'सः "नमः" इति वदति'
+दर्श(उक्तिः)
+दर्श('it\'s' + "'" + ('(' + ")"))
+दर्श('क' + "ख", "," मध्ये 'a,b')"#);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "सः \"नमः\" इति वदति\nit's'()\nकख सत्य\n");
+
+        let err = check_balanced("x = 'क\"").unwrap_err();
+        assert!(err.message.contains("'''"), "{}", err.message);
+        assert!(check_balanced("x = \"क'").is_err());
+    }
+
+    #[test]
+    fn test_sorted_maps_by_key_function() {
+        let src = r#"कार्य आयुः(जनः):
+    प्रतिफल जनः["आयु"]
+जनाः = [{"नाम": "राम", "आयु": 30}, {"नाम": "सीता", "आयु": 25}, {"नाम": "लक्ष्मण", "आयु": 25}]
+क्रमिताः = क्रमबद्ध(जनाः, कुंजी=आयुः)
+दर्श(क्रमिताः[0]["नाम"])
+दर्श(क्रमिताः[1]["नाम"])
+दर्श(क्रमिताः[2]["नाम"])"#;
+        let result = run(src);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "सीता\nलक्ष्मण\nराम\n");
+    }
+
+    #[test]
+    fn test_sorted_rejects_mixed_types() {
+        let result = run("क्रमबद्ध([1, \"a\"])");
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_constants_are_reserved() {
+        let result = run("पाई = 3");
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("स्थिरांकः"));
     }
-    Err("त्रुटिः: '}' न लब्धम्".into())
 }
@@ -0,0 +1,22 @@
+//! Paanini: a Sanskrit programming language with Python-like syntax.
+//!
+//! The parser and interpreter build for any target, including
+//! `wasm32-unknown-unknown` with the `wasm` feature. The `cli` feature (on
+//! by default) adds the web server and file watcher that the `paanini`
+//! command uses.
+
+pub mod ast;
+pub mod build_cache;
+pub mod diff;
+pub mod doctest;
+pub mod examples;
+pub mod formatter;
+pub mod interpreter;
+#[cfg(feature = "cli")]
+pub mod server;
+pub mod test_runner;
+pub mod transpiler;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "cli")]
+pub mod watch;
@@ -1,15 +1,16 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use colored::*;
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::io::IsTerminal;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-mod interpreter;
-mod server;
-mod transpiler;
+use paanini_lang::{build_cache, diff, doctest, examples, formatter, interpreter, server, test_runner, transpiler, watch};
 
-use interpreter::Interpreter;
+use interpreter::{FunctionProfile, InterpError, Interpreter, RunReport};
 
 #[derive(Parser)]
 #[command(name = "paanini")]
@@ -24,11 +25,17 @@ Examples:
     paanini                          # Start interactive REPL
     paanini run hello.paanini         # Run a Sanskrit source file
     paanini build hello.paanini       # Transpile to Rust and build binary
+    paanini transpile hello.paanini   # Print the generated Rust
     paanini serve --port 8080        # Start web IDE server
+    paanini diff old.paanini new.paanini  # Compare two programs' output
 ")]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Disable colored output
+    #[arg(long, global = true, help = "Disable colored output (also off when NO_COLOR is set or stdout is not a terminal)")]
+    no_color: bool,
 }
 
 #[derive(Subcommand)]
@@ -40,13 +47,53 @@ enum Commands {
     /// Run a Paanini source file
     #[command(about = "Execute a .paanini source file")]
     Run {
+        /// Path to .paanini source file, or `-` for stdin
+        #[arg(help = "Path to the .paanini file to execute; `-` (or no file with piped input) reads the program from stdin")]
+        file: Option<String>,
+
+        /// Arguments for the program
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, help = "Arguments after the file are passed to the program in the आदेशतर्क list")]
+        args: Vec<String>,
+
+        /// Show detailed execution information; repeat for a statement trace
+        #[arg(short, long, action = clap::ArgAction::Count, help = "Enable verbose output (-vv also traces each statement, assignment and loop iteration)")]
+        verbose: u8,
+
+        /// Validate delimiters before running
+        #[arg(long, help = "Check brackets and quotes are balanced before executing")]
+        check_balanced: bool,
+
+        /// Load the standard-library prelude first
+        #[arg(long, help = "Make the prelude's library functions (वर्ग, योगफल, माध्य, ...) available")]
+        prelude: bool,
+
+        /// Print the result as JSON
+        #[arg(long, help = "Print one JSON object {output, errors, exit_code} instead of decorated text")]
+        json: bool,
+
+        /// Re-run whenever the file changes
+        #[arg(long, conflicts_with = "json", help = "Re-run the file each time it is saved, until Ctrl-C")]
+        watch: bool,
+
+        /// Report how long the program took
+        #[arg(long, conflicts_with = "json", help = "Print the wall-clock run time and per-function call counts and times to stderr")]
+        time: bool,
+
+        /// Print the value of a final bare expression
+        #[arg(long, help = "If the program ends with a bare expression, such as `योग(सूची)`, print its value")]
+        echo_last: bool,
+
+        /// Deepest allowed nesting of function calls
+        #[arg(long, value_name = "DEPTH", default_value_t = interpreter::DEFAULT_RECURSION_LIMIT, help = "Fail calls nested deeper than this instead of recursing further")]
+        recursion_limit: usize,
+    },
+
+    /// Check a Paanini source file without running it
+    #[command(about = "Check a .paanini file for syntax and block-structure errors without executing it")]
+    Check {
         /// Path to .paanini source file
-        #[arg(help = "Path to the .paanini file to execute")]
+        #[arg(help = "Path to the .paanini file to check")]
         file: String,
-        
-        /// Show detailed execution information
-        #[arg(short, long, help = "Enable verbose output")]
-        verbose: bool,
     },
     
     /// Build Paanini code to Rust binary (transpilation)
@@ -63,40 +110,177 @@ enum Commands {
         /// Release mode build
         #[arg(short, long, help = "Build in release mode")]
         release: bool,
+
+        /// Rebuild even if the source is unchanged since the last build
+        #[arg(long, help = "Ignore the build cache and always recompile")]
+        force: bool,
     },
     
+    /// Transpile Paanini code to Rust without compiling
+    #[command(about = "Transpile .paanini code to Rust and print or save it, without running rustc")]
+    Transpile {
+        /// Path to .paanini source file
+        #[arg(help = "Path to the .paanini file to transpile")]
+        file: String,
+
+        /// Where to emit the Rust code
+        #[arg(long, value_enum, help = "Emit to stdout or to a file (default: file when --output is given, else stdout)")]
+        emit: Option<Emit>,
+
+        /// Output .rs path when emitting to a file
+        #[arg(short, long, help = "Path of the .rs file to write (default: <name>.rs)")]
+        output: Option<String>,
+    },
+
     /// Start web IDE server
     #[command(about = "Start the web-based Paanini IDE")]
     Serve {
         /// Port to run server on
         #[arg(short, long, default_value = "8080", help = "Port number for web server")]
         port: u16,
+
+        /// Cache identical /api/run submissions
+        #[arg(long, value_name = "SECONDS", help = "Cache /api/run results for identical code for this many seconds (only for deterministic programs)")]
+        cache_ttl: Option<u64>,
+
+        /// Limit on how long one /api/run request may execute
+        #[arg(long, value_name = "SECONDS", default_value = "5", help = "Stop /api/run programs that run longer than this many seconds")]
+        timeout: u64,
+
+        /// Per-client allowance for running programs
+        #[arg(long, value_name = "PER_MINUTE", default_value = "60", help = "Answer 429 to clients running more than this many programs a minute (0 disables)")]
+        rate_limit: u32,
+
+        /// Programs a client may run at once
+        #[arg(long, value_name = "PROGRAMS", default_value = "10", help = "Programs a client may run back to back before --rate-limit applies")]
+        rate_burst: u32,
+
+        /// Origins allowed to call the API from a browser
+        #[arg(long = "allow-origin", value_name = "ORIGIN", env = "PAANINI_ALLOWED_ORIGINS", value_delimiter = ',', help = "Only let browsers on this origin call the API (repeatable or comma-separated; any origin when unset)")]
+        allowed_origins: Vec<String>,
+
+        /// Most passes one loop may make in a served program
+        #[arg(long, value_name = "PASSES", default_value_t = interpreter::DEFAULT_LOOP_LIMIT, help = "Stop loops in /api/run programs after this many passes")]
+        max_iterations: usize,
     },
     
     /// Show example Paanini code
     #[command(about = "Display example Sanskrit code")]
-    Example,
+    Example {
+        /// Which bundled example to show
+        #[arg(help = "Name of the example to show (default: basics)")]
+        name: Option<String>,
+    },
+
+    /// Run two programs and diff their outputs
+    #[command(about = "Run two .paanini files and show a line-by-line diff of their output")]
+    Diff {
+        /// First program
+        #[arg(help = "Path to the first .paanini file")]
+        left: String,
+
+        /// Second program
+        #[arg(help = "Path to the second .paanini file")]
+        right: String,
+    },
+
+    /// Run Paanini test files
+    #[command(about = "Run every .paanini file in a directory (or one file) as a test")]
+    Test {
+        /// Directory of test files, or a single .paanini file
+        #[arg(help = "Directory to search for .paanini test files, or a single file")]
+        path: String,
+
+        /// Run `>>>` examples from function docstrings
+        #[arg(long, help = "Run the `>>>` examples in function docstrings and compare their output")]
+        doctests: bool,
+    },
+
+    /// Format a Paanini source file
+    #[command(about = "Format a .paanini file with 4-space indentation and consistent spacing")]
+    Fmt {
+        /// Path to .paanini source file
+        #[arg(help = "Path to the .paanini file to format")]
+        file: String,
+
+        /// Print instead of rewriting the file
+        #[arg(long, help = "Print the formatted source to stdout instead of writing it; exits non-zero if the file is not formatted")]
+        check: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    Stdout,
+    File,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if !color_enabled(cli.no_color, no_color_env, std::io::stdout().is_terminal()) {
+        colored::control::set_override(false);
+    }
 
     match cli.command {
         Some(Commands::Repl) => {
             start_repl();
         }
-        Some(Commands::Run { file, verbose }) => {
-            run_file(&file, verbose);
+        Some(Commands::Run { file, args, verbose, check_balanced, prelude, json, watch, time, echo_last, recursion_limit }) => {
+            let file = match file {
+                Some(file) => file,
+                None if !std::io::stdin().is_terminal() => STDIN_PATH.to_string(),
+                None => {
+                    eprintln!("{} No file given; pass a .paanini file, or `-` to read stdin", "त्रुटि:".bright_red().bold());
+                    std::process::exit(2);
+                }
+            };
+            let options = RunOptions { verbosity: verbose, check_balanced, prelude, time, echo_last, recursion_limit, args };
+            if json {
+                run_file_json(&file, &options);
+            }
+            if watch {
+                watch_file(&file, &options);
+            }
+            run_file(&file, &options);
+        }
+        Some(Commands::Check { file }) => {
+            check_file(&file);
+        }
+        Some(Commands::Build { file, output, release, force }) => {
+            build_file(&file, output.as_deref(), release, force);
+        }
+        Some(Commands::Transpile { file, emit, output }) => {
+            transpile_file(&file, emit, output.as_deref());
+        }
+        Some(Commands::Serve { port, cache_ttl, timeout, rate_limit, rate_burst, allowed_origins, max_iterations }) => {
+            server::start_server(server::ServerConfig {
+                port,
+                cache_ttl: cache_ttl.map(std::time::Duration::from_secs),
+                timeout: std::time::Duration::from_secs(timeout),
+                rate_limit,
+                rate_burst,
+                allowed_origins,
+                max_iterations,
+            })
+            .await;
         }
-        Some(Commands::Build { file, output, release }) => {
-            build_file(&file, output.as_deref(), release);
+        Some(Commands::Example { name }) => {
+            show_example(name.as_deref().unwrap_or(examples::DEFAULT));
         }
-        Some(Commands::Serve { port }) => {
-            server::start_server(port).await;
+        Some(Commands::Diff { left, right }) => {
+            diff_files(&left, &right);
         }
-        Some(Commands::Example) => {
-            show_example();
+        Some(Commands::Test { path, doctests }) => {
+            if doctests {
+                run_doctests(&path);
+            } else {
+                run_test_files(&path);
+            }
+        }
+        Some(Commands::Fmt { file, check }) => {
+            format_file(&file, check);
         }
         None => {
             // Default behavior: start REPL
@@ -107,45 +291,65 @@ async fn main() {
 
 fn start_repl() {
     print_welcome();
-    
+
     let mut interpreter = Interpreter::default();
-    let stdin = io::stdin();
+    interpreter.set_base_dir(".");
+    interpreter.set_args(&[]);
+    interpreter.set_file_access(true);
+    interpreter.set_env_access(true);
+    interpreter.set_interactive(true);
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("{} {}", "Input error:".red(), error);
+            return;
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(path);
+    }
 
     loop {
-        print!("{}", "paanini> ".bright_blue().bold());
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        match stdin.read_line(&mut input) {
-            Ok(0) => {
-                // EOF reached (e.g., piped input finished)
-                println!("\n{}", "धन्यवाद! Namaste! 🙏".bright_yellow());
-                break;
-            }
-            Ok(_) => {
+        match editor.readline(&"paanini> ".bright_blue().bold().to_string()) {
+            Ok(input) => {
                 let line = input.trim();
-                
+
                 if line.is_empty() {
                     continue;
                 }
-                
+                let _ = editor.add_history_entry(line);
+
                 if line == "exit" || line == "quit" || line == "बाहर" {
                     println!("{}", "धन्यवाद! Namaste! 🙏".bright_yellow());
                     break;
                 }
-                
+
                 if line == "help" || line == "सहायता" {
                     print_repl_help();
                     continue;
                 }
-                
+
+                if let Some(path) = line.strip_prefix("debug ") {
+                    debug_file(&mut interpreter, &mut editor, path.trim());
+                    continue;
+                }
+
                 if line == "clear" || line == "स्पष्ट" {
                     print!("\x1B[2J\x1B[1;1H"); // Clear screen
                     print_welcome();
                     continue;
                 }
 
-                let result = interpreter.run(line);
+                // A trailing ':' opens a block: keep reading until a blank line.
+                let source = if line.ends_with(':') {
+                    read_repl_block(&mut editor, line)
+                } else {
+                    line.to_string()
+                };
+
+                let result = interpreter.run(&source);
                 if !result.output.is_empty() {
                     print!("{}", result.output);
                 }
@@ -155,21 +359,198 @@ fn start_repl() {
                     }
                 }
             }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                // Ctrl-C / Ctrl-D (or piped input finished)
+                println!("\n{}", "धन्यवाद! Namaste! 🙏".bright_yellow());
+                break;
+            }
             Err(error) => {
                 eprintln!("{} {}", "Input error:".red(), error);
                 break;
             }
         }
     }
+
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
 }
 
-fn run_file(file_path: &str, verbose: bool) {
-    if !Path::new(file_path).exists() {
-        eprintln!("{} File not found: {}", "त्रुटि:".bright_red().bold(), file_path);
+/// Location of the persistent REPL history file (`~/.paanini_history`).
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".paanini_history"))
+}
+
+/// `debug <file>` in the REPL: run the file in the session one top-level
+/// statement at a time, showing the variables after each. Enter runs the
+/// next statement; `q` stops, leaving what has run so far defined.
+fn debug_file(interpreter: &mut Interpreter, editor: &mut DefaultEditor, path: &str) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), path, e);
+            return;
+        }
+    };
+    for step in interpreter.steps(&source) {
+        println!("{} {}", format!("Line {}:", step.line).bright_cyan(), step.text);
+        print!("{}", step.output);
+        for error in &step.errors {
+            println!("{} {}", "त्रुटि:".bright_red().bold(), error);
+        }
+        print!("{}", format_bindings(&step.vars).bright_black());
+        match editor.readline(&"(Enter: next, q: stop) ".bright_blue().to_string()) {
+            Ok(input) if input.trim() != "q" => {}
+            _ => break,
+        }
+    }
+}
+
+/// Variable bindings as `  name = value` lines, sorted by name.
+fn format_bindings(vars: &BTreeMap<String, String>) -> String {
+    vars.iter().map(|(name, value)| format!("  {} = {}\n", name, value)).collect()
+}
+
+/// Accumulate the indented body of a block started by `header` until a blank line or EOF.
+fn read_repl_block(editor: &mut DefaultEditor, header: &str) -> String {
+    let mut block = header.to_string();
+    while let Ok(next) = editor.readline(&"...... ".bright_blue().to_string()) {
+        if next.trim().is_empty() {
+            break;
+        }
+        let _ = editor.add_history_entry(next.as_str());
+        block.push('\n');
+        block.push_str(next.trim_end());
+    }
+    block
+}
+
+fn check_file(file_path: &str) {
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let errors = Interpreter::default().check(&source_code);
+    if errors.is_empty() {
+        println!("{} {}: no problems found", "✅".bright_green(), file_path);
+        return;
+    }
+    for error in &errors {
+        eprintln!("{} {}", "त्रुटि:".bright_red().bold(), error);
+    }
+    std::process::exit(1);
+}
+
+/// Whether to color output: not with `--no-color`, a non-empty `NO_COLOR`
+/// (https://no-color.org), or when stdout is piped or redirected.
+fn color_enabled(no_color_flag: bool, no_color_env: bool, stdout_is_terminal: bool) -> bool {
+    !no_color_flag && !no_color_env && stdout_is_terminal
+}
+
+/// `run --watch`: run the file, then again after each save, clearing the
+/// screen first when stdout is a terminal. Never returns; Ctrl-C ends it.
+fn watch_file(file_path: &str, options: &RunOptions) -> ! {
+    if file_path == STDIN_PATH {
+        eprintln!("{} --watch needs a file, not stdin", "त्रुटि:".bright_red().bold());
+        std::process::exit(2);
+    }
+    let clear = std::io::stdout().is_terminal();
+    let result = watch::watch(Path::new(file_path), || {
+        if clear {
+            print!("\x1b[2J\x1b[H");
+        }
+        println!("{} {} {}", "👀 Watching:".bright_blue().bold(), file_path, "(Ctrl-C to stop)".dimmed());
+        execute_file(file_path, options);
+    });
+    if let Err(e) = result {
+        eprintln!("{} Cannot watch {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+    }
+    std::process::exit(1);
+}
+
+/// The `run` path that reads the program from stdin.
+const STDIN_PATH: &str = "-";
+
+/// Read the program at `file_path`, or all of `stdin` for `-`.
+fn read_program(file_path: &str, mut stdin: impl std::io::Read) -> std::io::Result<String> {
+    if file_path != STDIN_PATH {
+        return fs::read_to_string(file_path);
+    }
+    let mut source_code = String::new();
+    stdin.read_to_string(&mut source_code)?;
+    Ok(source_code)
+}
+
+/// An interpreter for the program at `file_path`; one read from stdin
+/// resolves imports against the current directory, like the REPL.
+fn program_interpreter(file_path: &str, options: &RunOptions) -> Interpreter {
+    let mut interpreter = if options.prelude { Interpreter::with_prelude() } else { Interpreter::default() };
+    interpreter.set_recursion_limit(options.recursion_limit);
+    interpreter.set_args(&options.args);
+    interpreter.set_interactive(options.echo_last);
+    interpreter.set_file_access(true);
+    interpreter.set_env_access(true);
+    if file_path == STDIN_PATH {
+        interpreter.set_base_dir(".");
+    } else {
+        interpreter.set_source_path(Path::new(file_path));
+    }
+    interpreter
+}
+
+/// The `paanini run` flags that decide how a program is run.
+struct RunOptions {
+    /// `-v` count: 1 shows progress, 2 also traces each statement.
+    verbosity: u8,
+    check_balanced: bool,
+    prelude: bool,
+    /// Print the run time and function profile afterwards (`--time`).
+    time: bool,
+    /// Show the value of a trailing expression, as the REPL does (`--echo-last`).
+    echo_last: bool,
+    recursion_limit: usize,
+    /// Arguments after the file name, for `आदेशतर्क`.
+    args: Vec<String>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            verbosity: 0,
+            check_balanced: false,
+            prelude: false,
+            time: false,
+            echo_last: false,
+            recursion_limit: interpreter::DEFAULT_RECURSION_LIMIT,
+            args: Vec::new(),
+        }
+    }
+}
+
+fn run_file(file_path: &str, options: &RunOptions) {
+    if !execute_file(file_path, options) {
         std::process::exit(1);
     }
+}
+
+/// Run the program at `file_path`, printing its output and errors. Returns
+/// whether it ran without errors. With `--time`, the run time and function
+/// profile follow on stderr.
+fn execute_file(file_path: &str, options: &RunOptions) -> bool {
+    let verbose = options.verbosity > 0;
+    let from_stdin = file_path == STDIN_PATH;
+    if !from_stdin && !Path::new(file_path).exists() {
+        eprintln!("{} File not found: {}", "त्रुटि:".bright_red().bold(), file_path);
+        return false;
+    }
 
-    if !file_path.ends_with(".paanini") {
+    if !from_stdin && !file_path.ends_with(".paanini") {
         eprintln!("{} File should have .paanini extension", "चेतावनी:".bright_yellow().bold());
     }
 
@@ -177,136 +558,360 @@ fn run_file(file_path: &str, verbose: bool) {
         println!("{} {}", "▶️  Executing:".bright_green().bold(), file_path);
     }
 
-    match fs::read_to_string(file_path) {
+    match read_program(file_path, std::io::stdin()) {
         Ok(source_code) => {
             if verbose {
                 println!("{} {} lines", "📄 Source:".bright_blue(), source_code.lines().count());
             }
+
+            if options.check_balanced {
+                if let Err(e) = interpreter::check_balanced(&source_code) {
+                    eprintln!("{} {}", "त्रुटि:".bright_red().bold(), e);
+                    return false;
+                }
+            }
             
-            let mut interpreter = Interpreter::default();
+            let mut interpreter = program_interpreter(file_path, options);
+            interpreter.set_trace(options.verbosity > 1);
+            interpreter.set_profile(options.time);
+            let started = std::time::Instant::now();
             let result = interpreter.run(&source_code);
+            let elapsed = started.elapsed();
             
             if !result.output.is_empty() {
                 print!("{}", result.output);
             }
+
+            if !result.trace.is_empty() {
+                eprintln!("\n{}", "🔍 Trace:".bright_blue());
+                for entry in &result.trace {
+                    eprintln!("  {}", entry.dimmed());
+                }
+            }
+
+            if options.time {
+                eprintln!("\n{} {:.3?}", "⏱️  Time:".bright_blue(), elapsed);
+                eprint!("{}", format_profile(interpreter.profile()));
+            }
             
             if !result.errors.is_empty() {
                 for error in result.errors {
                     eprintln!("{} {}", "त्रुटि:".bright_red().bold(), error);
                 }
-                std::process::exit(1);
+                return false;
             }
             
             if verbose && result.errors.is_empty() {
                 println!("\n{}", "✅ Execution completed successfully".bright_green());
             }
+            true
         }
         Err(e) => {
             eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+            false
+        }
+    }
+}
+
+/// The `--time` table: one row per called function, slowest first.
+fn format_profile(profile: &BTreeMap<String, FunctionProfile>) -> String {
+    if profile.is_empty() {
+        return String::new();
+    }
+    let mut rows: Vec<_> = profile.iter().collect();
+    rows.sort_by_key(|(_, p)| std::cmp::Reverse(p.time));
+    // Names go last: Devanagari doesn't pad to a predictable width.
+    let mut table = format!("  {:>8}  {:>10}  {}\n", "calls", "time", "कार्य");
+    for (name, p) in rows {
+        table.push_str(&format!("  {:>8}  {:>10}  {}\n", p.calls, format!("{:.3?}", p.time), name));
+    }
+    table
+}
+
+/// `paanini run --json`: print a single `{output, errors, exit_code}` object
+/// and exit with that code.
+fn run_file_json(file_path: &str, options: &RunOptions) -> ! {
+    let failure = |error: InterpError| RunReport {
+        output: String::new(),
+        errors: vec![error],
+        exit_code: 1,
+    };
+    let report = match read_program(file_path, std::io::stdin()) {
+        Ok(source_code) => {
+            let balanced = if options.check_balanced { interpreter::check_balanced(&source_code) } else { Ok(()) };
+            match balanced {
+                Ok(()) => RunReport::from(program_interpreter(file_path, options).run(&source_code)),
+                Err(e) => failure(e),
+            }
+        }
+        Err(e) => failure(format!("Cannot read file {}: {}", file_path, e).into()),
+    };
+    println!("{}", serde_json::to_string(&report).expect("run report serializes"));
+    std::process::exit(report.exit_code);
+}
+
+fn diff_files(left_path: &str, right_path: &str) {
+    let mut outputs = Vec::new();
+    for path in [left_path, right_path] {
+        let source_code = match fs::read_to_string(path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), path, e);
+                std::process::exit(1);
+            }
+        };
+        let mut interpreter = Interpreter::default();
+        interpreter.set_source_path(Path::new(path));
+        interpreter.set_file_access(true);
+        interpreter.set_env_access(true);
+        let result = interpreter.run(&source_code);
+        for error in &result.errors {
+            eprintln!("{} {}: {}", "चेतावनी:".bright_yellow().bold(), path, error);
+        }
+        outputs.push(result.output);
+    }
+
+    match diff::output_diff(&outputs[0], &outputs[1]) {
+        None => {
+            println!("{} Outputs are identical", "✅".bright_green());
+        }
+        Some(text) => {
+            println!("{} {}", "---".bright_red(), left_path);
+            println!("{} {}", "+++".bright_green(), right_path);
+            for line in text.lines() {
+                if line.starts_with('-') {
+                    println!("{}", line.bright_red());
+                } else if line.starts_with('+') {
+                    println!("{}", line.bright_green());
+                } else {
+                    println!("{}", line);
+                }
+            }
             std::process::exit(1);
         }
     }
 }
 
-fn build_file(file_path: &str, output_name: Option<&str>, release: bool) {
-    if !Path::new(file_path).exists() {
-        eprintln!("{} File not found: {}", "त्रुटि:".bright_red().bold(), file_path);
+fn run_test_files(path: &str) {
+    let outcomes = match test_runner::run_tests(Path::new(path)) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!("{} Cannot read {}: {}", "त्रुटि:".bright_red().bold(), path, e);
+            std::process::exit(1);
+        }
+    };
+    if outcomes.is_empty() {
+        eprintln!("{} no .paanini files found in {}", "त्रुटि:".bright_red().bold(), path);
         std::process::exit(1);
     }
 
-    let output = output_name.unwrap_or("output");
-    
-    println!("{} {}", "🔧 Building:".bright_green().bold(), file_path);
+    let mut failed = 0;
+    for outcome in &outcomes {
+        if outcome.passed() {
+            println!("{} {}", "✅".bright_green(), outcome.path.display());
+            continue;
+        }
+        failed += 1;
+        println!("{} {}", "❌".bright_red(), outcome.path.display());
+        for error in &outcome.errors {
+            println!("   {} {}", "त्रुटि:".bright_red().bold(), error);
+        }
+    }
+    println!("\n{} passed, {} failed", outcomes.len() - failed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
 
-    match fs::read_to_string(file_path) {
-        Ok(source_code) => {
-            match transpiler::transpile_to_rust(&source_code) {
-                Ok(rust_code) => {
-                    let rust_file = format!("{}.rs", output);
-                    if let Err(e) = fs::write(&rust_file, rust_code) {
-                        eprintln!("{} Cannot write Rust file: {}", "त्रुटि:".bright_red().bold(), e);
-                        std::process::exit(1);
-                    }
-                    
-                    println!("{} Generated: {}", "✅".bright_green(), rust_file);
-                    
-                    // Compile with rustc
-                    let mut cmd = Command::new("rustc");
-                    cmd.arg(&rust_file).arg("-o").arg(output);
-                    
-                    if release {
-                        cmd.arg("-O");
-                        println!("{} Building in release mode...", "🚀".bright_blue());
-                    }
-                    
-                    match cmd.output() {
-                        Ok(output_result) => {
-                            if output_result.status.success() {
-                                println!("{} Built executable: {}", "🎉".bright_green(), output);
-                                // Clean up rust file
-                                let _ = fs::remove_file(&rust_file);
-                            } else {
-                                eprintln!("{} Build failed:", "त्रुटि:".bright_red().bold());
-                                eprintln!("{}", String::from_utf8_lossy(&output_result.stderr));
-                                std::process::exit(1);
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("{} Failed to run rustc: {}", "त्रुटि:".bright_red().bold(), e);
-                            eprintln!("Make sure Rust is installed and rustc is in your PATH");
-                            std::process::exit(1);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("{} Transpilation failed: {}", "त्रुटि:".bright_red().bold(), e);
-                    std::process::exit(1);
-                }
+fn run_doctests(file_path: &str) {
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let outcomes = match doctest::run_doctests(&source_code) {
+        Ok(outcomes) => outcomes,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{} {}", "त्रुटि:".bright_red().bold(), error);
             }
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = 0;
+    for outcome in &outcomes {
+        let test = &outcome.test;
+        if outcome.passed() {
+            println!("{} {}: >>> {}", "✅".bright_green(), test.function, test.code);
+            continue;
+        }
+        failed += 1;
+        println!("{} {}: >>> {}", "❌".bright_red(), test.function, test.code);
+        println!("   {} {}", "expected:".bright_blue(), test.expected);
+        println!("   {} {}", "got:".bright_blue(), outcome.output.trim_end_matches('\n'));
+        for error in &outcome.errors {
+            println!("   {} {}", "त्रुटि:".bright_red().bold(), error);
+        }
+    }
+    println!("\n{} passed, {} failed", outcomes.len() - failed, failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn format_file(file_path: &str, check: bool) {
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let formatted = formatter::format_source(&source_code);
+    if check {
+        print!("{}", formatted);
+        if formatted != source_code {
+            std::process::exit(1);
         }
+        return;
+    }
+
+    if formatted == source_code {
+        println!("{} {} already formatted", "✅".bright_green(), file_path);
+    } else if let Err(e) = fs::write(file_path, &formatted) {
+        eprintln!("{} Cannot write {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+        std::process::exit(1);
+    } else {
+        println!("{} Formatted {}", "✨".bright_green(), file_path);
+    }
+}
+
+fn transpile_file(file_path: &str, emit: Option<Emit>, output: Option<&str>) {
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(code) => code,
         Err(e) => {
             eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
             std::process::exit(1);
         }
+    };
+
+    let rust_code = match transpiler::transpile_to_rust(&source_code) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{} Transpilation failed: {}", "त्रुटि:".bright_red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let emit = emit.unwrap_or(if output.is_some() { Emit::File } else { Emit::Stdout });
+    match emit {
+        Emit::Stdout => print!("{}", rust_code),
+        Emit::File => {
+            let rust_file = match output {
+                Some(path) => path.to_string(),
+                None => Path::new(file_path).with_extension("rs").to_string_lossy().into_owned(),
+            };
+            if let Err(e) = fs::write(&rust_file, rust_code) {
+                eprintln!("{} Cannot write Rust file: {}", "त्रुटि:".bright_red().bold(), e);
+                std::process::exit(1);
+            }
+            println!("{} Generated: {}", "✅".bright_green(), rust_file);
+        }
     }
 }
 
-fn show_example() {
-    println!("{}", "📚 Paanini Sanskrit Programming Examples".bright_blue().bold());
-    println!();
+fn build_file(file_path: &str, output_name: Option<&str>, release: bool, force: bool) {
+    if !Path::new(file_path).exists() {
+        eprintln!("{} File not found: {}", "त्रुटि:".bright_red().bold(), file_path);
+        std::process::exit(1);
+    }
+
+    let output = output_name.unwrap_or("output");
     
-    let example_code = r#"!! नमस्ते विश्व - Hello World
-दर्श("नमस्ते विश्व")
+    println!("{} {}", "🔧 Building:".bright_green().bold(), file_path);
 
-!! चर और गणना - Variables and Math  
-x = 5
-y = 10
-योग = x + y
-दर्श("योग:", योग)
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(source_code) => source_code,
+        Err(e) => {
+            eprintln!("{} Cannot read file {}: {}", "त्रुटि:".bright_red().bold(), file_path, e);
+            std::process::exit(1);
+        }
+    };
+    match build_source(&source_code, output, release, force) {
+        Ok(BuildStatus::Cached) => {
+            println!("{} Up to date (cached): {}", "♻️".bright_green(), output);
+        }
+        Ok(BuildStatus::Built) => {
+            println!("{} Built executable: {}", "🎉".bright_green(), output);
+        }
+        Err(e) => {
+            eprintln!("{} {}", "त्रुटि:".bright_red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
 
-!! शर्त - Conditionals
-यदि x < y:
-    दर्श("x छोटा है")
-अन्यथा:
-    दर्श("x बड़ा है")
+#[derive(Debug, PartialEq)]
+enum BuildStatus {
+    Built,
+    /// `output` was already built from the same source, so rustc was skipped.
+    Cached,
+}
 
-!! लूप - Loops
-यावत् x <= y:
-    दर्श(x)
-    x = x + 1
+/// Transpile `source_code` and compile it to the executable `output`,
+/// unless the build cache shows `output` is already up to date and `force`
+/// is unset.
+fn build_source(source_code: &str, output: &str, release: bool, force: bool) -> Result<BuildStatus, String> {
+    let key = build_cache::cache_key(source_code, release);
+    if !force && build_cache::is_fresh(Path::new(output), &key) {
+        return Ok(BuildStatus::Cached);
+    }
 
-!! फंक्शन - Functions
-कार्य greet(नाम):
-    दर्श("नमस्ते", नाम)
+    let rust_code = transpiler::transpile_to_rust(source_code).map_err(|e| format!("Transpilation failed: {}", e))?;
+    let rust_file = format!("{}.rs", output);
+    fs::write(&rust_file, rust_code).map_err(|e| format!("Cannot write Rust file: {}", e))?;
+    println!("{} Generated: {}", "✅".bright_green(), rust_file);
 
-greet("भारत")"#;
+    // Compile with rustc
+    let mut cmd = Command::new("rustc");
+    cmd.arg(&rust_file).arg("-o").arg(output);
+    if release {
+        cmd.arg("-O");
+        println!("{} Building in release mode...", "🚀".bright_blue());
+    }
+    let output_result = cmd
+        .output()
+        .map_err(|e| format!("Failed to run rustc: {}\nMake sure Rust is installed and rustc is in your PATH", e))?;
+    if !output_result.status.success() {
+        return Err(format!("Build failed:\n{}", String::from_utf8_lossy(&output_result.stderr)));
+    }
+    // Clean up rust file
+    let _ = fs::remove_file(&rust_file);
+    if let Err(e) = build_cache::record(Path::new(output), &key) {
+        eprintln!("{} Cannot update build cache: {}", "⚠️".bright_yellow(), e);
+    }
+    Ok(BuildStatus::Built)
+}
 
+fn show_example(name: &str) {
+    let Some(example_code) = examples::get(name) else {
+        let names: Vec<String> = examples::all().into_iter().map(|(name, _)| name).collect();
+        eprintln!("{} Unknown example '{}'; available: {}", "त्रुटि:".bright_red().bold(), name, names.join(", "));
+        std::process::exit(1);
+    };
+    println!("{}", "📚 Paanini Sanskrit Programming Examples".bright_blue().bold());
+    println!();
     println!("{}", example_code.bright_white());
     println!();
     println!("{}", "💡 Usage:".bright_yellow().bold());
-    println!("  {} Save the above code as 'hello.paanini'", "1.".bright_cyan());
-    println!("  {} Run with: paanini run hello.paanini", "2.".bright_cyan());
-    println!("  {} Build with: paanini build hello.paanini", "3.".bright_cyan());
+    println!("  {} Save the above code as '{}.paanini'", "1.".bright_cyan(), name);
+    println!("  {} Run with: paanini run {}.paanini", "2.".bright_cyan(), name);
+    println!("  {} Build with: paanini build {}.paanini", "3.".bright_cyan(), name);
 }
 
 fn print_welcome() {
@@ -321,6 +926,8 @@ fn print_repl_help() {
     println!("  {} {} - Exit REPL", "exit/quit/बाहर".bright_cyan(), "".bright_white());
     println!("  {} {} - Show this help", "help/सहायता".bright_cyan(), "".bright_white());
     println!("  {} {} - Clear screen", "clear/स्पष्ट".bright_cyan(), "".bright_white());
+    println!("  {} - Step through a file, showing variables after each statement", "debug <file>".bright_cyan());
+    println!("  {} - Lines ending in ':' start a block; finish it with an empty line", "...... ".bright_cyan());
     println!();
     println!("{}", "🎯 Sanskrit Keywords:".bright_blue().bold());
     println!("  {} {} - Print/Display", "दर्श()".bright_green(), "darsh()".bright_white());
@@ -332,3 +939,101 @@ fn print_repl_help() {
     println!("  {} {} - Comments", "!!".bright_green(), "".bright_white());
     println!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_can_be_disabled() {
+        assert!(color_enabled(false, false, true));
+        assert!(!color_enabled(true, false, true));
+        assert!(!color_enabled(false, true, true));
+        assert!(!color_enabled(false, false, false));
+
+        colored::control::set_override(false);
+        let text = format!("{} {}", "त्रुटि:".bright_red().bold(), "✅ done".bright_green());
+        colored::control::unset_override();
+        assert!(!text.contains('\x1b'), "{:?}", text);
+        assert_eq!(text, "त्रुटि: ✅ done");
+    }
+
+    #[test]
+    fn test_run_dash_reads_the_program_from_stdin() {
+        let piped = "x = 2\nयदि x > 1:\n    दर्श(x * 3)\n".as_bytes();
+        let source = read_program(STDIN_PATH, piped).unwrap();
+        let result = program_interpreter(STDIN_PATH, &RunOptions::default()).run(&source);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "6\n");
+
+        // A real path ignores stdin.
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("prog.paanini");
+        fs::write(&file, "दर्श(1)\n").unwrap();
+        assert_eq!(read_program(file.to_str().unwrap(), piped).unwrap(), "दर्श(1)\n");
+    }
+
+    #[test]
+    fn test_arguments_after_the_file_reach_the_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("args.paanini");
+        fs::write(&file, "दर्श(आदेशतर्क)\nपरिभ्रमण a in आदेशतर्क:\n    दर्श(a)\n").unwrap();
+        let file = file.to_str().unwrap();
+        let cli = Cli::try_parse_from(["paanini", "run", file, "एक", "--time", "3"]).unwrap();
+        let Some(Commands::Run { args, time, .. }) = cli.command else { panic!("not a run command") };
+        // Flags after the first argument belong to the program.
+        assert!(!time);
+        let options = RunOptions { args, ..RunOptions::default() };
+        let result = program_interpreter(file, &options).run(&fs::read_to_string(file).unwrap());
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "[एक, --time, 3]\nएक\n--time\n3\n");
+
+        let result = program_interpreter(file, &RunOptions::default()).run("दर्श(आदेशतर्क)");
+        assert_eq!(result.output, "[]\n");
+    }
+
+    #[test]
+    fn test_echo_last_prints_the_final_expression() {
+        let source = "कार्य वर्ग(n):\n    प्रतिफल n * n\nदर्श(\"आरम्भः\")\nवर्ग(7) + 1\n!! अन्ते टिप्पणी\n";
+        let options = RunOptions { echo_last: true, ..RunOptions::default() };
+        let result = program_interpreter(STDIN_PATH, &options).run(source);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.output, "आरम्भः\n50\n");
+
+        // Without the flag a bare expression is still an unknown command.
+        let result = program_interpreter(STDIN_PATH, &RunOptions::default()).run(source);
+        assert_eq!(result.output, "आरम्भः\n");
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_profile_table_lists_slowest_first() {
+        let profile = BTreeMap::from([
+            ("क".to_string(), FunctionProfile { calls: 3, time: std::time::Duration::from_millis(1) }),
+            ("ख".to_string(), FunctionProfile { calls: 12, time: std::time::Duration::from_millis(5) }),
+        ]);
+        assert_eq!(
+            format_profile(&profile),
+            "     calls        time  कार्य\n        12     5.000ms  ख\n         3     1.000ms  क\n"
+        );
+        assert_eq!(format_profile(&BTreeMap::new()), "");
+    }
+
+    #[test]
+    fn test_unchanged_rebuild_is_a_cache_hit() {
+        if Command::new("rustc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("app");
+        let output = output.to_str().unwrap();
+        let source = "दर्श(\"{}\", 1)\n";
+
+        assert_eq!(build_source(source, output, false, false), Ok(BuildStatus::Built));
+        assert_eq!(build_source(source, output, false, false), Ok(BuildStatus::Cached));
+        assert_eq!(build_source(source, output, false, true), Ok(BuildStatus::Built));
+        assert_eq!(build_source("दर्श(\"{}\", 2)\n", output, false, false), Ok(BuildStatus::Built));
+        let run = Command::new(output).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "2\n");
+    }
+}
@@ -1,49 +1,298 @@
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, StatusCode},
-    response::{Json, Response},
-    routing::{get, post},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path, Request, State,
+    },
+    http::{header, HeaderName, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{atomic::AtomicBool, atomic::Ordering, Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
 
 use rust_embed::RustEmbed;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::interpreter::{InterpError, Interpreter, NoInput};
 
-use crate::interpreter::Interpreter;
+/// Settings for `paanini serve`.
+pub struct ServerConfig {
+    pub port: u16,
+    /// Cache `/api/run` responses by exact source for this long; off when `None`.
+    pub cache_ttl: Option<Duration>,
+    /// Longest a single `/api/run` program may execute before it is cancelled.
+    pub timeout: Duration,
+    /// Programs each client may run per minute; off when 0.
+    pub rate_limit: u32,
+    /// Programs a client may run at once before the per-minute rate applies.
+    pub rate_burst: u32,
+    /// Origins browsers may call the API from; any origin when empty.
+    pub allowed_origins: Vec<String>,
+    /// Most passes one loop in a served program may make.
+    pub max_iterations: usize,
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub interpreter: Interpreter,
+    pub cache: Option<Arc<RunCache>>,
+    pub sessions: Arc<SessionStore>,
+    pub timeout: Duration,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub allowed_origins: Vec<String>,
+}
+
+/// Per-client token buckets for the routes that run code: each client may
+/// run `burst` programs at once, and regains `per_minute` a minute after
+/// that. Only the `MAX_CLIENTS` most recently seen clients are tracked.
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, (Instant, f64)>>,
+}
+
+impl RateLimiter {
+    const MAX_CLIENTS: usize = 4096;
+
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        RateLimiter {
+            per_second: f64::from(per_minute) / 60.0,
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take `cost` tokens from `client`'s bucket; false, taking none, when
+    /// it holds fewer.
+    fn allow(&self, client: IpAddr, cost: usize) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        let refill = |(last, tokens): &(Instant, f64)| (tokens + last.elapsed().as_secs_f64() * self.per_second).min(self.burst);
+        if buckets.len() >= Self::MAX_CLIENTS && !buckets.contains_key(&client) {
+            // A full bucket is the same as no bucket.
+            buckets.retain(|_, bucket| refill(bucket) < self.burst);
+            // Otherwise forget the client seen longest ago.
+            if buckets.len() >= Self::MAX_CLIENTS {
+                let oldest = buckets.iter().min_by_key(|(_, (last, _))| *last).map(|(ip, _)| *ip);
+                if let Some(ip) = oldest {
+                    buckets.remove(&ip);
+                }
+            }
+        }
+        let tokens = buckets.get(&client).map_or(self.burst, refill);
+        if tokens < cost as f64 {
+            return false;
+        }
+        buckets.insert(client, (Instant::now(), tokens - cost as f64));
+        true
+    }
+}
+
+/// Interpreters kept alive between requests that share a `session_id`. At
+/// most `MAX_SESSIONS` are kept; past that, starting a session drops the
+/// one idle longest.
+pub struct SessionStore {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, (Instant, SharedInterpreter)>>,
+}
+
+type SharedInterpreter = Arc<Mutex<Interpreter>>;
+
+impl SessionStore {
+    /// Sessions idle for longer than this are dropped.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(30 * 60);
+    const MAX_SESSIONS: usize = 1024;
+
+    pub fn new(ttl: Duration) -> Self {
+        SessionStore {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drop the sessions that have expired, and those whose interpreter a
+    /// panicking run left poisoned, so their ids start over.
+    fn drop_stale(&self, sessions: &mut HashMap<String, (Instant, SharedInterpreter)>) {
+        sessions.retain(|_, (last_used, interpreter)| last_used.elapsed() < self.ttl && !interpreter.is_poisoned());
+    }
+
+    /// The interpreter for `id`, if that session exists and hasn't expired.
+    fn get(&self, id: &str) -> Option<SharedInterpreter> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.drop_stale(&mut sessions);
+        let entry = sessions.get_mut(id)?;
+        entry.0 = Instant::now();
+        Some(entry.1.clone())
+    }
+
+    /// The interpreter for `id`, starting from `template` if the session is new or expired.
+    fn get_or_create(&self, id: &str, template: &Interpreter) -> SharedInterpreter {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.drop_stale(&mut sessions);
+        if sessions.len() >= Self::MAX_SESSIONS && !sessions.contains_key(id) {
+            let oldest = sessions.iter().min_by_key(|(_, (last_used, _))| *last_used).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        let entry = sessions
+            .entry(id.to_string())
+            .or_insert_with(|| (Instant::now(), Arc::new(Mutex::new(template.clone()))));
+        entry.0 = Instant::now();
+        entry.1.clone()
+    }
+
+    fn remove(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().remove(id).is_some()
+    }
+}
+
+/// Response header telling whether `/api/run` was answered from the cache.
+const CACHE_HEADER: HeaderName = HeaderName::from_static("x-paanini-cache");
+
+/// Longest one `विश्राम` may pause a request's run. Every run is cancelled
+/// at `state.timeout` anyway; this keeps a single call from spending that
+/// whole budget, and the blocking thread running it, on waiting.
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// Memoized `/api/run` responses keyed by the submitted code.
+pub struct RunCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, RunResponse)>>,
+}
+
+impl RunCache {
+    const MAX_ENTRIES: usize = 1024;
+
+    pub fn new(ttl: Duration) -> Self {
+        RunCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, code: &str) -> Option<RunResponse> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(code)
+            .filter(|(stored, _)| stored.elapsed() < self.ttl)
+            .map(|(_, response)| response.clone())
+    }
+
+    fn insert(&self, code: &str, response: &RunResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= Self::MAX_ENTRIES {
+            entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+            if entries.len() >= Self::MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert(code.to_string(), (Instant::now(), response.clone()));
+    }
 }
 
 #[derive(Deserialize)]
 pub struct RunRequest {
     pub code: String,
+    /// Keep variables and functions between requests that send the same id.
+    #[serde(default)]
+    pub session_id: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct RunResponse {
     pub output: String,
     pub errors: Vec<String>,
+    /// The program read the clock or drew unseeded random numbers, so it
+    /// isn't cached.
+    #[serde(skip)]
+    pub nondeterministic: bool,
+}
+
+/// One program in a `/api/run/batch` request.
+#[derive(Deserialize)]
+pub struct BatchItem {
+    /// Echoed back with the result, e.g. a student or exercise id.
+    pub id: serde_json::Value,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub id: serde_json::Value,
+    pub output: String,
+    pub errors: Vec<String>,
+}
+
+/// Most programs one `/api/run/batch` request may hold.
+const MAX_BATCH: usize = 100;
+
+#[derive(Serialize)]
+pub struct CheckResponse {
+    pub errors: Vec<InterpError>,
+}
+
+#[derive(Serialize)]
+pub struct TranspileResponse {
+    pub rust: String,
+    pub errors: Vec<String>,
+}
+
+/// Messages sent over `/api/stream`: an `output` event per `दर्श`, then one `done`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StreamEvent {
+    Output { data: String },
+    Done { errors: Vec<String> },
+}
+
+#[derive(Deserialize)]
+pub struct ExpectRequest {
+    pub code: String,
+    pub expected: String,
+}
+
+#[derive(Serialize)]
+pub struct ExpectResponse {
+    pub passed: bool,
+    pub output: String,
+    pub errors: Vec<String>,
+    /// Line diff from expected (`-`) to actual (`+`); absent when they match.
+    pub diff: Option<String>,
 }
 
 #[derive(RustEmbed)]
 #[folder = "static"]
 struct StaticAssets;
 
-pub async fn start_server(port: u16) {
+pub async fn start_server(config: ServerConfig) {
+    let port = config.port;
+    let mut interpreter = Interpreter::default();
+    interpreter.set_input_provider(Arc::new(NoInput));
+    interpreter.set_max_sleep(MAX_SLEEP);
+    interpreter.set_args(&[]);
+    interpreter.set_loop_limit(config.max_iterations);
+    // JSON log lines; `RUST_LOG` picks what is logged, `info` by default.
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    let _ = tracing_subscriber::fmt().json().with_env_filter(filter).try_init();
     let app_state = AppState {
-        interpreter: Interpreter::default(),
+        interpreter,
+        cache: config.cache_ttl.map(|ttl| Arc::new(RunCache::new(ttl))),
+        sessions: Arc::new(SessionStore::new(SessionStore::DEFAULT_TTL)),
+        timeout: config.timeout,
+        rate_limiter: (config.rate_limit > 0).then(|| Arc::new(RateLimiter::new(config.rate_limit, config.rate_burst))),
+        allowed_origins: config.allowed_origins,
     };
 
-    let app = Router::new()
-        .route("/api/run", post(run_code))
-        .route("/health", get(health_check))
-        .route("/", get(static_index))
-        .route("/*path", get(static_handler))
-        .with_state(Arc::new(app_state));
+    let app = app(app_state);
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
         .await
@@ -55,20 +304,390 @@ pub async fn start_server(port: u16) {
     );
     println!("📝 Open your browser to start coding in Sanskrit!");
 
-    axum::serve(listener, app).await.unwrap();
+    // Client addresses key the rate limiter.
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
+}
+
+pub fn app(app_state: AppState) -> Router {
+    let cors = cors_layer(&app_state.allowed_origins);
+    let state = Arc::new(app_state);
+    Router::new()
+        .route(
+            "/api/run",
+            post(run_code)
+                .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+                .layer(middleware::from_fn(log_run)),
+        )
+        .route("/api/run/batch", post(run_batch))
+        .route(
+            "/api/expect",
+            post(expect_output).layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route("/api/transpile", post(transpile_code))
+        .route("/api/check", post(check_code))
+        .route(
+            "/api/stream",
+            get(stream_code).layer(middleware::from_fn_with_state(state.clone(), rate_limit)),
+        )
+        .route("/api/session/:id", delete(reset_session))
+        .route("/api/session/:id/state", get(export_session).put(import_session))
+        .route("/api/examples", get(list_examples))
+        .route("/api/examples/:name", get(show_example))
+        .route("/api/info", get(info))
+        .route("/health", get(health_check))
+        .route("/", get(static_index))
+        .route("/*path", get(static_handler))
+        .layer(cors)
+        .with_state(state)
+}
+
+/// Let browsers on `origins` call the API, or on any origin when empty.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let layer = CorsLayer::new().allow_methods(Any).allow_headers(Any);
+    if origins.is_empty() {
+        return layer.allow_origin(Any);
+    }
+    layer.allow_origin(AllowOrigin::list(origins.iter().filter_map(|origin| origin.parse().ok())))
+}
+
+/// Largest `/api/run` body accepted, the same as axum's `Json` default.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+/// Log each `/api/run` with the code's length, how long it took, how many
+/// errors it reported and the response status.
+async fn log_run(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_BODY).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let code_length = serde_json::from_slice::<RunRequest>(&body).ok().map(|run| run.code.chars().count());
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let errors = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| json["errors"].as_array().map(Vec::len));
+    tracing::info!(
+        code_length,
+        duration_ms = started.elapsed().as_millis() as u64,
+        errors,
+        status = parts.status.as_u16(),
+        "run"
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Answer 429 once the client has used up its allowance for running code.
+async fn rate_limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let client = request.extensions().get::<ConnectInfo<SocketAddr>>().cloned();
+    if let Some(response) = charge(&state, client, 1) {
+        return response;
+    }
+    next.run(request).await
+}
+
+/// Take `programs` runs from the client's allowance, returning the 429
+/// response to send instead when it hasn't that many left. Requests without
+/// a client address share one allowance.
+fn charge(state: &AppState, client: Option<ConnectInfo<SocketAddr>>, programs: usize) -> Option<Response> {
+    let limiter = state.rate_limiter.as_ref()?;
+    let client = client.map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(addr)| addr.ip());
+    if limiter.allow(client, programs) {
+        return None;
+    }
+    let response = RunResponse {
+        output: String::new(),
+        errors: vec!["त्रुटिः: अत्यधिकाः अनुरोधाः; किञ्चित् प्रतीक्षस्व".to_string()],
+        nondeterministic: false,
+    };
+    Some((StatusCode::TOO_MANY_REQUESTS, Json(response)).into_response())
+}
+
+fn timeout_error(timeout: Duration) -> String {
+    format!("त्रुटिः: समयसीमा अतिक्रान्ता ({} ms)", timeout.as_millis())
+}
+
+/// Run `code` on a blocking thread, in the session's interpreter when given.
+/// Past `state.timeout` the run is cancelled and the error comes back with
+/// a 408 status.
+async fn execute_with_timeout(
+    state: &AppState,
+    code: &str,
+    session: Option<SharedInterpreter>,
+) -> Result<RunResponse, (StatusCode, RunResponse)> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    let template = state.interpreter.clone();
+    let code = code.to_string();
+    let task = tokio::task::spawn_blocking(move || {
+        let run = |interpreter: &mut Interpreter| {
+            interpreter.set_cancel_flag(flag);
+            interpreter.run(&code)
+        };
+        match session {
+            // A session poisoned after we looked it up is dropped at the next lookup.
+            Some(session) => run(&mut session.lock().unwrap_or_else(PoisonError::into_inner)),
+            None => run(&mut template.clone()),
+        }
+    });
+
+    match tokio::time::timeout(state.timeout, task).await {
+        Ok(Ok(result)) => Ok(RunResponse {
+            output: result.output,
+            errors: result.errors,
+            nondeterministic: result.nondeterministic,
+        }),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            RunResponse {
+                output: String::new(),
+                errors: vec![format!("interpreter task failed: {}", e)],
+                nondeterministic: false,
+            },
+        )),
+        Err(_) => {
+            cancel.store(true, Ordering::Relaxed);
+            Err((
+                StatusCode::REQUEST_TIMEOUT,
+                RunResponse {
+                    output: String::new(),
+                    errors: vec![timeout_error(state.timeout)],
+                    nondeterministic: false,
+                },
+            ))
+        }
+    }
 }
 
 async fn run_code(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<RunRequest>,
-) -> Result<Json<RunResponse>, StatusCode> {
+) -> Response {
+    // Session runs depend on earlier requests, so they bypass the cache.
+    if let Some(id) = &payload.session_id {
+        let session = state.sessions.get_or_create(id, &state.interpreter);
+        return match execute_with_timeout(&state, &payload.code, Some(session)).await {
+            Ok(response) => Json(response).into_response(),
+            Err((status, response)) => (status, Json(response)).into_response(),
+        };
+    }
+    if let Some(cached) = state.cache.as_ref().and_then(|cache| cache.get(&payload.code)) {
+        return ([(CACHE_HEADER, "hit")], Json(cached)).into_response();
+    }
+    let response = match execute_with_timeout(&state, &payload.code, None).await {
+        Ok(response) => response,
+        Err((status, response)) => return (status, Json(response)).into_response(),
+    };
+    match &state.cache {
+        Some(cache) => {
+            // Clock or random output would differ on the next run, so don't
+            // replay it.
+            if !response.nondeterministic {
+                cache.insert(&payload.code, &response);
+            }
+            ([(CACHE_HEADER, "miss")], Json(response)).into_response()
+        }
+        None => Json(response).into_response(),
+    }
+}
+
+/// Run each program in its own interpreter, one after another, each with
+/// the usual timeout. Results come back in the order the programs were sent.
+async fn run_batch(
+    State(state): State<Arc<AppState>>,
+    client: Option<ConnectInfo<SocketAddr>>,
+    Json(items): Json<Vec<BatchItem>>,
+) -> Response {
+    if items.len() > MAX_BATCH {
+        let error = format!("a batch may hold at most {} programs", MAX_BATCH);
+        return (StatusCode::PAYLOAD_TOO_LARGE, Json(serde_json::json!({ "error": error }))).into_response();
+    }
+    // Each program costs as much as a request to `/api/run`.
+    if let Some(response) = charge(&state, client, items.len()) {
+        return response;
+    }
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        let run = match execute_with_timeout(&state, &item.code, None).await {
+            Ok(run) | Err((_, run)) => run,
+        };
+        results.push(BatchResult {
+            id: item.id,
+            output: run.output,
+            errors: run.errors,
+        });
+    }
+    Json(results).into_response()
+}
+
+async fn stream_code(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| stream_session(state, socket))
+}
+
+/// Output lines buffered between a streamed run and its socket; a run that
+/// prints faster than the client reads waits for it.
+const STREAM_BUFFER: usize = 64;
+
+/// Run the program sent as the first `{code}` message, forwarding output as it
+/// is printed. Closing the socket or running past `state.timeout` cancels
+/// the run.
+async fn stream_session(state: Arc<AppState>, mut socket: WebSocket) {
+    let code = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<RunRequest>(&text) {
+            Ok(request) => request.code,
+            Err(e) => {
+                let done = StreamEvent::Done {
+                    errors: vec![format!("invalid request: {}", e)],
+                };
+                let _ = send_event(&mut socket, &done).await;
+                return;
+            }
+        },
+        _ => return,
+    };
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(STREAM_BUFFER);
     let mut interpreter = state.interpreter.clone();
-    let result = interpreter.run(&payload.code);
+    interpreter.set_cancel_flag(cancel.clone());
+    let stop = cancel.clone();
+    // The sender lives in the sink, so the channel closes once the run finishes.
+    let run = tokio::task::spawn_blocking(move || {
+        interpreter.run_with_sink(
+            &code,
+            Arc::new(move |text: &str| {
+                // The receiver is dropped once the socket closes or the run
+                // times out.
+                if tx.blocking_send(text.to_string()).is_err() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }),
+        )
+    });
 
-    Ok(Json(RunResponse {
-        output: result.output,
-        errors: result.errors,
-    }))
+    let deadline = tokio::time::Instant::now() + state.timeout;
+    loop {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(data)) => {
+                if send_event(&mut socket, &StreamEvent::Output { data }).await.is_err() {
+                    cancel.store(true, Ordering::Relaxed);
+                    return;
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {
+                cancel.store(true, Ordering::Relaxed);
+                let errors = vec![timeout_error(state.timeout)];
+                let _ = send_event(&mut socket, &StreamEvent::Done { errors }).await;
+                return;
+            }
+        }
+    }
+    let errors = match run.await {
+        Ok(result) => result.errors,
+        Err(e) => vec![format!("interpreter task failed: {}", e)],
+    };
+    let _ = send_event(&mut socket, &StreamEvent::Done { errors }).await;
+}
+
+async fn send_event(socket: &mut WebSocket, event: &StreamEvent) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).expect("stream events serialize");
+    socket.send(Message::Text(text)).await
+}
+
+/// The session's variables, as `Interpreter::export_state` writes them; 404
+/// for a session that doesn't exist, which this doesn't start.
+async fn export_session(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Response {
+    let Some(session) = state.sessions.get(&id) else {
+        let error = format!("no session {}", id);
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": error }))).into_response();
+    };
+    let exported = session.lock().unwrap_or_else(PoisonError::into_inner).export_state();
+    let exported: serde_json::Value = serde_json::from_str(&exported).expect("exported state is JSON");
+    Json(exported).into_response()
+}
+
+/// Replace the session's variables with a state from `export_session`.
+async fn import_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(saved): Json<serde_json::Value>,
+) -> Response {
+    let session = state.sessions.get_or_create(&id, &state.interpreter);
+    let result = session.lock().unwrap_or_else(PoisonError::into_inner).import_state(&saved.to_string());
+    match result {
+        Ok(()) => Json(serde_json::json!({ "restored": true })).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
+
+/// Every bundled sample program, as `[{"name", "code"}]`.
+async fn list_examples() -> Json<serde_json::Value> {
+    let examples: Vec<_> = crate::examples::all()
+        .into_iter()
+        .map(|(name, code)| serde_json::json!({ "name": name, "code": code }))
+        .collect();
+    Json(serde_json::json!(examples))
+}
+
+async fn show_example(Path(name): Path<String>) -> Response {
+    match crate::examples::get(&name) {
+        Some(code) => Json(serde_json::json!({ "name": name, "code": code })).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": format!("no example named {}", name) }))).into_response(),
+    }
+}
+
+async fn reset_session(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "reset": state.sessions.remove(&id) }))
+}
+
+async fn check_code(State(state): State<Arc<AppState>>, Json(payload): Json<RunRequest>) -> Json<CheckResponse> {
+    Json(CheckResponse {
+        errors: state.interpreter.check(&payload.code),
+    })
+}
+
+async fn transpile_code(Json(payload): Json<RunRequest>) -> Json<TranspileResponse> {
+    Json(match crate::transpiler::transpile_to_rust(&payload.code) {
+        Ok(rust) => TranspileResponse {
+            rust,
+            errors: Vec::new(),
+        },
+        Err(e) => TranspileResponse {
+            rust: String::new(),
+            errors: vec![e.to_string()],
+        },
+    })
+}
+
+/// Run the program like `/api/run` (with the same timeout) and compare its
+/// output with the expected text. A program that times out fails with a 408.
+async fn expect_output(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ExpectRequest>,
+) -> (StatusCode, Json<ExpectResponse>) {
+    let (status, run) = match execute_with_timeout(&state, &payload.code, None).await {
+        Ok(run) => (StatusCode::OK, run),
+        Err(failed) => failed,
+    };
+    // Trailing newlines are not significant when checking exercises.
+    let expected = format!("{}\n", payload.expected.trim_end_matches('\n'));
+    let actual = format!("{}\n", run.output.trim_end_matches('\n'));
+    let diff = crate::diff::output_diff(&expected, &actual);
+
+    (
+        status,
+        Json(ExpectResponse {
+            passed: diff.is_none() && run.errors.is_empty(),
+            output: run.output,
+            errors: run.errors,
+            diff,
+        }),
+    )
 }
 
 async fn health_check() -> Json<serde_json::Value> {
@@ -79,6 +698,15 @@ async fn health_check() -> Json<serde_json::Value> {
     }))
 }
 
+/// The version and the limits programs run under, so clients can adapt
+/// (e.g. hide file examples when `पठ` is off).
+async fn info(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let mut info = serde_json::to_value(state.interpreter.capabilities()).expect("capabilities are JSON");
+    info["version"] = env!("CARGO_PKG_VERSION").into();
+    info["timeout_ms"] = (state.timeout.as_millis() as u64).into();
+    Json(info)
+}
+
 async fn static_index() -> Result<Response, StatusCode> {
     serve_asset("index.html")
 }
@@ -113,3 +741,531 @@ fn build_asset_response(path: &str, data: Cow<'static, [u8]>) -> Response {
         .body(body)
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn test_state() -> AppState {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_input_provider(Arc::new(NoInput));
+        AppState {
+            interpreter,
+            cache: None,
+            sessions: Arc::new(SessionStore::new(SessionStore::DEFAULT_TTL)),
+            timeout: Duration::from_secs(5),
+            rate_limiter: None,
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    fn test_app() -> Router {
+        app(test_state())
+    }
+
+    async fn post_raw(app: Router, uri: &str, body: serde_json::Value) -> Response {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    async fn into_json(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null)
+    }
+
+    async fn post_json(app: Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let response = post_raw(app, uri, body).await;
+        (response.status(), into_json(response).await)
+    }
+
+    #[tokio::test]
+    async fn test_run_cache_serves_identical_code() {
+        let mut state = test_state();
+        state.cache = Some(Arc::new(RunCache::new(Duration::from_secs(60))));
+        let app = app(state);
+        let body = serde_json::json!({ "code": "दर्श(2 + 2)" });
+
+        let first = post_raw(app.clone(), "/api/run", body.clone()).await;
+        assert_eq!(first.headers()[&CACHE_HEADER], "miss");
+        let first = into_json(first).await;
+
+        let second = post_raw(app, "/api/run", body).await;
+        assert_eq!(second.headers()[&CACHE_HEADER], "hit");
+        assert_eq!(into_json(second).await, first);
+        assert_eq!(first["output"], "4\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_cache_skips_nondeterministic_programs() {
+        let mut state = test_state();
+        state.cache = Some(Arc::new(RunCache::new(Duration::from_secs(60))));
+        let app = app(state);
+        for (code, second_header) in [("दर्श(यादृच्छिक())", "miss"), ("दर्श(कालः())", "miss"), ("बीज(1)\nदर्श(यादृच्छिक())", "hit")] {
+            let body = serde_json::json!({ "code": code });
+            let first = post_raw(app.clone(), "/api/run", body.clone()).await;
+            assert_eq!(first.headers()[&CACHE_HEADER], "miss");
+            let second = post_raw(app.clone(), "/api/run", body).await;
+            assert_eq!(second.headers()[&CACHE_HEADER], second_header, "{}", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_sends_output_then_done() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, test_app()).await.unwrap() });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/stream", addr))
+            .await
+            .unwrap();
+        let code = serde_json::json!({ "code": "दर्श(1)\nदर्श(\"द्वे\")\nअज्ञात(3)" });
+        ws.send(WsMessage::Text(code.to_string())).await.unwrap();
+
+        let mut events = Vec::new();
+        while let Some(Ok(message)) = ws.next().await {
+            if let WsMessage::Text(text) = message {
+                let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let done = event["type"] == "done";
+                events.push(event);
+                if done {
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], serde_json::json!({ "type": "output", "data": "1\n" }));
+        assert_eq!(events[1], serde_json::json!({ "type": "output", "data": "द्वे\n" }));
+        assert_eq!(events[2]["type"], "done");
+        assert_eq!(events[2]["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_stops_at_the_timeout() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app(AppState {
+            timeout: Duration::from_millis(200),
+            ..test_state()
+        });
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/stream", addr))
+            .await
+            .unwrap();
+        let code = serde_json::json!({ "code": "चक्र:\n    दर्श(1)\n    विश्राम(0.01)" });
+        ws.send(WsMessage::Text(code.to_string())).await.unwrap();
+
+        let mut outputs = 0;
+        let done = loop {
+            let Some(Ok(WsMessage::Text(text))) = ws.next().await else { panic!("stream ended without done") };
+            let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+            if event["type"] == "done" {
+                break event;
+            }
+            outputs += 1;
+        };
+        assert!(outputs > 0);
+        assert!(done["errors"][0].as_str().unwrap().contains("समयसीमा"), "{}", done);
+    }
+
+    #[tokio::test]
+    async fn test_session_keeps_state_between_requests() {
+        let app = test_app();
+        let define = serde_json::json!({ "code": "कार्य द्वि(n):\n    प्रतिफल n * 2\nx = 5", "session_id": "s1" });
+        let (status, _) = post_json(app.clone(), "/api/run", define).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let call = serde_json::json!({ "code": "दर्श(द्वि(x))", "session_id": "s1" });
+        let (_, body) = post_json(app.clone(), "/api/run", call.clone()).await;
+        assert_eq!(body["output"], "10\n");
+        assert_eq!(body["errors"], serde_json::json!([]));
+
+        // Another session, and session-less runs, start fresh.
+        let other = serde_json::json!({ "code": "y = द्वि(x)", "session_id": "s2" });
+        let (_, body) = post_json(app.clone(), "/api/run", other).await;
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+        let (_, body) = post_json(app.clone(), "/api/run", serde_json::json!({ "code": "y = x" })).await;
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_session_clears_state() {
+        let app = test_app();
+        let set = serde_json::json!({ "code": "x = 5", "session_id": "s1" });
+        post_json(app.clone(), "/api/run", set).await;
+
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/api/session/s1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(into_json(response).await, serde_json::json!({ "reset": true }));
+
+        let read = serde_json::json!({ "code": "y = x", "session_id": "s1" });
+        let (_, body) = post_json(app, "/api/run", read).await;
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_state_can_be_saved_and_restored() {
+        let app = test_app();
+        let set = serde_json::json!({ "code": "x = [1, \"क\"]\nध्रुव y = 2", "session_id": "s1" });
+        post_json(app.clone(), "/api/run", set).await;
+        let request = Request::builder().uri("/api/session/s1/state").body(Body::empty()).unwrap();
+        let saved = into_json(app.clone().oneshot(request).await.unwrap()).await;
+        assert_eq!(saved["constants"], serde_json::json!(["y"]));
+        // Reading an unknown session doesn't start one.
+        let state = || Request::builder().uri("/api/session/unknown/state").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(state()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(app.clone().oneshot(state()).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let put = |body: serde_json::Value| {
+            Request::builder()
+                .method("PUT")
+                .uri("/api/session/s2/state")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let response = app.clone().oneshot(put(saved)).await.unwrap();
+        assert_eq!(into_json(response).await, serde_json::json!({ "restored": true }));
+        let read = serde_json::json!({ "code": "दर्श(x, y)", "session_id": "s2" });
+        let (_, body) = post_json(app.clone(), "/api/run", read).await;
+        assert_eq!(body["output"], "[1, क] 2\n");
+
+        let response = app.oneshot(put(serde_json::json!({ "vars": 1 }))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rapid_runs_from_one_client_are_rate_limited() {
+        let app = app(AppState {
+            rate_limiter: Some(Arc::new(RateLimiter::new(1, 3))),
+            ..test_state()
+        });
+        let run = |ip: [u8; 4]| {
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/api/run")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::json!({ "code": "दर्श(1)" }).to_string()))
+                .unwrap();
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::from((ip, 4000))));
+            app.clone().oneshot(request)
+        };
+        let mut statuses = Vec::new();
+        for _ in 0..5 {
+            statuses.push(run([10, 0, 0, 1]).await.unwrap().status());
+        }
+        assert_eq!(statuses[..3], [StatusCode::OK; 3]);
+        assert_eq!(statuses[3..], [StatusCode::TOO_MANY_REQUESTS; 2]);
+        // Other clients have their own allowance.
+        assert_eq!(run([10, 0, 0, 2]).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_every_route_that_runs_code_is_rate_limited() {
+        let app = app(AppState {
+            rate_limiter: Some(Arc::new(RateLimiter::new(1, 3))),
+            ..test_state()
+        });
+        let send = |method: &str, uri: &str, body: serde_json::Value| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+            app.clone().oneshot(request)
+        };
+        let expect = serde_json::json!({ "code": "दर्श(1)", "expected": "1\n" });
+        assert_eq!(send("POST", "/api/expect", expect.clone()).await.unwrap().status(), StatusCode::OK);
+        // A batch costs one run per program: two fit in what is left, three don't.
+        let batch = |n: usize| serde_json::json!(vec![serde_json::json!({ "id": 1, "code": "दर्श(1)" }); n]);
+        let status = send("POST", "/api/run/batch", batch(3)).await.unwrap().status();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(send("POST", "/api/run/batch", batch(2)).await.unwrap().status(), StatusCode::OK);
+        let status = send("POST", "/api/expect", expect).await.unwrap().status();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        let status = send("GET", "/api/stream", serde_json::Value::Null).await.unwrap().status();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_rate_limiter_forgets_the_oldest_clients() {
+        let limiter = RateLimiter::new(0, 1);
+        let client = |n: usize| IpAddr::from((n as u32).to_be_bytes());
+        for n in 0..=RateLimiter::MAX_CLIENTS {
+            assert!(limiter.allow(client(n), 1));
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), RateLimiter::MAX_CLIENTS);
+        // The first client was dropped to make room, so its allowance is back.
+        assert!(!limiter.allow(client(1), 1));
+        assert!(limiter.allow(client(0), 1));
+    }
+
+    #[tokio::test]
+    async fn test_batch_runs_each_program_separately_in_order() {
+        let state = AppState {
+            timeout: Duration::from_millis(200),
+            ..test_state()
+        };
+        let batch = serde_json::json!([
+            { "id": "a", "code": "x = 1\nदर्श(x)" },
+            { "id": 2, "code": "दर्श(x)" },
+            { "id": "loop", "code": "यावत् सत्य:\n    विश्राम(1)" },
+        ]);
+        let (status, body) = post_json(app(state), "/api/run/batch", batch).await;
+        assert_eq!(status, StatusCode::OK);
+        let results = body.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], serde_json::json!({ "id": "a", "output": "1\n", "errors": [] }));
+        // The second program doesn't see the first one's variables.
+        assert_eq!(results[1]["id"], 2);
+        assert_eq!(results[1]["errors"].as_array().unwrap().len(), 1);
+        assert!(results[2]["errors"][0].as_str().unwrap().contains("समयसीमा"));
+    }
+
+    #[tokio::test]
+    async fn test_info_reports_configured_limits() {
+        let mut state = test_state();
+        state.interpreter.set_loop_limit(500);
+        state.interpreter.set_max_sleep(MAX_SLEEP);
+        let request = Request::builder().uri("/api/info").body(Body::empty()).unwrap();
+        let info = into_json(app(state).oneshot(request).await.unwrap()).await;
+        assert_eq!(info["max_iterations"], 500);
+        assert_eq!(info["recursion_limit"], 1000);
+        assert_eq!(info["file_access"], false);
+        assert_eq!(info["max_sleep_ms"], 1000);
+        assert_eq!(info["timeout_ms"], 5000);
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_preflight_gets_cors_headers() {
+        let preflight = |app: Router, origin: &str| {
+            let request = Request::builder()
+                .method("OPTIONS")
+                .uri("/api/run")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+                .body(Body::empty())
+                .unwrap();
+            app.oneshot(request)
+        };
+        let allowed = |response: &Response| response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).cloned();
+
+        let response = preflight(test_app(), "http://elsewhere.example").await.unwrap();
+        assert_eq!(allowed(&response).unwrap(), "*");
+        assert!(response.headers().get(header::ACCESS_CONTROL_ALLOW_METHODS).is_some());
+
+        let app = app(AppState {
+            allowed_origins: vec!["https://ide.example".to_string()],
+            ..test_state()
+        });
+        let response = preflight(app.clone(), "https://ide.example").await.unwrap();
+        assert_eq!(allowed(&response).unwrap(), "https://ide.example");
+        let response = preflight(app, "http://elsewhere.example").await.unwrap();
+        assert_eq!(allowed(&response), None);
+    }
+
+    /// Log output captured by a test subscriber.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_are_logged_as_json() {
+        let logs = Captured::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().json().with_writer(move || writer.clone()).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let code = serde_json::json!({ "code": "दर्श(1)\nदर्श(अज्ञात)" });
+        post_json(test_app(), "/api/run", code).await;
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let event: serde_json::Value = serde_json::from_str(logs.lines().next().unwrap()).unwrap();
+        assert_eq!(event["fields"]["message"], "run");
+        assert_eq!(event["fields"]["code_length"], 20);
+        assert_eq!(event["fields"]["errors"], 1);
+        assert_eq!(event["fields"]["status"], 200);
+        assert!(event["fields"]["duration_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_examples_endpoint_lists_bundled_programs() {
+        let app = test_app();
+        let request = Request::builder().uri("/api/examples").body(Body::empty()).unwrap();
+        let listed = into_json(app.clone().oneshot(request).await.unwrap()).await;
+        let hello = listed.as_array().unwrap().iter().find(|example| example["name"] == "hello").unwrap();
+        assert!(hello["code"].as_str().unwrap().contains("दर्श"));
+
+        let request = Request::builder().uri("/api/examples/hello").body(Body::empty()).unwrap();
+        assert_eq!(into_json(app.clone().oneshot(request).await.unwrap()).await, *hello);
+        let request = Request::builder().uri("/api/examples/missing").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_sessions_expire_after_inactivity() {
+        let store = SessionStore::new(Duration::ZERO);
+        let template = Interpreter::default();
+        store.get_or_create("s1", &template).lock().unwrap().run("x = 1");
+        let fresh = store.get_or_create("s1", &template);
+        assert_eq!(fresh.lock().unwrap().run("y = x").errors.len(), 1);
+        assert!(store.get("s1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_a_session_poisoned_by_a_panic_starts_over() {
+        let state = test_state();
+        let session = state.sessions.get_or_create("s1", &state.interpreter);
+        session.lock().unwrap().run("x = 1");
+        let poisoned = session.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoned.lock().unwrap();
+            panic!("run panicked");
+        })
+        .join();
+        assert!(session.is_poisoned());
+
+        let app = app(state);
+        let request = || Request::builder().uri("/api/session/s1/state").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let run = serde_json::json!({ "code": "दर्श(1)\nदर्श(x)", "session_id": "s1" });
+        let (status, body) = post_json(app.clone(), "/api/run", run).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["output"], "1\n");
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(app.oneshot(request()).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_session_store_drops_the_longest_idle_session_when_full() {
+        let store = SessionStore::new(SessionStore::DEFAULT_TTL);
+        let template = Interpreter::default();
+        for n in 0..SessionStore::MAX_SESSIONS {
+            store.get_or_create(&n.to_string(), &template);
+        }
+        // Using a session keeps it.
+        assert!(store.get("0").is_some());
+        store.get_or_create("new", &template);
+        assert_eq!(store.sessions.lock().unwrap().len(), SessionStore::MAX_SESSIONS);
+        assert!(store.get("0").is_some());
+        assert!(store.get("1").is_none());
+        assert!(store.get("new").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_times_out_long_programs() {
+        let mut state = test_state();
+        state.timeout = Duration::from_millis(50);
+        let code = "i = 0\nयावत् (i < 1):\n    j = 0\n    यावत् (j < 1):\n        k = 0\n        यावत् (k < 1):\n            k = k + 0";
+        let (status, body) = post_json(app(state), "/api/run", serde_json::json!({ "code": code })).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert!(body["errors"][0].as_str().unwrap().contains("समयसीमा"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_errors_without_running() {
+        let body = serde_json::json!({ "code": "दर्श(1)\nकार्य (a):\n    दर्श(a)" });
+        let (status, body) = post_json(test_app(), "/api/check", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(body["errors"][0]["line"], 2);
+
+        let body = serde_json::json!({ "code": "दर्श(1)" });
+        let (_, body) = post_json(test_app(), "/api/check", body).await;
+        assert_eq!(body, serde_json::json!({ "errors": [] }));
+    }
+
+    #[tokio::test]
+    async fn test_transpile_returns_rust() {
+        let body = serde_json::json!({ "code": "x = 5\nदर्श(x)" });
+        let (status, body) = post_json(test_app(), "/api/transpile", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["rust"].as_str().unwrap().contains("fn main"), "{}", body);
+        assert_eq!(body["errors"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_transpile_errors_are_reported() {
+        let body = serde_json::json!({ "code": "!! rust: {" });
+        let (status, body) = post_json(test_app(), "/api/transpile", body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["rust"], "");
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_without_cache_has_no_header() {
+        let response = post_raw(test_app(), "/api/run", serde_json::json!({ "code": "दर्श(1)" })).await;
+        assert!(response.headers().get(&CACHE_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expect_matching_output() {
+        let (status, body) = post_json(
+            test_app(),
+            "/api/expect",
+            serde_json::json!({ "code": "दर्श(1 + 1)", "expected": "2" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["passed"], true);
+        assert!(body["diff"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_expect_mismatched_output() {
+        let (status, body) = post_json(
+            test_app(),
+            "/api/expect",
+            serde_json::json!({ "code": "दर्श(\"क\")\nदर्श(3)", "expected": "क\n2\n" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["passed"], false);
+        assert_eq!(body["diff"], " क\n-2\n+3\n");
+    }
+
+    #[tokio::test]
+    async fn test_expect_times_out_like_run() {
+        let app = app(AppState {
+            timeout: Duration::from_millis(100),
+            ..test_state()
+        });
+        let code = "चक्र:\n    विश्राम(1)";
+        let (status, body) = post_json(app, "/api/expect", serde_json::json!({ "code": code, "expected": "" })).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(body["passed"], false);
+        assert!(body["errors"][0].as_str().unwrap().contains("समयसीमा"));
+    }
+}
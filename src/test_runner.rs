@@ -0,0 +1,92 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::Interpreter;
+
+/// The result of running one test file: it passes when it ran without errors,
+/// including failed `निश्चय` assertions.
+pub struct FileOutcome {
+    pub path: PathBuf,
+    pub errors: Vec<String>,
+}
+
+impl FileOutcome {
+    pub fn passed(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// The `.paanini` files under `path` (recursively, in sorted order), or
+/// `path` itself when it is a file.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(discover(&entry)?);
+        } else if entry.extension().is_some_and(|ext| ext == "paanini") {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+/// Run one file in a fresh interpreter.
+pub fn run_file(path: &Path) -> FileOutcome {
+    let errors = match fs::read_to_string(path) {
+        Ok(src) => {
+            let mut interpreter = Interpreter::default();
+            interpreter.set_source_path(path);
+            interpreter.run(&src).errors
+        }
+        Err(e) => vec![format!("Cannot read file: {}", e)],
+    };
+    FileOutcome {
+        path: path.to_path_buf(),
+        errors,
+    }
+}
+
+/// Discover and run every test file under `path`.
+pub fn run_tests(path: &Path) -> io::Result<Vec<FileOutcome>> {
+    Ok(discover(path)?.iter().map(|file| run_file(file)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passing_and_failing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("pass.paanini"), "x = 2 + 2\nनिश्चय (x == 4), \"योगः\"\n").unwrap();
+        fs::write(dir.path().join("nested/fail.paanini"), "निश्चय (1 == 2), \"असमम्\"\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a test").unwrap();
+
+        let outcomes = run_tests(dir.path()).unwrap();
+        let names: Vec<_> = outcomes.iter().map(|o| o.path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["fail.paanini", "pass.paanini"]);
+        assert!(!outcomes[0].passed());
+        assert!(outcomes[0].errors[0].contains("असमम्"), "{:?}", outcomes[0].errors);
+        assert!(outcomes[1].passed(), "{:?}", outcomes[1].errors);
+    }
+
+    #[test]
+    fn test_single_file_and_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("one.paanini");
+        fs::write(&file, "दर्श(1)\n").unwrap();
+        let outcomes = run_tests(&file).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].passed());
+
+        let outcome = run_file(&dir.path().join("missing.paanini"));
+        assert!(!outcome.passed());
+    }
+}
@@ -1,4 +1,10 @@
+use std::collections::{HashMap, HashSet};
+
 use anyhow::{Result, anyhow};
+use crate::ast::{
+    bool_to_sanskrit, do_while_condition, is_quote, join_triple_quoted, parse_break, sanskrit_to_bool,
+    split_loop_label, StrScan, DO_WITHOUT_WHILE,
+};
 
 /// Transpile Paanini Sanskrit code to Rust code
 pub fn transpile_to_rust(paanini_code: &str) -> Result<String> {
@@ -7,48 +13,352 @@ pub fn transpile_to_rust(paanini_code: &str) -> Result<String> {
     // Add Rust boilerplate
     rust_code.push_str("fn main() {\n");
     
+    let paanini_code = join_triple_quoted(paanini_code);
     let lines: Vec<&str> = paanini_code.lines().collect();
-    let indent_level = 1;
+    // Source indentation of each open block's header, as in the
+    // interpreter's `preprocess_indentation`.
+    let mut block_stack: Vec<usize> = Vec::new();
+    // Blank lines and comments seen since the last statement, with their
+    // indentation. They are written once the next statement shows which
+    // blocks close first: a comment stays inside a block it is indented
+    // under, and a blank line goes after any closing braces.
+    let mut pending: Vec<(usize, String)> = Vec::new();
+    // Depths in `block_stack` of the open `कुरु` blocks, which become `loop`s
+    // ended by their `यावत् (cond)` line.
+    let mut do_blocks: Vec<usize> = Vec::new();
+    let functions = function_params(&lines);
     
-    for line in lines {
+    for (idx, line) in lines.iter().enumerate() {
         let trimmed = line.trim();
+        let raw_rust = raw_rust_directive(trimmed);
         
-        // Skip empty lines and comments
-        if trimmed.is_empty() || trimmed.starts_with("!!") {
+        // Comments (except `!! rust:` directives) become `//` comments.
+        if raw_rust.is_none() && (trimmed.is_empty() || trimmed.starts_with("!!") || trimmed.starts_with('#')) {
+            match rust_comment(trimmed) {
+                Some(comment) => pending.push((source_indent(line), comment)),
+                None if rust_code.len() > "fn main() {\n".len() => pending.push((0, String::new())),
+                None => {}
+            }
             continue;
         }
         
-        // Handle indentation
-        let _current_indent = line.len() - line.trim_start().len();
-        let rust_indent = "    ".repeat(indent_level);
+        // Handle indentation: dedenting to or past a header closes its block.
+        // An `अन्यथा` at its `यदि`'s level closes that block itself via `} else {`.
+        let current_indent = source_indent(line);
+        let is_else = trimmed == "अन्यथा:" || trimmed == "anyatha:";
+        let mut ended_do = false;
+        while let Some(&header_indent) = block_stack.last() {
+            if current_indent > header_indent {
+                break;
+            }
+            flush_pending(&mut rust_code, &mut pending, Some(header_indent), block_stack.len());
+            block_stack.pop();
+            if is_else && header_indent == current_indent {
+                break;
+            }
+            if do_blocks.last() == Some(&block_stack.len()) {
+                do_blocks.pop();
+                if header_indent != current_indent || !trimmed.starts_with("यावत्") || trimmed.ends_with(':') {
+                    return Err(anyhow!("Line {}: {}", idx + 1, DO_WITHOUT_WHILE));
+                }
+                let cond = do_while_condition(trimmed).map_err(|e| anyhow!("Line {}: {}", idx + 1, e))?;
+                let inner = "    ".repeat(block_stack.len() + 2);
+                rust_code.push_str(&format!("{}if !({}) {{\n", inner, transpile_expression(cond)?));
+                rust_code.push_str(&format!("{}    break;\n{}}}\n", inner, inner));
+                ended_do = true;
+            }
+            rust_code.push_str(&"    ".repeat(block_stack.len() + 1));
+            rust_code.push_str("}\n");
+        }
+        if ended_do {
+            continue;
+        }
+        flush_pending(&mut rust_code, &mut pending, None, block_stack.len());
+        let rust_indent = "    ".repeat(block_stack.len() + 1);
         
-        // Transpile line based on Sanskrit keywords
-        let rust_line = transpile_line(trimmed)?;
+        // Transpile line based on Sanskrit keywords; function headers also
+        // look ahead at their body to pick a return type.
+        let rust_line = if let Some(raw) = raw_rust {
+            validate_raw_rust(raw, idx + 1)?;
+            raw.to_string()
+        } else if trimmed.starts_with("कार्य ") || trimmed.starts_with("karya ") {
+            transpile_function_header(trimmed, function_body(&lines[idx + 1..], line))?
+        } else {
+            transpile_line(trimmed)?
+        };
+        let rust_line = float_arguments(&rust_line, &functions);
         
         if !rust_line.is_empty() {
             rust_code.push_str(&rust_indent);
             rust_code.push_str(&rust_line);
             rust_code.push('\n');
         }
+        if rust_line.ends_with('{') {
+            if split_loop_label(trimmed).is_ok_and(|(_, header)| header == "कुरु:") {
+                do_blocks.push(block_stack.len());
+            }
+            block_stack.push(current_indent);
+        }
+    }
+    if !do_blocks.is_empty() {
+        return Err(anyhow!("Line {}: {}", lines.len(), DO_WITHOUT_WHILE));
     }
     
+    // Trailing blank lines are dropped.
+    pending.retain(|(_, comment)| !comment.is_empty());
+    while let Some(&header_indent) = block_stack.last() {
+        flush_pending(&mut rust_code, &mut pending, Some(header_indent), block_stack.len());
+        block_stack.pop();
+        rust_code.push_str(&"    ".repeat(block_stack.len() + 1));
+        rust_code.push_str("}\n");
+    }
+    flush_pending(&mut rust_code, &mut pending, None, 0);
     rust_code.push_str("}\n");
     
-    Ok(rust_code)
+    Ok(ascii_identifiers(&rust_code))
+}
+
+/// Rust's strict keywords and reserved words, which a transliterated name
+/// must not take.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static",
+    "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Rename every non-ASCII identifier in the generated Rust to its ASCII
+/// transliteration, the same way at each use so references still resolve.
+/// A name that would clash with a keyword, an ASCII name already in the
+/// program, or another transliteration gets a numeric suffix; names are
+/// assigned in order of first appearance, so the output is deterministic.
+fn ascii_identifiers(rust_code: &str) -> String {
+    let mut taken: HashSet<String> = RUST_KEYWORDS.iter().map(|k| k.to_string()).collect();
+    for line in rust_code.lines() {
+        let (_, code) = split_rust_label(split_rust_comment(line).0);
+        map_words(code, |word| {
+            if word.is_ascii() {
+                taken.insert(word.to_string());
+            }
+            None
+        });
+    }
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut out = String::new();
+    for line in rust_code.lines() {
+        let (code, comment) = split_rust_comment(line);
+        let (label, code) = split_rust_label(code);
+        out.push_str(label);
+        out.push_str(&map_words(code, |word| {
+            if word.is_ascii() {
+                return None;
+            }
+            let base = transliterate(word);
+            // Devanagari digits make a number literal, not a name.
+            if base.starts_with(|c: char| c.is_ascii_digit()) {
+                return Some(base);
+            }
+            if let Some(name) = names.get(word) {
+                return Some(name.clone());
+            }
+            let mut name = base.clone();
+            let mut n = 2;
+            while taken.contains(&name) {
+                name = format!("{}_{}", base, n);
+                n += 1;
+            }
+            taken.insert(name.clone());
+            names.insert(word.to_string(), name.clone());
+            Some(name)
+        }));
+        out.push_str(comment);
+        out.push('\n');
+    }
+    out
+}
+
+/// Split a line of generated Rust before its `//` comment, if any.
+fn split_rust_comment(line: &str) -> (&str, &str) {
+    let mut scan = StrScan::default();
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        if !scan.step(c) && c == '/' && prev == Some('/') {
+            return line.split_at(i - 1);
+        }
+        prev = Some(c);
+    }
+    (line, "")
+}
+
+/// Split a line of generated Rust after the label starting it, if any
+/// (`'baahya: ` in `'baahya: for ...`), so its quote isn't read as opening
+/// a string.
+fn split_rust_label(line: &str) -> (&str, &str) {
+    let indent = line.len() - line.trim_start().len();
+    let label_len = line[indent..]
+        .strip_prefix('\'')
+        .and_then(|rest| rest.split_once(": "))
+        .filter(|(label, _)| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .map(|(label, _)| label.len() + 3);
+    line.split_at(indent + label_len.unwrap_or(0))
+}
+
+/// ASCII spelling of a Devanagari identifier: consonants carry an inherent
+/// `a` unless followed by a vowel sign or virama, long vowels are doubled
+/// (`आ` is `aa`), and Devanagari digits become ASCII digits. ASCII characters
+/// are kept, and anything else is spelled by its code point (`u0a95`).
+fn transliterate(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(mut consonant) = devanagari_consonant(c) {
+            if chars.get(i + 1) == Some(&'\u{093C}') {
+                consonant = nukta_consonant(c).unwrap_or(consonant);
+                i += 1;
+            }
+            out.push_str(consonant);
+            match chars.get(i + 1).copied() {
+                Some('\u{094D}') => i += 1,
+                Some(next) => match devanagari_vowel_sign(next) {
+                    Some(vowel) => {
+                        out.push_str(vowel);
+                        i += 1;
+                    }
+                    None => out.push('a'),
+                },
+                None => out.push('a'),
+            }
+        } else if let Some(sound) = devanagari_vowel(c) {
+            out.push_str(sound);
+        } else if let Some(digit) = c.to_digit(10).filter(|_| ('\u{0966}'..='\u{096F}').contains(&c)) {
+            out.push_str(&digit.to_string());
+        } else if c.is_ascii() {
+            out.push(c);
+        } else if !matches!(c, '\u{093C}' | '\u{094D}' | '\u{093D}') {
+            out.push_str(&format!("u{:04x}", c as u32));
+        }
+        i += 1;
+    }
+    if out.is_empty() {
+        out = word.chars().map(|c| format!("u{:04x}", c as u32)).collect();
+    }
+    out
+}
+
+fn devanagari_consonant(c: char) -> Option<&'static str> {
+    Some(match c {
+        'क' => "k", 'ख' => "kh", 'ग' => "g", 'घ' => "gh", 'ङ' => "ng",
+        'च' => "ch", 'छ' => "chh", 'ज' => "j", 'झ' => "jh", 'ञ' => "ny",
+        'ट' | 'त' => "t", 'ठ' | 'थ' => "th", 'ड' | 'द' => "d", 'ढ' | 'ध' => "dh", 'ण' | 'न' | 'ऩ' => "n",
+        'प' => "p", 'फ' => "ph", 'ब' => "b", 'भ' => "bh", 'म' => "m",
+        'य' => "y", 'र' | 'ऱ' => "r", 'ल' | 'ळ' | 'ऴ' => "l", 'व' => "v",
+        'श' | 'ष' => "sh", 'स' => "s", 'ह' => "h",
+        '\u{0958}' => "q", '\u{0959}' => "kh", '\u{095A}' => "g", '\u{095B}' => "z",
+        '\u{095C}' => "r", '\u{095D}' => "rh", '\u{095E}' => "f", '\u{095F}' => "y",
+        _ => return None,
+    })
+}
+
+/// The consonant written as `c` followed by a nukta (`ड` + `़` is `ड़`).
+fn nukta_consonant(c: char) -> Option<&'static str> {
+    Some(match c {
+        'क' => "q", 'ज' => "z", 'ड' => "r", 'ढ' => "rh", 'फ' => "f",
+        _ => return None,
+    })
+}
+
+fn devanagari_vowel_sign(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ा' => "aa", 'ि' => "i", 'ी' => "ii", 'ु' => "u", 'ू' => "uu", 'ृ' => "ri", 'ॄ' => "rii", 'ॢ' => "lri",
+        'े' | 'ॅ' => "e", 'ै' => "ai", 'ो' | 'ॉ' => "o", 'ौ' => "au",
+        _ => return None,
+    })
+}
+
+/// Independent vowels and the signs that stand alone after a syllable.
+fn devanagari_vowel(c: char) -> Option<&'static str> {
+    Some(match c {
+        'अ' => "a", 'आ' => "aa", 'इ' => "i", 'ई' => "ii", 'उ' => "u", 'ऊ' => "uu", 'ऋ' => "ri", 'ॠ' => "rii",
+        'ऌ' => "lri", 'ए' | 'ऍ' => "e", 'ऐ' => "ai", 'ओ' | 'ऑ' => "o", 'औ' => "au",
+        'ं' => "m", 'ँ' => "n", 'ः' => "h",
+        _ => return None,
+    })
+}
+
+/// `!! text` or `# text` as a Rust `// text` comment; `None` for a blank line.
+fn rust_comment(line: &str) -> Option<String> {
+    let text = line.strip_prefix("!!").or_else(|| line.strip_prefix('#'))?.trim();
+    Some(if text.is_empty() { "//".to_string() } else { format!("// {}", text) })
+}
+
+/// Write the pending blank lines and comments at nesting `depth`: all of
+/// them, or with `inside` set only the leading ones indented deeper than
+/// that block header, which belong inside the block about to close.
+fn flush_pending(rust_code: &mut String, pending: &mut Vec<(usize, String)>, inside: Option<usize>, depth: usize) {
+    let count = match inside {
+        Some(header_indent) => pending.iter().take_while(|(indent, _)| *indent > header_indent).count(),
+        None => pending.len(),
+    };
+    for (_, comment) in pending.drain(..count) {
+        if !comment.is_empty() {
+            rust_code.push_str(&"    ".repeat(depth + 1));
+            rust_code.push_str(&comment);
+        }
+        rust_code.push('\n');
+    }
+}
+
+/// `!! rust: <code>` passes `<code>` through verbatim. The interpreter sees
+/// it as an ordinary comment.
+fn raw_rust_directive(line: &str) -> Option<&str> {
+    line.strip_prefix("!!")?.trim_start().strip_prefix("rust:").map(str::trim)
+}
+
+/// Raw Rust must be a complete statement: it takes the place of one Paanini
+/// line, so it can't be empty or open/close blocks the transpiler tracks.
+fn validate_raw_rust(code: &str, line_no: usize) -> Result<()> {
+    if code.is_empty() {
+        return Err(anyhow!("Line {}: empty `!! rust:` directive", line_no));
+    }
+    let mut depth = 0i32;
+    let mut scan = StrScan::default();
+    for c in code.chars() {
+        match c {
+            _ if scan.step(c) => {}
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        if depth < 0 {
+            break;
+        }
+    }
+    if depth != 0 {
+        return Err(anyhow!(
+            "Line {}: `!! rust:` code must be a single statement with balanced braces: {}",
+            line_no,
+            code
+        ));
+    }
+    Ok(())
 }
 
 fn transpile_line(line: &str) -> Result<String> {
     // दर्श() -> println!()
     if line.starts_with("दर्श(") || line.starts_with("darsh(") {
         let args = extract_function_args(line)?;
-        return Ok(format!("println!({});", args));
+        return transpile_print(&args);
     }
     
     // यदि -> if
     if line.starts_with("यदि ") || line.starts_with("yadi ") {
         let condition = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
         let condition = condition.trim_end_matches(':');
-        return Ok(format!("if {} {{", condition));
+        return Ok(format!("if {} {{", transpile_expression(condition)?));
     }
     
     // अन्यथा -> else
@@ -56,18 +366,71 @@ fn transpile_line(line: &str) -> Result<String> {
         return Ok("} else {".to_string());
     }
     
+    // चिह्न name: -> a Rust loop label. Labels can't clash with variables,
+    // so they are transliterated here rather than by `ascii_identifiers`.
+    if let (Some(label), header) = split_loop_label(line).map_err(|e| anyhow!(e))? {
+        return Ok(format!("'{}: {}", transliterate(label), transpile_line(header)?));
+    }
+
+    // विरम -> break
+    if let Some(label) = parse_break(line) {
+        return Ok(match label.map_err(|e| anyhow!(e))? {
+            Some(label) => format!("break '{};", transliterate(label)),
+            None => "break;".to_string(),
+        });
+    }
+
+    // कुरु -> loop; its closing `यावत्` line is handled with the block's end
+    if line == "कुरु:" {
+        return Ok("loop {".to_string());
+    }
+
+    // चक्र -> loop
+    if line == "चक्र:" {
+        return Ok("loop {".to_string());
+    }
+
     // यावत् -> while
     if line.starts_with("यावत् ") || line.starts_with("yavat ") {
         let condition = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
         let condition = condition.trim_end_matches(':');
-        return Ok(format!("while {} {{", condition));
+        return Ok(format!("while {} {{", transpile_expression(condition)?));
     }
     
+    // परिभ्रमण -> for
+    if line.starts_with("परिभ्रमण ") || line.starts_with("paribhraman ") {
+        let header = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
+        let header = header.trim_end_matches(':');
+        let (var, iterable) = header
+            .split_once(" in ")
+            .ok_or_else(|| anyhow!("Invalid for loop: {}", line))?;
+        let vars = split_top_level_commas(var.trim())
+            .into_iter()
+            .map(transpile_expression)
+            .collect::<Result<Vec<_>>>()?;
+        let var = match vars.as_slice() {
+            [var] => var.clone(),
+            _ => format!("({})", vars.join(", ")),
+        };
+        return Ok(format!("for {} in {} {{", var, transpile_iterable(iterable.trim())?));
+    }
+
     // कार्य -> fn (function definition)
     if line.starts_with("कार्य ") || line.starts_with("karya ") {
-        let func_def = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
-        let func_def = func_def.trim_end_matches(':');
-        return Ok(format!("fn {} {{", func_def));
+        return transpile_function_header(line, Vec::new());
+    }
+
+    // प्रतिफल -> return
+    if line == "प्रतिफल" || line == "pratiphal" {
+        return Ok("return;".to_string());
+    }
+    if line.starts_with("प्रतिफल ") || line.starts_with("pratiphal ") {
+        let value = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
+        let value = transpile_expression(&value)?;
+        if value.starts_with('"') {
+            return Ok(format!("return {}.to_string();", value));
+        }
+        return Ok(format!("return {};", value));
     }
     
     // Handle block endings (dedentation)
@@ -76,13 +439,18 @@ fn transpile_line(line: &str) -> Result<String> {
     }
     
     // Variable assignments and expressions
-    if line.contains("=") && !line.contains("==") {
-        let parts: Vec<&str> = line.splitn(2, '=').collect();
-        if parts.len() == 2 {
-            let var_name = parts[0].trim();
-            let value = parts[1].trim();
-            return Ok(format!("let {} = {};", var_name, transpile_expression(value)?));
+    if let Some((var_name, value)) = crate::ast::split_assignment(line) {
+        // `a, b = b, a` becomes a tuple binding, which also evaluates every
+        // value before binding any name.
+        if let Some(unpack) = crate::ast::split_unpack(var_name, value) {
+            let (targets, values) = unpack.map_err(|e| anyhow!(e))?;
+            if values.len() != targets.len() {
+                return Err(anyhow!("Unpacking a list is not supported when transpiling: {}", line));
+            }
+            let values = values.into_iter().map(transpile_expression).collect::<Result<Vec<_>>>()?;
+            return Ok(format!("let ({}) = ({});", targets.join(", "), values.join(", ")));
         }
+        return Ok(format!("let {} = {};", var_name, transpile_expression(value)?));
     }
     
     // Function calls
@@ -94,6 +462,204 @@ fn transpile_line(line: &str) -> Result<String> {
     Ok(format!("{};", transpile_expression(line)?))
 }
 
+/// Leading indentation width, counting a tab as two spaces like the interpreter.
+fn source_indent(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { 2 } else { 1 })
+        .sum()
+}
+
+/// The lines indented deeper than `header`, i.e. a function's body.
+fn function_body<'a>(rest: &[&'a str], header: &str) -> Vec<&'a str> {
+    let header_indent = source_indent(header);
+    rest.iter()
+        .take_while(|l| l.trim().is_empty() || source_indent(l) > header_indent)
+        .map(|l| l.trim())
+        .collect()
+}
+
+/// `कार्य name(a, b):` becomes `fn name(a: f64, b: f64) -> T {`. Parameters
+/// default to `f64` to match the interpreter's numeric model; an explicit
+/// `name: Type` is kept. The return type is inferred from the body's
+/// `प्रतिफल` lines: string literals give `String`, comparisons and boolean
+/// literals give `bool`, anything else `f64`, and no value gives no type.
+fn transpile_function_header(line: &str, body: Vec<&str>) -> Result<String> {
+    let func_def = line.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
+    let func_def = func_def.trim_end_matches(':');
+    let (name, rest) = func_def
+        .split_once('(')
+        .ok_or_else(|| anyhow!("Invalid function definition: {}", line))?;
+    let params = rest
+        .strip_suffix(')')
+        .ok_or_else(|| anyhow!("Invalid function definition: {}", line))?;
+    let params = split_top_level_commas(params)
+        .into_iter()
+        .map(|p| {
+            let p = transpile_expression(p)?;
+            Ok(if p.contains(':') { p } else { format!("{}: f64", p) })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let return_type = body
+        .iter()
+        .filter_map(|l| l.strip_prefix("प्रतिफल ").or_else(|| l.strip_prefix("pratiphal ")))
+        .map(|value| {
+            let value = value.trim();
+            if value.starts_with('"') {
+                "String"
+            } else if sanskrit_to_bool(value).is_some()
+                || ["==", "!=", ">", "<"].iter().any(|op| value.contains(op))
+            {
+                "bool"
+            } else {
+                "f64"
+            }
+        })
+        .next();
+
+    let name = transpile_expression(name.trim())?;
+    Ok(match return_type {
+        Some(ty) => format!("fn {}({}) -> {} {{", name, params.join(", "), ty),
+        None => format!("fn {}({}) {{", name, params.join(", ")),
+    })
+}
+
+/// For each function the program defines, which of its parameters are
+/// untyped and so become `f64`.
+fn function_params(lines: &[&str]) -> HashMap<String, Vec<bool>> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let header = line.trim();
+            let header = header.strip_prefix("कार्य ").or_else(|| header.strip_prefix("karya "))?;
+            let (name, params) = header.trim_end_matches(':').split_once('(')?;
+            let params = split_top_level_commas(params.strip_suffix(')')?);
+            Some((name.trim().to_string(), params.iter().map(|p| !p.contains(':')).collect()))
+        })
+        .collect()
+}
+
+/// Write integer literals passed to `f64` parameters as float literals, so
+/// `जोड़(2, 3)` calls `jora(2.0, 3.0)`: Rust doesn't convert them itself.
+fn float_arguments(code: &str, functions: &HashMap<String, Vec<bool>>) -> String {
+    let mut inserts = Vec::new();
+    let mut scan = StrScan::default();
+    let mut word_start = None;
+    for (i, c) in code.char_indices() {
+        if scan.step(c) {
+            word_start = None;
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' || (c as u32) > 127 {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        let name = word_start.take().map(|start| &code[start..i]);
+        let (Some(floats), '(') = (name.and_then(|name| functions.get(name)), c) else {
+            continue;
+        };
+        let Some(close) = crate::ast::find_matching_paren(code, i) else {
+            continue;
+        };
+        let mut offset = i + 1;
+        for (arg, &float) in crate::ast::split_top_level(&code[i + 1..close], ',').iter().zip(floats) {
+            let digits = arg.trim().strip_prefix('-').unwrap_or(arg.trim());
+            let is_integer = !digits.is_empty()
+                && digits.chars().all(|c| c.is_ascii_digit() || ('\u{0966}'..='\u{096F}').contains(&c));
+            if float && is_integer {
+                inserts.push(offset + arg.trim_end().len());
+            }
+            offset += arg.len() + 1;
+        }
+    }
+    // A call nested in another's arguments is found after the outer one.
+    inserts.sort_unstable();
+    let mut out = code.to_string();
+    for at in inserts.into_iter().rev() {
+        out.insert_str(at, ".0");
+    }
+    out
+}
+
+/// Translate the iterable of a `परिभ्रमण` loop. `परिधि(...)` becomes a Rust
+/// range and `क्रमसूची(x)` enumerates `x`; anything else (e.g. a list
+/// variable) is iterated directly.
+fn transpile_iterable(iterable: &str) -> Result<String> {
+    if let Some(list) = iterable.strip_prefix("क्रमसूची(").and_then(|rest| rest.strip_suffix(')')) {
+        return Ok(format!("{}.into_iter().enumerate()", transpile_expression(list)?));
+    }
+    let range_args = iterable
+        .strip_prefix("परिधि(")
+        .and_then(|rest| rest.strip_suffix(')'));
+    let Some(range_args) = range_args else {
+        return transpile_expression(iterable);
+    };
+    let args = split_top_level_commas(range_args)
+        .into_iter()
+        .map(transpile_expression)
+        .collect::<Result<Vec<_>>>()?;
+    match args.as_slice() {
+        [stop] => Ok(format!("0..{}", stop)),
+        [start, stop] => Ok(format!("{}..{}", start, stop)),
+        [start, stop, step] => match step.parse::<i64>() {
+            Ok(0) => Err(anyhow!("परिधि step cannot be zero: {}", iterable)),
+            Ok(n) if n > 0 => Ok(format!("({}..{}).step_by({})", start, stop, n)),
+            Ok(n) => Ok(format!("(({}) + 1..={}).rev().step_by({})", stop, start, -n)),
+            // Step only known at runtime: walk in its direction until passing `stop`.
+            Err(_) => Ok(format!(
+                "std::iter::successors(Some({start}), |&i| Some(i + ({step}))).take_while(|&i| if ({step}) > 0 {{ i < ({stop}) }} else {{ i > ({stop}) }})",
+                start = start,
+                stop = stop,
+                step = step
+            )),
+        },
+        _ => Err(anyhow!("परिधि takes one to three arguments: {}", iterable)),
+    }
+}
+
+/// `println!` needs a literal format string. A string with `{}` followed by
+/// values is used as one, and a lone string without braces is printed as
+/// is; otherwise each value gets a `{}`, separated by spaces as the
+/// interpreter prints them.
+fn transpile_print(args: &str) -> Result<String> {
+    let args = split_top_level_commas(args)
+        .into_iter()
+        .map(transpile_expression)
+        .collect::<Result<Vec<_>>>()?;
+    let literal = args.first().filter(|first| first.starts_with('"'));
+    let has_braces = literal.is_some_and(|first| first.contains(['{', '}']));
+    if literal.is_some() && has_braces == (args.len() > 1) {
+        return Ok(format!("println!({});", args.join(", ")));
+    }
+    if args.is_empty() {
+        return Ok("println!();".to_string());
+    }
+    let format = vec!["{}"; args.len()].join(" ");
+    Ok(format!("println!(\"{}\", {});", format, args.join(", ")))
+}
+
+fn split_top_level_commas(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut scan = StrScan::default();
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            _ if scan.step(c) => {}
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
 fn extract_function_args(line: &str) -> Result<String> {
     if let Some(start) = line.find('(') {
         if let Some(end) = line.rfind(')') {
@@ -111,21 +677,133 @@ fn transpile_expression(expr: &str) -> Result<String> {
     result = result.replace("दर्श(", "println!(");
     result = result.replace("darsh(", "println!(");
     
-    // Replace Sanskrit variable names with transliterated versions
-    result = result.replace("योग", "yog");
-    result = result.replace("नाम", "naam");
+    // Replace Sanskrit keywords and variable names; only whole words are
+    // replaced so identifiers like `नाम` are not mangled by `न`.
+    result = replace_words(&result, &[
+        (bool_to_sanskrit(true), "true"),
+        (bool_to_sanskrit(false), "false"),
+        ("तथा", "&&"),
+        ("वा", "||"),
+        ("न", "!"),
+    ]);
     
-    // Handle string literals in Sanskrit
-    if result.contains("\"") {
-        // Keep string literals as-is since Rust supports UTF-8
+    // String literals are kept as-is since Rust supports UTF-8, but
+    // single-quoted ones must become double-quoted.
+    if result.contains('\'') {
+        result = double_quote_strings(&result);
     }
     
     Ok(result)
 }
 
+/// Rewrite `'...'` string literals as Rust `"..."` literals, escaping any
+/// double quotes they contain.
+fn double_quote_strings(expr: &str) -> String {
+    let mut out = String::new();
+    let mut quote = None;
+    let mut chars = expr.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            None if is_quote(c) => {
+                quote = Some(c);
+                out.push('"');
+            }
+            None => out.push(c),
+            Some(_) if c == '\\' => match chars.next() {
+                Some('\'') => out.push('\''),
+                Some(next) => {
+                    out.push(c);
+                    out.push(next);
+                }
+                None => out.push(c),
+            },
+            Some(q) if c == q => {
+                quote = None;
+                out.push('"');
+            }
+            Some(_) if c == '"' => out.push_str("\\\""),
+            Some(_) => out.push(c),
+        }
+    }
+    out
+}
+
+/// Replace whole identifier-like words outside string literals.
+fn replace_words(expr: &str, words: &[(&str, &str)]) -> String {
+    map_words(expr, |word| {
+        words.iter().find(|(from, _)| *from == word).map(|(_, to)| to.to_string())
+    })
+}
+
+/// Rewrite identifier-like words outside string literals with `f`, keeping
+/// any word it returns `None` for.
+fn map_words(expr: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut word = String::new();
+    let mut scan = StrScan::default();
+    let mut flush = |word: &mut String, out: &mut String| {
+        match f(word) {
+            Some(to) => out.push_str(&to),
+            None => out.push_str(word),
+        }
+        word.clear();
+    };
+    for c in expr.chars() {
+        if !scan.step(c) && (c.is_alphanumeric() || c == '_' || (c as u32) > 127) {
+            word.push(c);
+            continue;
+        }
+        flush(&mut word, &mut out);
+        out.push(c);
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::process::Command;
+
+    /// Compile `rust_code` with rustc, panicking with its diagnostics on failure.
+    /// Skipped when rustc isn't available.
+    fn assert_compiles(rust_code: &str) {
+        compile(rust_code);
+    }
+
+    /// Compile and run `rust_code`, checking what it prints. Skipped, like
+    /// `assert_compiles`, when rustc isn't available.
+    fn assert_prints(rust_code: &str, expected: &str) {
+        let Some(dir) = compile(rust_code) else {
+            return;
+        };
+        let output = Command::new(dir.path().join("main")).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{}", rust_code);
+    }
+
+    /// The directory holding the compiled `main`, or `None` without rustc.
+    fn compile(rust_code: &str) -> Option<tempfile::TempDir> {
+        if Command::new("rustc").arg("--version").output().is_err() {
+            return None;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("main.rs");
+        std::fs::write(&src, rust_code).unwrap();
+        let output = Command::new("rustc")
+            .arg("--edition=2021")
+            .arg(&src)
+            .arg("-o")
+            .arg(dir.path().join("main"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "generated Rust failed to compile:\n{}\n{}",
+            rust_code,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Some(dir)
+    }
 
     #[test]
     fn test_simple_transpilation() {
@@ -147,6 +825,254 @@ x = 5
         
         let result = transpile_to_rust(paanini_code).unwrap();
         assert!(result.contains("let x = 5;"));
-        assert!(result.contains("println!(x);"));
+        assert!(result.contains("println!(\"{}\", x);"), "{}", result);
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_print_always_has_a_literal_format_string() {
+        let result = transpile_to_rust("x = 1
+दर्श(\"योग\", x)
+दर्श('{x}')
+दर्श(\"{} {}\", x, x)
+दर्श()").unwrap();
+        assert!(result.contains("println!(\"{} {}\", \"योग\", x);"), "{}", result);
+        assert!(result.contains("println!(\"{}\", \"{x}\");"), "{}", result);
+        assert!(result.contains("println!(\"{} {}\", x, x);"), "{}", result);
+        assert!(result.contains("println!();"), "{}", result);
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_boolean_logic_in_condition() {
+        let paanini_code = r#"
+x = 5
+y = सत्य
+यदि (x == 5 तथा न असत्य वा y):
+    दर्श(x)
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        assert!(result.contains("let y = true;"));
+        assert!(result.contains("if (x == 5 && ! false || y) {"));
+    }
+
+    #[test]
+    fn test_keywords_do_not_corrupt_identifiers() {
+        let result = transpile_to_rust("नमन = \"न\"").unwrap();
+        assert!(result.contains("let namana = \"न\";"), "{}", result);
+    }
+
+    #[test]
+    fn test_single_quoted_strings_become_rust_strings() {
+        let result = transpile_to_rust(r#"x = 'सः "नमः" इति, it\'s'"#).unwrap();
+        assert!(result.contains(r#"let x = "सः \"नमः\" इति, it's";"#), "{}", result);
+    }
+
+    #[test]
+    fn test_triple_quoted_string_is_one_rust_literal() {
+        let result = transpile_to_rust("x = \"\"\"क \"ख\"\nग\"\"\"").unwrap();
+        assert!(result.contains(r#"let x = "क \"ख\"\nग";"#), "{}", result);
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines_are_kept() {
+        let src = "!! गणना\nx = 1\n\nयदि x > 0:\n    # धनात्मकम्\n    दर्श(\"{}\", x)\n    !! खण्डान्तः\n\nदर्श(\"{}\", x)\n";
+        let result = transpile_to_rust(src).unwrap();
+        assert_eq!(
+            result,
+            "fn main() {\n    // गणना\n    let x = 1;\n\n    if x > 0 {\n        // धनात्मकम्\n        println!(\"{}\", x);\n        // खण्डान्तः\n    }\n\n    println!(\"{}\", x);\n}\n"
+        );
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_multiple_assignment_becomes_a_tuple_binding() {
+        let result = transpile_to_rust("a, b = 1, 2\na, b = b, a\nदर्श(\"{} {}\", a, b)").unwrap();
+        assert!(result.contains("let (a, b) = (1, 2);"), "{}", result);
+        assert!(result.contains("let (a, b) = (b, a);"), "{}", result);
+        assert_compiles(&result);
+        assert!(transpile_to_rust("a, b = सूची").is_err());
+    }
+
+    #[test]
+    fn test_enumerate_loop_becomes_iterator_enumerate() {
+        let result = transpile_to_rust("परिभ्रमण i, v in क्रमसूची(नामानि):\n    दर्श(\"{} {}\", i, v)").unwrap();
+        assert!(result.contains("for (i, v) in naamaani.into_iter().enumerate() {"), "{}", result);
+    }
+
+    #[test]
+    fn test_labeled_break_becomes_a_rust_label() {
+        let src = "चिह्न बाह्य: परिभ्रमण अङ्कः in परिधि(3):\n    परिभ्रमण j in परिधि(3):\n        यदि अङ्कः == j:\n            विरम बाह्य\n        विरम";
+        let result = transpile_to_rust(src).unwrap();
+        assert!(result.contains("'baahya: for angkah in 0..3 {"), "{}", result);
+        assert!(result.contains("break 'baahya;"), "{}", result);
+        assert!(result.contains("            break;\n"), "{}", result);
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_do_while_becomes_a_loop_tested_at_the_end() {
+        let src = "x = 10\nकुरु:\n    दर्श(\"{}\", x)\nयावत् (x < 3)\nदर्श(\"{}\", 0)";
+        let result = transpile_to_rust(src).unwrap();
+        assert!(
+            result.contains("    loop {\n        println!(\"{}\", x);\n        if !(x < 3) {\n            break;\n        }\n    }\n"),
+            "{}",
+            result
+        );
+        assert_compiles(&result);
+        assert!(transpile_to_rust("कुरु:\n    दर्श(\"{}\", 1)\nदर्श(\"{}\", 2)").is_err());
+        assert!(transpile_to_rust("कुरु:\n    दर्श(\"{}\", 1)").is_err());
+    }
+
+    #[test]
+    fn test_endless_loop_becomes_rust_loop() {
+        let result = transpile_to_rust("चक्र:\n    दर्श(\"{}\", 1)\n    विरम").unwrap();
+        assert!(result.contains("    loop {\n        println!(\"{}\", 1);\n        break;\n    }\n"), "{}", result);
+        assert_compiles(&result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_assigning_a_comparison() {
+        let result = transpile_to_rust("x = y == z").unwrap();
+        assert!(result.contains("let x = y == z;"), "{}", result);
+    }
+
+    #[test]
+    fn test_for_loop_over_range() {
+        let paanini_code = r#"
+सूची = [1, 2]
+परिभ्रमण i in परिधि(5):
+    दर्श(i)
+परिभ्रमण j in परिधि(2, 8, 2):
+    दर्श(j)
+परिभ्रमण k in परिधि(5, 0, -1):
+    दर्श(k)
+परिभ्रमण x in सूची:
+    दर्श(x)
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        assert!(result.contains("for i in 0..5 {"));
+        assert!(result.contains("for j in (2..8).step_by(2) {"));
+        assert!(result.contains("for k in ((0) + 1..=5).rev().step_by(1) {"));
+        assert!(result.contains("for x in suuchii {"));
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_for_loop_compiles() {
+        let result = transpile_to_rust("परिभ्रमण i in परिधि(5):\n    दर्श(i)").unwrap();
+        assert_prints(&result, "0\n1\n2\n3\n4\n");
+        let result = transpile_to_rust("परिभ्रमण k in परिधि(5, 0, -2):\n    दर्श(k)").unwrap();
+        assert_prints(&result, "5\n3\n1\n");
+    }
+
+    #[test]
+    fn test_function_with_return() {
+        let paanini_code = r#"
+कार्य जोड़(a, b):
+    प्रतिफल a + b
+कार्य नमस्ते(नाम):
+    प्रतिफल "नमस्ते"
+परिणाम = जोड़(2.5, 3.5)
+दर्श(जोड़(2, जोड़(-1, परिणाम)))
+दर्श(नमस्ते(3))
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        assert!(result.contains("fn jora(a: f64, b: f64) -> f64 {"), "{}", result);
+        assert!(result.contains("return a + b;"));
+        assert!(result.contains("fn namaste(naama: f64) -> String {"));
+        assert!(result.contains("return \"नमस्ते\".to_string();"));
+        assert!(result.contains("let parinaama = jora(2.5, 3.5);"));
+        assert!(result.contains("println!(\"{}\", jora(2.0, jora(-1.0, parinaama)));"), "{}", result);
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_devanagari_identifiers_become_ascii() {
+        let paanini_code = r#"
+!! संख्या is kept in comments and strings
+संख्या = 4
+वर्गः = संख्या * संख्या
+!! `in` is a keyword and `samkhyaa` is taken, so both get a suffix
+इन् = वर्गः + 1
+samkhyaa = 0
+दर्श("{} {} {}", संख्या, वर्गः, इन्)
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        assert!(result.contains("// संख्या is kept"), "{}", result);
+        assert!(result.contains("let samkhyaa_2 = 4;"), "{}", result);
+        assert!(result.contains("let vargah = samkhyaa_2 * samkhyaa_2;"));
+        assert!(result.contains("let in_2 = vargah + 1;"));
+        assert!(result.contains(r#"println!("{} {} {}", samkhyaa_2, vargah, in_2);"#));
+        assert!(result.lines().all(|l| l.split("//").next().unwrap().split('"').step_by(2).all(str::is_ascii)));
+        assert_compiles(&result);
+        assert_eq!(transliterate("जोड़"), transliterate("ज\u{094B}\u{095C}"));
+    }
+
+    #[test]
+    fn test_nested_blocks_are_balanced() {
+        let paanini_code = r#"
+x = 0
+यावत् x < 3:
+    यदि x == 1:
+        y = 1
+    अन्यथा:
+        y = 2
+        यदि y > 1:
+            z = 3
+    x = x + 1
+w = x
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        let expected = "fn main() {
+    let x = 0;
+    while x < 3 {
+        if x == 1 {
+            let y = 1;
+        } else {
+            let y = 2;
+            if y > 1 {
+                let z = 3;
+            }
+        }
+        let x = x + 1;
+    }
+    let w = x;
+}
+";
+        assert_eq!(result, expected);
+        assert_eq!(result.matches('{').count(), result.matches('}').count());
+        assert_compiles(&result);
+    }
+
+    #[test]
+    fn test_raw_rust_directive_is_emitted_verbatim() {
+        let paanini_code = r#"
+x = 5
+यदि x > 1:
+    z = x
+    !! rust: let squares: Vec<i32> = (1..4).map(|n| n * n).collect();
+    !! साधारण टिप्पणी
+y = x
+        "#;
+
+        let result = transpile_to_rust(paanini_code).unwrap();
+        assert!(result.contains(
+            "        let squares: Vec<i32> = (1..4).map(|n| n * n).collect();\n        // साधारण टिप्पणी\n    }\n"
+        ));
+
+        let interpreted = crate::interpreter::Interpreter::default().run(paanini_code);
+        assert!(interpreted.errors.is_empty());
+    }
+
+    #[test]
+    fn test_raw_rust_directive_must_be_a_statement() {
+        assert!(transpile_to_rust("!! rust: if true {").is_err());
+        assert!(transpile_to_rust("!! rust:").is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::interpreter::{Interpreter, NoInput};
+
+/// What `run` hands back to JavaScript: `{ output, errors }`.
+#[wasm_bindgen(getter_with_clone)]
+pub struct WasmRun {
+    pub output: String,
+    pub errors: Vec<String>,
+}
+
+/// Run a program in a fresh interpreter, as `/api/run` does. There is no
+/// stdin, file system or environment in the browser, so `निवेश`, `पठ`,
+/// `लिख` and `पर्यावरण` report errors.
+#[wasm_bindgen]
+pub fn run(code: &str) -> WasmRun {
+    let mut interpreter = Interpreter::default();
+    interpreter.set_input_provider(Arc::new(NoInput));
+    interpreter.set_args(&[]);
+    let result = interpreter.run(code);
+    WasmRun {
+        output: result.output,
+        errors: result.errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_returns_output_and_errors() {
+        let result = run("दर्श(\"नमस्ते\")\nदर्श(अज्ञात)");
+        assert_eq!(result.output, "नमस्ते\n");
+        assert_eq!(result.errors.len(), 1);
+    }
+}
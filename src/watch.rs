@@ -0,0 +1,104 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+/// How long a file must stay unchanged before it is re-run, so one save that
+/// an editor performs as several writes (or a rename) runs the file once.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Call `run` now and again after every change to `path`. Blocks until the
+/// process is interrupted.
+pub fn watch(path: &Path, mut run: impl FnMut()) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    // Watch the directory: editors often save by replacing the file, which
+    // would end a watch on the file itself.
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    run();
+    rerun_on_change(path, &rx, DEBOUNCE, run);
+    Ok(())
+}
+
+/// Call `run` once for each burst of events on `events` that change `path`;
+/// a burst ends when no event arrives for `debounce`. Returns once the
+/// channel closes.
+pub fn rerun_on_change(path: &Path, events: &Receiver<notify::Result<Event>>, debounce: Duration, mut run: impl FnMut()) {
+    let changes_path = |event: &notify::Result<Event>| match event {
+        // Reading the file to run it is an access event; don't loop on it.
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|p| p.file_name() == path.file_name())
+        }
+        Err(_) => false,
+    };
+    while let Ok(event) = events.recv() {
+        if !changes_path(&event) {
+            continue;
+        }
+        // Swallow the rest of the burst.
+        while events.recv_timeout(debounce).is_ok() {}
+        run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, ModifyKind};
+    use std::path::PathBuf;
+    use std::thread;
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn test_each_burst_of_changes_reruns_once() {
+        let debounce = Duration::from_millis(50);
+        let modify = EventKind::Modify(ModifyKind::Data(DataChange::Any));
+        let (tx, rx) = mpsc::channel();
+        tx.send(event(EventKind::Access(AccessKind::Any), "/w/prog.paanini")).unwrap();
+        tx.send(event(modify, "/w/other.paanini")).unwrap();
+        tx.send(event(modify, "/w/prog.paanini")).unwrap();
+        tx.send(event(EventKind::Create(CreateKind::File), "/w/prog.paanini")).unwrap();
+        let later = thread::spawn(move || {
+            thread::sleep(debounce * 4);
+            tx.send(event(modify, "/w/prog.paanini")).unwrap();
+        });
+
+        let mut runs = 0;
+        rerun_on_change(Path::new("/w/prog.paanini"), &rx, debounce, || runs += 1);
+        later.join().unwrap();
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn test_saving_the_file_triggers_a_rerun() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("prog.paanini");
+        std::fs::write(&file, "दर्श(1)\n").unwrap();
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).unwrap();
+        watcher.watch(dir.path(), RecursiveMode::NonRecursive).unwrap();
+
+        let writer = {
+            let file = file.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                std::fs::write(&file, "दर्श(2)\n").unwrap();
+                thread::sleep(Duration::from_millis(500));
+                drop(watcher);
+            })
+        };
+        let mut outputs = Vec::new();
+        rerun_on_change(&file, &rx, Duration::from_millis(50), || {
+            let src = std::fs::read_to_string(&file).unwrap();
+            outputs.push(crate::interpreter::Interpreter::default().run(&src).output);
+        });
+        writer.join().unwrap();
+        assert_eq!(outputs, ["2\n"]);
+    }
+}